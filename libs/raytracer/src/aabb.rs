@@ -0,0 +1,236 @@
+
+use crate::*;
+
+use ray::Ray;
+use point::Point3;
+use interval::Interval;
+
+/// Axis-aligned bounding box, stored as one interval per axis
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+  pub x: Interval,
+  pub y: Interval,
+  pub z: Interval,
+}
+
+impl Aabb {
+  pub const fn new(x: Interval, y: Interval, z: Interval) -> Self {
+    Self { x, y, z, }
+  }
+
+  /// Produces the box with a and b as opposite corners, in any order
+  pub fn from_points(a: Point3, b: Point3) -> Self {
+    Self::new(
+      Interval::new(a.x.min(b.x), a.x.max(b.x)),
+      Interval::new(a.y.min(b.y), a.y.max(b.y)),
+      Interval::new(a.z.min(b.z), a.z.max(b.z)),
+    )
+  }
+
+  /// Produces the tightest box containing both a and b
+  pub fn surrounding(a: Aabb, b: Aabb) -> Self {
+    Self::new(
+      Interval::enclosing(a.x, b.x),
+      Interval::enclosing(a.y, b.y),
+      Interval::enclosing(a.z, b.z),
+    )
+  }
+
+  pub fn axis(&self, n: usize) -> Interval {
+    match n {
+      0 => self.x,
+      1 => self.y,
+      2 => self.z,
+      _ => panic!("axis out of bounds for Aabb"),
+    }
+  }
+
+  pub fn longest_axis(&self) -> usize {
+    let (x, y, z) = (self.x.size(), self.y.size(), self.z.size());
+    if x > y && x > z {
+      0
+    } else if y > z {
+      1
+    } else {
+      2
+    }
+  }
+
+  /// Produces the box widened so no axis is thinner than delta; flat boxes
+  /// would otherwise be missed by the slab test
+  pub fn padded(&self, delta: f64) -> Self {
+    let pad = |axis: Interval| if axis.size() < delta { axis.expand(delta) } else { axis };
+    Self::new(pad(self.x), pad(self.y), pad(self.z))
+  }
+
+  pub fn centroid(&self) -> Point3 {
+    Point3::new(
+      0.5 * (self.x.min + self.x.max),
+      0.5 * (self.y.min + self.y.max),
+      0.5 * (self.z.min + self.z.max),
+    )
+  }
+
+  /// Produces whether every side of the box lies at a finite position; it
+  /// does not for unbounded boxes, nor for empty ones
+  pub fn is_finite(&self) -> bool {
+    [self.x, self.y, self.z].iter().all(|axis| axis.min.is_finite() && axis.max.is_finite())
+  }
+
+  pub fn surface_area(&self) -> f64 {
+    let (x, y, z) = (self.x.size(), self.y.size(), self.z.size());
+    if x < 0.0 || y < 0.0 || z < 0.0 {
+      // empty box
+      0.0
+    } else {
+      2.0 * (x*y + y*z + z*x)
+    }
+  }
+
+  /// Produces whether the ray passes through the box within ray_i (slab method)
+  pub fn hit(&self, ray: &Ray, ray_i: Interval) -> bool {
+    self.clip(ray, ray_i).is_some()
+  }
+
+  /// Produces the part of ray_i for which the ray is inside the box, if any
+  pub fn clip(&self, ray: &Ray, mut ray_i: Interval) -> Option<Interval> {
+    let origin = ray.position();
+    let direction = ray.direction();
+
+    for n in 0..3 {
+      let axis = self.axis(n);
+      let inv_d = 1.0 / direction[n];
+      let t0 = (axis.min - origin[n]) * inv_d;
+      let t1 = (axis.max - origin[n]) * inv_d;
+      let (t0, t1) = if t0 < t1 { (t0, t1) } else { (t1, t0) };
+
+      if t0 > ray_i.min {
+        ray_i.min = t0;
+      }
+      if t1 < ray_i.max {
+        ray_i.max = t1;
+      }
+      if ray_i.max <= ray_i.min {
+        return None;
+      }
+    }
+
+    Some(ray_i)
+  }
+}
+
+pub const EMPTY: Aabb = Aabb::new(interval::EMPTY, interval::EMPTY, interval::EMPTY);
+pub const UNIVERSE: Aabb = Aabb::new(interval::UNIVERSE, interval::UNIVERSE, interval::UNIVERSE);
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use rstest::*;
+
+  mod aabb {
+    use super::*;
+
+    use vector::Vector3;
+
+    #[rstest]
+    #[case(Point3::new(0.0, 0.0, 0.0), Point3::new(1.0, 2.0, 3.0))]
+    #[case(Point3::new(1.0, 2.0, 3.0), Point3::new(0.0, 0.0, 0.0))]
+    #[case(Point3::new(1.0, 0.0, 3.0), Point3::new(0.0, 2.0, 0.0))]
+    fn from_points(#[case] a: Point3, #[case] b: Point3) {
+      let bbox = Aabb::from_points(a, b);
+      assert_eq!(bbox.x, Interval::new(0.0, 1.0));
+      assert_eq!(bbox.y, Interval::new(0.0, 2.0));
+      assert_eq!(bbox.z, Interval::new(0.0, 3.0));
+    }
+
+    #[rstest]
+    fn surrounding() {
+      let a = Aabb::from_points(Point3::new(0.0, 0.0, 0.0), Point3::new(1.0, 1.0, 1.0));
+      let b = Aabb::from_points(Point3::new(-1.0, 0.5, 0.5), Point3::new(0.5, 3.0, 0.5));
+      let expected = Aabb::from_points(Point3::new(-1.0, 0.0, 0.0), Point3::new(1.0, 3.0, 1.0));
+      assert_eq!(Aabb::surrounding(a, b), expected);
+      assert_eq!(Aabb::surrounding(EMPTY, a), a);
+    }
+
+    #[rstest]
+    #[case(0)]
+    #[case(1)]
+    #[case(2)]
+    #[should_panic]
+    #[case(3)]
+    fn axis(#[case] n: usize) {
+      let bbox = Aabb::from_points(Point3::new(0.0, 0.0, 0.0), Point3::new(1.0, 2.0, 3.0));
+      assert_eq!(bbox.axis(n), Interval::new(0.0, n as f64 + 1.0));
+    }
+
+    #[rstest]
+    #[case(Point3::new(3.0, 2.0, 1.0), 0)]
+    #[case(Point3::new(1.0, 3.0, 2.0), 1)]
+    #[case(Point3::new(1.0, 2.0, 3.0), 2)]
+    #[case(Point3::new(1.0, 1.0, 1.0), 2)]
+    fn longest_axis(#[case] corner: Point3, #[case] expected: usize) {
+      let bbox = Aabb::from_points(Point3::default(), corner);
+      assert_eq!(bbox.longest_axis(), expected);
+    }
+
+    #[rstest]
+    fn padded() {
+      let flat = Aabb::from_points(Point3::new(0.0, 0.0, 0.0), Point3::new(1.0, 1.0, 0.0));
+      let padded = flat.padded(0.5);
+      assert_eq!(padded.x, flat.x);
+      assert_eq!(padded.y, flat.y);
+      assert_eq!(padded.z, Interval::new(-0.25, 0.25));
+
+      let ray = Ray::new(Point3::new(0.5, 0.5, -1.0), Vector3::new(0.0, 0.0, 1.0));
+      assert!(!flat.hit(&ray, Interval::new(0.001, 10.0)));
+      assert!(padded.hit(&ray, Interval::new(0.001, 10.0)));
+    }
+
+    #[rstest]
+    fn centroid() {
+      let bbox = Aabb::from_points(Point3::new(-1.0, 0.0, 2.0), Point3::new(1.0, 4.0, 3.0));
+      assert_eq!(bbox.centroid(), Point3::new(0.0, 2.0, 2.5));
+    }
+
+    #[rstest]
+    #[case(Aabb::from_points(Point3::new(0.0, 0.0, 0.0), Point3::new(1.0, 2.0, 3.0)), true)]
+    #[case(Aabb::new(interval::UNIVERSE, Interval::new(0.0, 1.0), Interval::new(0.0, 1.0)), false)]
+    #[case(Aabb::new(Interval::new(0.0, f64::INFINITY), Interval::new(0.0, 1.0), Interval::new(0.0, 1.0)), false)]
+    #[case(UNIVERSE, false)]
+    #[case(EMPTY, false)]
+    fn is_finite(#[case] bbox: Aabb, #[case] expected: bool) {
+      assert_eq!(bbox.is_finite(), expected);
+    }
+
+    #[rstest]
+    #[case(Aabb::from_points(Point3::new(0.0, 0.0, 0.0), Point3::new(1.0, 1.0, 1.0)), 6.0)]
+    #[case(Aabb::from_points(Point3::new(0.0, 0.0, 0.0), Point3::new(1.0, 2.0, 3.0)), 22.0)]
+    #[case(Aabb::from_points(Point3::new(0.0, 0.0, 0.0), Point3::new(1.0, 1.0, 0.0)), 2.0)]
+    #[case(EMPTY, 0.0)]
+    fn surface_area(#[case] bbox: Aabb, #[case] expected: f64) {
+      assert_eq!(bbox.surface_area(), expected);
+    }
+
+    #[rstest]
+    #[case(Ray::new(Point3::new(0.0, 0.0, -5.0), Vector3::new(0.0, 0.0, 1.0)), true)]
+    #[case(Ray::new(Point3::new(0.0, 0.0, -5.0), Vector3::new(0.0, 0.0, -1.0)), false)]
+    #[case(Ray::new(Point3::new(0.0, 5.0, -5.0), Vector3::new(0.0, 0.0, 1.0)), false)]
+    #[case(Ray::new(Point3::new(-5.0, -5.0, -5.0), Vector3::new(1.0, 1.0, 1.0)), true)]
+    #[case(Ray::new(Point3::new(0.0, 0.0, 0.0), Vector3::new(1.0, 0.0, 0.0)), true)]
+    #[case(Ray::new(Point3::new(0.0, 0.0, -50.0), Vector3::new(0.0, 0.0, 1.0)), false)]
+    fn hit(#[case] ray: Ray, #[case] expected: bool) {
+      let bbox = Aabb::from_points(Point3::new(-1.0, -1.0, -1.0), Point3::new(1.0, 1.0, 1.0));
+      assert_eq!(bbox.hit(&ray, Interval::new(0.001, 10.0)), expected);
+    }
+
+    #[rstest]
+    #[case(Point3::new(0.0, 0.0, -5.0), Some(Interval::new(4.0, 6.0)))]
+    #[case(Point3::new(0.0, 0.0, 0.0), Some(Interval::new(0.001, 1.0)))]
+    #[case(Point3::new(0.0, 0.0, -12.0), None)]
+    fn clip(#[case] position: Point3, #[case] expected: Option<Interval>) {
+      let bbox = Aabb::from_points(Point3::new(-1.0, -1.0, -1.0), Point3::new(1.0, 1.0, 1.0));
+      let ray = Ray::new(position, Vector3::new(0.0, 0.0, 1.0));
+      assert_eq!(bbox.clip(&ray, Interval::new(0.001, 10.0)), expected);
+    }
+  }
+}
@@ -0,0 +1,172 @@
+
+#[allow(unused_imports)]
+use crate::*;
+
+use colour::Colour;
+
+/// Settings that let each pixel stop sampling once its estimate is good
+/// enough: after min_samples, a pixel stops when the standard error of its
+/// mean luminance falls below threshold times that mean, and always by
+/// max_samples
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Adaptive {
+  pub min_samples: usize,
+  pub max_samples: usize,
+  // relative standard error a pixel's mean luminance must reach
+  pub threshold: f64,
+}
+
+impl Adaptive {
+  pub fn new(min_samples: usize, max_samples: usize, threshold: f64) -> Self {
+    let min_samples = min_samples.max(2);
+    Self { min_samples, max_samples: max_samples.max(min_samples), threshold, }
+  }
+
+  /// Produces whether a pixel with stats has been sampled enough
+  pub fn is_converged(&self, stats: &PixelStats) -> bool {
+    stats.count() >= self.max_samples
+      || (stats.count() >= self.min_samples && stats.relative_error() < self.threshold)
+  }
+}
+
+impl Default for Adaptive {
+  fn default() -> Self {
+    Self::new(16, 1024, 0.01)
+  }
+}
+
+/// Running sum of a pixel's samples, with the mean and variance of their
+/// luminance kept by Welford's method
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct PixelStats {
+  count: usize,
+  sum: Colour,
+  mean: f64,
+  // sum of squared differences from the mean
+  m2: f64,
+}
+
+impl PixelStats {
+  pub fn add(&mut self, colour: Colour) {
+    self.count += 1;
+    self.sum += colour;
+    let luminance = colour::luminance(colour);
+    let delta = luminance - self.mean;
+    self.mean += delta / self.count as f64;
+    self.m2 += delta * (luminance - self.mean);
+  }
+
+  pub fn count(&self) -> usize {
+    self.count
+  }
+
+  pub fn sum(&self) -> Colour {
+    self.sum
+  }
+
+  pub fn mean(&self) -> Colour {
+    if self.count == 0 { Colour::default() } else { self.sum / self.count as f64 }
+  }
+
+  /// Produces the sample variance of the luminance
+  pub fn variance(&self) -> f64 {
+    if self.count < 2 { 0.0 } else { self.m2 / (self.count - 1) as f64 }
+  }
+
+  /// Produces the standard error of the mean luminance relative to it; dark
+  /// pixels are measured against a floor so they do not sample forever
+  pub fn relative_error(&self) -> f64 {
+    if self.count == 0 {
+      return f64::INFINITY;
+    }
+    let standard_error = (self.variance() / self.count as f64).sqrt();
+    standard_error / self.mean.max(1e-3)
+  }
+}
+
+/// Produces the colour showing a pixel that took count of up to max samples,
+/// from dark blue for few through red to pale yellow for max
+pub fn heatmap_colour(count: usize, max: usize) -> Colour {
+  let t = if max == 0 { 0.0 } else { (count as f64 / max as f64).clamp(0.0, 1.0) };
+  let stops = [
+    Colour::new(0.0, 0.0, 0.2),
+    Colour::new(0.1, 0.1, 0.8),
+    Colour::new(0.9, 0.1, 0.1),
+    Colour::new(1.0, 1.0, 0.6),
+  ];
+  let scaled = t * (stops.len() - 1) as f64;
+  let n = (scaled as usize).min(stops.len() - 2);
+  let f = scaled - n as f64;
+  (1.0 - f) * stops[n] + f * stops[n + 1]
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use rstest::*;
+
+  mod adaptive {
+    use super::*;
+
+    #[rstest]
+    fn new() {
+      let adaptive = Adaptive::new(0, 1, 0.05);
+      assert_eq!(adaptive.min_samples, 2);
+      assert_eq!(adaptive.max_samples, 2);
+      assert_eq!(adaptive.threshold, 0.05);
+    }
+
+    #[rstest]
+    fn is_converged() {
+      let adaptive = Adaptive::new(4, 8, 0.05);
+      let (mut flat, mut noisy) = (PixelStats::default(), PixelStats::default());
+      for n in 0..8 {
+        assert!(!adaptive.is_converged(&flat) || n >= 4);
+        assert!(!adaptive.is_converged(&noisy));
+        flat.add(Colour::new(0.5, 0.5, 0.5));
+        noisy.add(Colour::new(1.0, 1.0, 1.0) * (n % 2) as f64);
+      }
+      assert!(adaptive.is_converged(&flat));
+      // the cap stops even the noisiest pixel
+      assert!(adaptive.is_converged(&noisy));
+    }
+  }
+
+  mod pixel_stats {
+    use super::*;
+
+    #[rstest]
+    fn add() {
+      let mut stats = PixelStats::default();
+      assert_eq!(stats.relative_error(), f64::INFINITY);
+      for value in [1.0, 2.0, 3.0, 4.0] {
+        stats.add(Colour::new(value, value, value));
+      }
+      assert_eq!(stats.count(), 4);
+      assert_eq!(stats.sum(), Colour::new(10.0, 10.0, 10.0));
+      assert_eq!(stats.mean(), Colour::new(2.5, 2.5, 2.5));
+      assert!((stats.variance() - 5.0 / 3.0).abs() < 1e-12);
+      let expected = (5.0 / 3.0 / 4.0_f64).sqrt() / 2.5;
+      assert!((stats.relative_error() - expected).abs() < 1e-12);
+    }
+
+    #[rstest]
+    fn dark() {
+      let mut stats = PixelStats::default();
+      stats.add(Colour::default());
+      stats.add(Colour::default());
+      assert_eq!(stats.variance(), 0.0);
+      assert_eq!(stats.relative_error(), 0.0);
+    }
+  }
+
+  #[rstest]
+  fn heatmap_colour() {
+    assert_eq!(super::heatmap_colour(0, 100), Colour::new(0.0, 0.0, 0.2));
+    assert_eq!(super::heatmap_colour(100, 100), Colour::new(1.0, 1.0, 0.6));
+    assert_eq!(super::heatmap_colour(500, 100), Colour::new(1.0, 1.0, 0.6));
+    assert_eq!(super::heatmap_colour(0, 0), Colour::new(0.0, 0.0, 0.2));
+    let half = super::heatmap_colour(50, 100);
+    assert!(half.z < 0.8 && half.x > 0.1);
+  }
+}
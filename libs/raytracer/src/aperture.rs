@@ -0,0 +1,175 @@
+
+use rand::Rng;
+
+use crate::*;
+
+use std::f64::consts::PI;
+use std::sync::Arc;
+
+/// Shape of the lens opening rays are sampled from, which sets the bokeh shape
+#[derive(Debug, Default, Clone, PartialEq)]
+pub enum Aperture {
+  #[default]
+  Circular,
+  /// Regular polygon with `blades` sides, rotated by `rotation` radians
+  Polygon { blades: usize, rotation: f64 },
+  /// Arbitrary shape weighted by a greyscale mask
+  Mask(Arc<ApertureMask>),
+}
+
+impl Aperture {
+  /// Produces a point on the aperture, scaled to fit inside [-1, 1] on both axes
+  pub fn sample(&self, rng: &mut impl Rng) -> (f64, f64) {
+    match self {
+      Self::Circular => {
+        let p = vector::Vector3::random_in_unit_disk(rng);
+        (p.x, p.y)
+      },
+      Self::Polygon { blades, rotation } => {
+        let blades = (*blades).max(3);
+        // each blade spans an equal triangle fanned out from the center
+        let k = rng.gen_range(0..blades);
+        let theta = 2.0 * PI / blades as f64;
+        let (a, b) = (rotation + theta * k as f64, rotation + theta * (k + 1) as f64);
+        let (mut s, mut t) = (rng.gen::<f64>(), rng.gen::<f64>());
+        if s + t > 1.0 {
+          s = 1.0 - s;
+          t = 1.0 - t;
+        }
+        (s * a.cos() + t * b.cos(), s * a.sin() + t * b.sin())
+      },
+      Self::Mask(mask) => mask.sample(rng),
+    }
+  }
+}
+
+/// Greyscale image whose pixel weights give the relative density of the aperture
+#[derive(Debug, Clone, PartialEq)]
+pub struct ApertureMask {
+  width: usize,
+  height: usize,
+  // running sum of pixel weights, row major, top row first
+  cdf: Vec<f64>,
+}
+
+impl ApertureMask {
+  pub fn new(width: usize, height: usize, weights: &[f64]) -> Result<Self, RaytracerError> {
+    if width == 0 || height == 0 || weights.len() != width * height {
+      return Err(RaytracerError::ApertureMaskError);
+    }
+
+    let cdf: Vec<f64> = weights
+      .iter()
+      .scan(0.0, |total, weight| {
+        *total += weight.max(0.0);
+        Some(*total)
+      })
+      .collect();
+    if cdf.last().is_none_or(|total| *total <= 0.0) {
+      return Err(RaytracerError::ApertureMaskError);
+    }
+
+    Ok(Self { width, height, cdf, })
+  }
+
+  pub fn width(&self) -> usize {
+    self.width
+  }
+
+  pub fn height(&self) -> usize {
+    self.height
+  }
+
+  fn sample(&self, rng: &mut impl Rng) -> (f64, f64) {
+    let total = self.cdf[self.cdf.len() - 1];
+    let target = rng.gen::<f64>() * total;
+    let index = self.cdf.partition_point(|&c| c <= target).min(self.cdf.len() - 1);
+    let (i, j) = (index % self.width, index / self.width);
+
+    // jitter within the chosen pixel, then centre on the origin so that the
+    // longer side of the mask spans [-1, 1]
+    let x = i as f64 + rng.gen::<f64>();
+    let y = j as f64 + rng.gen::<f64>();
+    let scale = 2.0 / self.width.max(self.height) as f64;
+    (
+      (x - self.width as f64 / 2.0) * scale,
+      (self.height as f64 / 2.0 - y) * scale,
+    )
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use rstest::*;
+
+  use rand::SeedableRng;
+  use rand_chacha::ChaCha8Rng;
+
+  mod aperture {
+    use super::*;
+
+    #[rstest]
+    fn circular() {
+      let mut rng = ChaCha8Rng::seed_from_u64(4);
+      for _ in 0..100 {
+        let (x, y) = Aperture::Circular.sample(&mut rng);
+        assert!(x*x + y*y < 1.0);
+      }
+    }
+
+    #[rstest]
+    #[case(3, 0.0)]
+    #[case(5, 0.3)]
+    #[case(6, PI / 6.0)]
+    #[case(8, 1.0)]
+    fn polygon(#[case] blades: usize, #[case] rotation: f64) {
+      let mut rng = ChaCha8Rng::seed_from_u64(4);
+      let aperture = Aperture::Polygon { blades, rotation };
+      let theta = 2.0 * PI / blades as f64;
+      for _ in 0..100 {
+        let (x, y) = aperture.sample(&mut rng);
+        // inside polygon iff on the inner side of every edge
+        for k in 0..blades {
+          let (a, b) = (rotation + theta * k as f64, rotation + theta * (k + 1) as f64);
+          let (ex, ey) = (b.cos() - a.cos(), b.sin() - a.sin());
+          let (px, py) = (x - a.cos(), y - a.sin());
+          assert!(ex*py - ey*px >= -1e-12);
+        }
+      }
+    }
+
+    #[rstest]
+    fn mask() {
+      let mut rng = ChaCha8Rng::seed_from_u64(4);
+      // only the top right quadrant is open
+      let weights = [0.0, 1.0, 0.0, 0.0];
+      let aperture = Aperture::Mask(Arc::new(ApertureMask::new(2, 2, &weights).unwrap()));
+      for _ in 0..100 {
+        let (x, y) = aperture.sample(&mut rng);
+        assert!((0.0..=1.0).contains(&x));
+        assert!((0.0..=1.0).contains(&y));
+      }
+    }
+  }
+
+  mod aperture_mask {
+    use super::*;
+
+    #[rstest]
+    #[case(2, 2, vec![1.0, 0.0, 0.5, 0.25], true)]
+    #[case(3, 1, vec![0.0, 0.0, 1.0], true)]
+    #[case(2, 2, vec![1.0, 1.0, 1.0], false)]
+    #[case(0, 0, vec![], false)]
+    #[case(2, 1, vec![0.0, 0.0], false)]
+    #[case(2, 1, vec![-1.0, 0.0], false)]
+    fn new(#[case] width: usize, #[case] height: usize, #[case] weights: Vec<f64>, #[case] expected: bool) {
+      let mask = ApertureMask::new(width, height, &weights);
+      assert_eq!(mask.is_ok(), expected);
+      if let Ok(mask) = mask {
+        assert_eq!(mask.width(), width);
+        assert_eq!(mask.height(), height);
+      }
+    }
+  }
+}
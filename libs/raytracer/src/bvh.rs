@@ -0,0 +1,309 @@
+
+use crate::*;
+
+use ray::Ray;
+use aabb::Aabb;
+use interval::Interval;
+use hittable::{Hittable, HitRecord, VecOfHittable};
+
+// relative costs used by the surface area heuristic
+const TRAVERSAL_COST: f64 = 1.0;
+const INTERSECTION_COST: f64 = 1.0;
+// largest number of objects a leaf may hold before it must be split
+const MAX_LEAF_SIZE: usize = 4;
+
+enum BvhNode {
+  Leaf {
+    bbox: Aabb,
+    objects: VecOfHittable,
+  },
+  Branch {
+    bbox: Aabb,
+    left: Box<BvhNode>,
+    right: Box<BvhNode>,
+  },
+}
+
+impl BvhNode {
+  fn build(mut items: Vec<(Aabb, Box<dyn Hittable>)>) -> Self {
+    let bbox = items
+      .iter()
+      .fold(aabb::EMPTY, |bbox, (object_bbox, _)| Aabb::surrounding(bbox, *object_bbox));
+
+    if items.len() <= 1 {
+      return Self::leaf(bbox, items);
+    }
+
+    let leaf_cost = INTERSECTION_COST * items.len() as f64;
+    let (axis, index) = match Self::best_split(&mut items, &bbox) {
+      Some((_, cost)) if items.len() <= MAX_LEAF_SIZE && leaf_cost <= cost => {
+        return Self::leaf(bbox, items);
+      },
+      Some((split, _)) => split,
+      None if items.len() <= MAX_LEAF_SIZE => return Self::leaf(bbox, items),
+      // every centroid coincides, so no split is better than another
+      None => (bbox.longest_axis(), items.len() / 2),
+    };
+    sort_by_centroid(&mut items, axis);
+    let right = items.split_off(index);
+
+    Self::Branch {
+      bbox,
+      left: Box::new(Self::build(items)),
+      right: Box::new(Self::build(right)),
+    }
+  }
+
+  fn leaf(bbox: Aabb, items: Vec<(Aabb, Box<dyn Hittable>)>) -> Self {
+    Self::Leaf {
+      bbox,
+      objects: items.into_iter().map(|(_, object)| object).collect(),
+    }
+  }
+
+  /// Produces the ((axis, index), cost) of the cheapest split, where objects
+  /// [0, index) sorted along axis go left and the rest go right
+  fn best_split(items: &mut [(Aabb, Box<dyn Hittable>)], bbox: &Aabb) -> Option<((usize, usize), f64)> {
+    let n = items.len();
+    let parent_area = bbox.surface_area();
+    let centroids = items
+      .iter()
+      .fold(aabb::EMPTY, |bounds, (object_bbox, _)| {
+        let centroid = object_bbox.centroid();
+        Aabb::surrounding(bounds, Aabb::from_points(centroid, centroid))
+      });
+
+    let mut best: Option<((usize, usize), f64)> = None;
+    for axis in 0..3 {
+      if centroids.axis(axis).size() <= 0.0 {
+        continue;
+      }
+      sort_by_centroid(items, axis);
+
+      // right_areas[i] is the area of the box around items[i..]
+      let mut right_areas = vec![0.0; n];
+      let mut right_bbox = aabb::EMPTY;
+      for i in (1..n).rev() {
+        right_bbox = Aabb::surrounding(right_bbox, items[i].0);
+        right_areas[i] = right_bbox.surface_area();
+      }
+
+      let mut left_bbox = aabb::EMPTY;
+      for index in 1..n {
+        left_bbox = Aabb::surrounding(left_bbox, items[index - 1].0);
+        let left_cost = left_bbox.surface_area() * index as f64;
+        let right_cost = right_areas[index] * (n - index) as f64;
+        let cost = if parent_area > 0.0 {
+          TRAVERSAL_COST + INTERSECTION_COST * (left_cost + right_cost) / parent_area
+        } else {
+          TRAVERSAL_COST + INTERSECTION_COST * n as f64 / 2.0
+        };
+
+        if best.is_none_or(|(_, best_cost)| cost < best_cost) {
+          best = Some(((axis, index), cost));
+        }
+      }
+    }
+
+    best
+  }
+
+  fn bbox(&self) -> Aabb {
+    match self {
+      Self::Leaf { bbox, .. } | Self::Branch { bbox, .. } => *bbox,
+    }
+  }
+}
+
+impl Hittable for BvhNode {
+  fn hit(&self, ray: &Ray, ray_i: Interval, record: &mut HitRecord) -> bool {
+    if !self.bbox().hit(ray, ray_i) {
+      return false;
+    }
+
+    match self {
+      Self::Leaf { objects, .. } => objects.hit(ray, ray_i, record),
+      Self::Branch { left, right, .. } => {
+        let hit_left = left.hit(ray, ray_i, record);
+        let closest_so_far = if hit_left { record.d } else { ray_i.max };
+        let hit_right = right.hit(ray, Interval::new(ray_i.min, closest_so_far), record);
+        hit_left || hit_right
+      },
+    }
+  }
+
+  fn bounding_box(&self) -> Aabb {
+    self.bbox()
+  }
+}
+
+fn sort_by_centroid(items: &mut [(Aabb, Box<dyn Hittable>)], axis: usize) {
+  items.sort_by(|(a, _), (b, _)| a.centroid()[axis].total_cmp(&b.centroid()[axis]));
+}
+
+/// Bounding volume hierarchy over a set of hittables, split using the surface
+/// area heuristic. Objects without a finite bounding box, such as infinite
+/// planes, have no centroid to sort by, so they are kept out of the tree and
+/// tested against every ray alongside it
+pub struct Bvh {
+  root: Option<BvhNode>,
+  unbounded: VecOfHittable,
+}
+
+impl Bvh {
+  pub fn new(objects: VecOfHittable) -> Self {
+    let (items, unbounded): (Vec<_>, Vec<_>) = objects
+      .into_iter()
+      .map(|object| (object.bounding_box(), object))
+      .partition(|(bbox, _)| bbox.is_finite());
+    let root = if items.is_empty() { None } else { Some(BvhNode::build(items)) };
+    let unbounded = unbounded.into_iter().map(|(_, object)| object).collect();
+    Self { root, unbounded, }
+  }
+}
+
+impl From<VecOfHittable> for Bvh {
+  fn from(objects: VecOfHittable) -> Self {
+    Self::new(objects)
+  }
+}
+
+impl Hittable for Bvh {
+  fn hit(&self, ray: &Ray, ray_i: Interval, record: &mut HitRecord) -> bool {
+    let hit_tree = self.root.as_ref().is_some_and(|root| root.hit(ray, ray_i, record));
+    let closest_so_far = if hit_tree { record.d } else { ray_i.max };
+    let hit_unbounded = self.unbounded.hit(ray, Interval::new(ray_i.min, closest_so_far), record);
+    hit_tree || hit_unbounded
+  }
+
+  fn bounding_box(&self) -> Aabb {
+    let bbox = self.root.as_ref().map_or(aabb::EMPTY, BvhNode::bbox);
+    Aabb::surrounding(bbox, self.unbounded.bounding_box())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use rstest::*;
+
+  mod bvh {
+    use super::*;
+
+    use point::Point3;
+    use vector::Vector3;
+
+    use rand::{Rng, SeedableRng};
+    use rand_chacha::ChaCha8Rng;
+
+    // hittable that is hit wherever a ray enters its bounding box
+    struct Cube(Aabb);
+    impl Hittable for Cube {
+      fn hit(&self, ray: &Ray, ray_i: Interval, record: &mut HitRecord) -> bool {
+        let (mut t_min, mut t_max) = (ray_i.min, ray_i.max);
+        for n in 0..3 {
+          let axis = self.0.axis(n);
+          let t0 = (axis.min - ray.position()[n]) / ray.direction()[n];
+          let t1 = (axis.max - ray.position()[n]) / ray.direction()[n];
+          t_min = t_min.max(t0.min(t1));
+          t_max = t_max.min(t0.max(t1));
+        }
+        if t_max <= t_min {
+          return false;
+        }
+        *record = HitRecord::new(ray.at(t_min), Vector3::default(), t_min);
+        true
+      }
+
+      fn bounding_box(&self) -> Aabb {
+        self.0
+      }
+    }
+
+    fn cubes(count: usize, seed: u64) -> VecOfHittable {
+      let mut rng = ChaCha8Rng::seed_from_u64(seed);
+      (0..count)
+        .map(|_| {
+          let corner = Point3::random_range(&mut rng, -10.0, 10.0);
+          let size = rng.gen_range(0.1..1.0);
+          Box::new(Cube(Aabb::from_points(corner, corner + Vector3::new(size, size, size)))) as Box<dyn Hittable>
+        })
+        .collect()
+    }
+
+    #[rstest]
+    #[case(0)]
+    #[case(1)]
+    #[case(3)]
+    #[case(100)]
+    fn bounding_box(#[case] count: usize) {
+      let objects = cubes(count, 4);
+      let expected = objects.bounding_box();
+      let bvh = Bvh::new(objects);
+      assert_eq!(bvh.bounding_box(), expected);
+    }
+
+    #[rstest]
+    fn unbounded() {
+      // a slab with no extent along x or y, like an axis aligned plane
+      let slab = Aabb::new(interval::UNIVERSE, interval::UNIVERSE, Interval::new(-12.0, -11.0));
+      let mut list = cubes(50, 7);
+      list.push(Box::new(Cube(slab)));
+      let mut objects = cubes(50, 7);
+      objects.push(Box::new(Cube(slab)));
+      let bvh = Bvh::new(objects);
+      assert_eq!(bvh.unbounded.len(), 1);
+      assert_eq!(bvh.bounding_box(), list.bounding_box());
+
+      let mut rng = ChaCha8Rng::seed_from_u64(5);
+      let mut slab_hits = 0;
+      for _ in 0..200 {
+        let ray = Ray::new(Point3::random_range(&mut rng, -10.0, 10.0), Vector3::random_unit_vector(&mut rng));
+        let (mut list_record, mut bvh_record) = (HitRecord::default(), HitRecord::default());
+        let ray_i = Interval::new(0.001, f64::INFINITY);
+        let list_hit = list.hit(&ray, ray_i, &mut list_record);
+        assert_eq!(list_hit, bvh.hit(&ray, ray_i, &mut bvh_record));
+        if list_hit {
+          assert_eq!(list_record.d, bvh_record.d);
+          slab_hits += usize::from(bvh_record.position.z <= -11.0);
+        }
+      }
+      assert!(slab_hits > 0);
+    }
+
+    #[rstest]
+    fn empty() {
+      let bvh = Bvh::new(Vec::new());
+      let ray = Ray::new(Point3::default(), Vector3::new(0.0, 0.0, -1.0));
+      let mut record = HitRecord::default();
+      assert!(!bvh.hit(&ray, Interval::new(0.001, f64::INFINITY), &mut record));
+    }
+
+    #[rstest]
+    #[case(1)]
+    #[case(2)]
+    #[case(10)]
+    #[case(500)]
+    fn hit_matches_list(#[case] count: usize) {
+      let list = cubes(count, 42);
+      let bvh = Bvh::new(cubes(count, 42));
+
+      let mut rng = ChaCha8Rng::seed_from_u64(4);
+      for _ in 0..200 {
+        let ray = Ray::new(
+          Point3::random_range(&mut rng, -15.0, 15.0),
+          Vector3::random_unit_vector(&mut rng),
+        );
+        let (mut list_record, mut bvh_record) = (HitRecord::default(), HitRecord::default());
+        let ray_i = Interval::new(0.001, f64::INFINITY);
+        let list_hit = list.hit(&ray, ray_i, &mut list_record);
+        let bvh_hit = bvh.hit(&ray, ray_i, &mut bvh_record);
+        assert_eq!(list_hit, bvh_hit);
+        if list_hit {
+          assert_eq!(list_record.d, bvh_record.d);
+          assert_eq!(list_record.position, bvh_record.position);
+        }
+      }
+    }
+  }
+}
@@ -9,6 +9,12 @@ use point::Point3;
 use colour::Colour;
 use vector::Vector3;
 use interval::Interval;
+use aperture::Aperture;
+use light::Lights;
+use sampler::{Sampler, SamplerKind, PixelSample};
+use adaptive::{Adaptive, PixelStats};
+use material::{Material, ScatterRecord};
+use environment::Environment;
 use hittable::{Hittable, HitRecord};
 
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -19,89 +25,393 @@ pub struct Config {
   pub viewport_width: f64,
   pub viewport_height: f64,
   pub focal_length: f64,
+  // vertical field of view, in degrees
+  pub vfov: f64,
+  pub look_from: Point3,
+  pub look_at: Point3,
+  pub vup: Vector3,
+  // orthonormal camera basis; u points right, v up, w opposite the view direction
+  pub u: Vector3,
+  pub v: Vector3,
+  pub w: Vector3,
+  // angle of the cone from the viewport center to the lens, in degrees; 0 is a pinhole
+  pub defocus_angle: f64,
+  // distance from the lens to the plane of perfect focus
+  pub focus_dist: f64,
+  // lens radius along u and v
+  pub defocus_disk_u: Vector3,
+  pub defocus_disk_v: Vector3,
   pub first_pixel: Point3,
   pub pixel_dx: Vector3,
   pub pixel_dy: Vector3,
   pub samples_per_pixel: usize,
+  // span of time the shutter is open; each ray is cast at a random instant in it
+  pub shutter: Interval,
+  // sequence pixel samples draw their dimensions from
+  pub sampler: SamplerKind,
+  // base seed every render rng is derived from
+  pub seed: u64,
+  // per-pixel sample counts driven by variance; none takes samples_per_pixel everywhere
+  pub adaptive: Option<Adaptive>,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Camera {
   pub center: Point3,
   pub config: Config,
+  pub aperture: Aperture,
+  // radiance for rays that miss every hittable
+  pub background: Environment,
+  // emitters sampled directly at each diffuse bounce; none is a pure random walk
+  pub lights: Lights,
 }
 
 impl Camera {
+  /// Produces a camera at the origin looking down -Z with a 90 degree vertical fov
   pub fn new(image_width: usize, aspect_ratio: f64) -> Self {
-    let focal_length = 1.0;
-    let samples_per_pixel = 100;
-
-    let image_height = (image_width as f64 / aspect_ratio).max(1.0) as usize;
-    let viewport_height = 2.0;
-    let viewport_width = viewport_height * (image_width as f64 / image_height as f64);
-    let center = Point3::new(0.0, 0.0, 0.0);
-    let viewport_x = Vector3::new(viewport_width, 0.0, 0.0);
-    let viewport_y = Vector3::new(0.0, -viewport_height, 0.0);
-    let pixel_dx = viewport_x / image_width as f64;
-    let pixel_dy = viewport_y / image_height as f64;
-    let viewport_upper_left = center - Vector3::new(0.0, 0.0, focal_length) - viewport_x/2.0 - viewport_y/2.0;
-    let first_pixel = viewport_upper_left + 0.5 * (pixel_dx + pixel_dy);
+    // the default basis looks down -Z with +Y up, so it is never degenerate
+    CameraBuilder::new(image_width, aspect_ratio).build().unwrap()
+  }
 
-    let config = Config {
-      aspect_ratio,
-      image_width,
-      image_height,
-      viewport_width,
-      viewport_height,
-      focal_length,
-      first_pixel,
-      pixel_dx,
-      pixel_dy,
-      samples_per_pixel,
-    };
+  /// Produces the sampler every pixel sample of a render draws from; with
+  /// adaptive sampling the samples are stratified in passes of min_samples,
+  /// so every pixel gets at least one whole pass
+  pub fn sampler(&self) -> Box<dyn Sampler> {
+    let strata = self.config.adaptive.map_or(self.config.samples_per_pixel, |adaptive| adaptive.min_samples);
+    self.config.sampler.build(self.config.seed, strata)
+  }
 
-    Self {
-      center,
-      config,
+  /// Produces the samples taken for pixel (i, j): samples_per_pixel of them,
+  /// or with adaptive sampling as many as it takes to converge
+  pub fn sample_pixel(
+    &self,
+    sampler: &dyn Sampler,
+    hittable: &impl Hittable,
+    i: usize,
+    j: usize,
+    max_depth: usize,
+  ) -> PixelStats {
+    let mut stats = PixelStats::default();
+    let max_samples = self.config.adaptive.map_or(self.config.samples_per_pixel, |a| a.max_samples);
+    for index in 0..max_samples {
+      let mut sample = PixelSample::new(sampler, i, j, index);
+      let ray = self.get_ray(&mut sample, i, j);
+      stats.add(self.ray_colour(&mut sample, &ray, hittable, max_depth));
+      if self.config.adaptive.is_some_and(|adaptive| adaptive.is_converged(&stats)) {
+        break;
+      }
     }
+    stats
   }
 
   pub fn get_ray(&self, rng: &mut impl Rng, i: usize, j: usize) -> Ray {
     let Config { first_pixel, pixel_dx, pixel_dy, .. } = self.config;
     let pixel_center = first_pixel + (i as f64 * pixel_dx) + (j as f64 * pixel_dy);
     let pixel_sample = pixel_center + self.pixel_sample_square(rng);
-    let ray_origin = self.center;
+    let ray_origin = if self.config.defocus_angle <= 0.0 {
+      self.center
+    } else {
+      self.defocus_disk_sample(rng)
+    };
     let ray_direction = pixel_sample - ray_origin;
-    Ray::new(ray_origin, ray_direction)
+    let shutter = self.config.shutter;
+    let ray_time = if shutter.size() > 0.0 {
+      rng.gen_range(shutter.min..shutter.max)
+    } else {
+      shutter.min
+    };
+    Ray::with_time(ray_origin, ray_direction, ray_time)
   }
 
-  pub fn ray_colour(&self, _rng: &mut impl Rng, ray: &Ray, hittable: &mut impl Hittable, depth: usize) -> Colour {
+  pub fn ray_colour(&self, rng: &mut impl Rng, ray: &Ray, hittable: &impl Hittable, depth: usize) -> Colour {
+    self.trace(rng, ray, hittable, depth, None)
+  }
+
+  // scatter_pdf is the density the previous bounce drew ray with when it also
+  // sampled the lights, so light found along ray must be weighted against that
+  fn trace(
+    &self,
+    rng: &mut impl Rng,
+    ray: &Ray,
+    hittable: &impl Hittable,
+    depth: usize,
+    scatter_pdf: Option<f64>,
+  ) -> Colour {
     let mut record = HitRecord::default();
     if depth == 0 {
       Colour::new(0.0, 0.0, 0.0)
     } else if hittable.hit(ray, Interval::new(0.001, f64::INFINITY), &mut record) {
-      let mut scattered = Ray::default();
-      let mut attenuation = Colour::default();
       if let Some(ref mat) = record.material {
-        if mat.scatter(ray, &record, &mut attenuation, &mut scattered) {
-          return attenuation * self.ray_colour(_rng, &scattered, hittable, depth-1);
+        let mut emitted = mat.emitted(record.u, record.v, record.position);
+        if let Some(pdf) = scatter_pdf {
+          emitted = light::power_heuristic(pdf, self.lights.pdf_value(ray)) * emitted;
+        }
+        let Some(ScatterRecord { attenuation, scattered, pdf, specular }) = mat.scatter(rng, ray, &record) else {
+          return emitted;
+        };
+
+        if self.lights.is_empty() || specular {
+          return emitted + attenuation * self.trace(rng, &scattered, hittable, depth-1, None);
         }
+        let direct = self.sample_lights(rng, ray, &record, mat.as_ref(), hittable);
+        return emitted + direct + attenuation * self.trace(rng, &scattered, hittable, depth-1, Some(pdf));
       }
 
       Colour::new(0.0, 0.0, 0.0)
     } else {
-      let unit_direction = ray.direction().to_unit();
-      let a = 0.5*(unit_direction.y + 1.0);
-    
-      (1.0-a)*Colour::new(1.0, 1.0, 1.0) + a*Colour::new(0.5, 0.7, 1.0)
+      self.background.sample(ray)
     }
   }
 
+  /// Produces the light reaching record along a direction picked by the
+  /// lights, weighted against material scattering in that direction
+  fn sample_lights(
+    &self,
+    rng: &mut impl Rng,
+    ray: &Ray,
+    record: &HitRecord,
+    material: &dyn Material,
+    hittable: &impl Hittable,
+  ) -> Colour {
+    let direction = self.lights.random(rng, record.position, ray.time());
+    let shadow = Ray::with_time(record.position, direction, ray.time());
+    let (wi, wo) = (direction.to_unit(), -ray.direction().to_unit());
+    let light_pdf = self.lights.pdf_value(&shadow);
+    let scatter_pdf = material.pdf(record, wi, wo);
+    if light_pdf <= 0.0 || scatter_pdf <= 0.0 {
+      return Colour::new(0.0, 0.0, 0.0);
+    }
+
+    // whatever the shadow ray reaches first, light or occluder, is what is seen
+    let mut light_record = HitRecord::default();
+    if !hittable.hit(&shadow, Interval::new(0.001, f64::INFINITY), &mut light_record) {
+      return Colour::new(0.0, 0.0, 0.0);
+    }
+    let Some(ref light) = light_record.material else {
+      return Colour::new(0.0, 0.0, 0.0);
+    };
+    let emitted = light.emitted(light_record.u, light_record.v, light_record.position);
+    let weight = light::power_heuristic(light_pdf, scatter_pdf);
+    (weight / light_pdf) * material.eval(record, wi, wo) * emitted
+  }
+
   pub fn pixel_sample_square(&self, rng: &mut impl Rng) -> Point3 {
     let px = -0.5 + rng.gen::<f64>();
     let py = -0.5 + rng.gen::<f64>();
     px * self.config.pixel_dx + py * self.config.pixel_dy
   }
+
+  /// Produces a ray origin on the lens, shaped by the aperture
+  pub fn defocus_disk_sample(&self, rng: &mut impl Rng) -> Point3 {
+    let (px, py) = self.aperture.sample(rng);
+    self.center + px * self.config.defocus_disk_u + py * self.config.defocus_disk_v
+  }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CameraBuilder {
+  image_width: usize,
+  aspect_ratio: f64,
+  vfov: f64,
+  look_from: Point3,
+  look_at: Point3,
+  vup: Vector3,
+  defocus_angle: f64,
+  focus_dist: Option<f64>,
+  aperture: Aperture,
+  background: Environment,
+  lights: Lights,
+  samples_per_pixel: usize,
+  shutter: Interval,
+  sampler: SamplerKind,
+  seed: u64,
+  adaptive: Option<Adaptive>,
+}
+
+impl CameraBuilder {
+  pub fn new(image_width: usize, aspect_ratio: f64) -> Self {
+    Self {
+      image_width,
+      aspect_ratio,
+      vfov: 90.0,
+      look_from: Point3::new(0.0, 0.0, 0.0),
+      look_at: Point3::new(0.0, 0.0, -1.0),
+      vup: Vector3::new(0.0, 1.0, 0.0),
+      defocus_angle: 0.0,
+      focus_dist: None,
+      aperture: Aperture::Circular,
+      background: Environment::sky(),
+      lights: Lights::default(),
+      samples_per_pixel: 100,
+      shutter: Interval::new(0.0, 0.0),
+      sampler: SamplerKind::default(),
+      seed: 0,
+      adaptive: None,
+    }
+  }
+
+  pub fn look_from(mut self, look_from: Point3) -> Self {
+    self.look_from = look_from;
+    self
+  }
+
+  pub fn look_at(mut self, look_at: Point3) -> Self {
+    self.look_at = look_at;
+    self
+  }
+
+  pub fn vup(mut self, vup: Vector3) -> Self {
+    self.vup = vup;
+    self
+  }
+
+  /// Vertical field of view, in degrees; 90 when unset
+  pub fn vfov(mut self, vfov: f64) -> Self {
+    self.vfov = vfov;
+    self
+  }
+
+  /// Lens cone angle, in degrees; 0 disables defocus blur
+  pub fn defocus_angle(mut self, defocus_angle: f64) -> Self {
+    self.defocus_angle = defocus_angle;
+    self
+  }
+
+  /// Distance to the plane of perfect focus; the look-from to look-at distance when unset
+  pub fn focus_dist(mut self, focus_dist: f64) -> Self {
+    self.focus_dist = Some(focus_dist);
+    self
+  }
+
+  pub fn aperture(mut self, aperture: Aperture) -> Self {
+    self.aperture = aperture;
+    self
+  }
+
+  pub fn background(mut self, background: Environment) -> Self {
+    self.background = background;
+    self
+  }
+
+  /// Emitters to sample directly; each must also be in the rendered world
+  pub fn lights(mut self, lights: Lights) -> Self {
+    self.lights = lights;
+    self
+  }
+
+  pub fn samples_per_pixel(mut self, samples_per_pixel: usize) -> Self {
+    self.samples_per_pixel = samples_per_pixel;
+    self
+  }
+
+  /// Times the shutter opens and closes; an instant (no motion blur) when unset
+  pub fn shutter(mut self, open: f64, close: f64) -> Self {
+    self.shutter = Interval::new(open.min(close), open.max(close));
+    self
+  }
+
+  pub fn sampler(mut self, sampler: SamplerKind) -> Self {
+    self.sampler = sampler;
+    self
+  }
+
+  pub fn seed(mut self, seed: u64) -> Self {
+    self.seed = seed;
+    self
+  }
+
+  /// Samples each pixel until its noise is low enough rather than a fixed
+  /// samples_per_pixel times
+  pub fn adaptive(mut self, adaptive: Adaptive) -> Self {
+    self.adaptive = Some(adaptive);
+    self
+  }
+
+  /// Produces the camera, unless it looks at its own position or vup is
+  /// parallel to the view direction, which leave it with no basis
+  pub fn build(self) -> Result<Camera, RaytracerError> {
+    let Self {
+      image_width,
+      aspect_ratio,
+      vfov,
+      look_from,
+      look_at,
+      vup,
+      defocus_angle,
+      focus_dist,
+      aperture,
+      background,
+      lights,
+      samples_per_pixel,
+      shutter,
+      sampler,
+      seed,
+      adaptive,
+    } = self;
+
+    let image_height = (image_width as f64 / aspect_ratio).max(1.0) as usize;
+    let view = look_from - look_at;
+    if view.length_squared() == 0.0 || vector::cross(vup, view).length_squared() == 0.0 {
+      return Err(RaytracerError::CameraError);
+    }
+    let center = look_from;
+    let focal_length = (look_from - look_at).length();
+    let focus_dist = focus_dist.unwrap_or(focal_length);
+    let h = (vfov.to_radians() / 2.0).tan();
+    let viewport_height = 2.0 * h * focus_dist;
+    let viewport_width = viewport_height * (image_width as f64 / image_height as f64);
+
+    let w = (look_from - look_at).to_unit();
+    let u = vector::cross(vup, w).to_unit();
+    let v = vector::cross(w, u);
+
+    let viewport_x = viewport_width * u;
+    let viewport_y = viewport_height * -v;
+    let pixel_dx = viewport_x / image_width as f64;
+    let pixel_dy = viewport_y / image_height as f64;
+    let viewport_upper_left = center - focus_dist * w - viewport_x/2.0 - viewport_y/2.0;
+    let first_pixel = viewport_upper_left + 0.5 * (pixel_dx + pixel_dy);
+
+    let defocus_radius = focus_dist * (defocus_angle.to_radians() / 2.0).tan();
+    let defocus_disk_u = defocus_radius * u;
+    let defocus_disk_v = defocus_radius * v;
+
+    let config = Config {
+      aspect_ratio,
+      image_width,
+      image_height,
+      viewport_width,
+      viewport_height,
+      focal_length,
+      vfov,
+      look_from,
+      look_at,
+      vup,
+      u,
+      v,
+      w,
+      defocus_angle,
+      focus_dist,
+      defocus_disk_u,
+      defocus_disk_v,
+      first_pixel,
+      pixel_dx,
+      pixel_dy,
+      samples_per_pixel,
+      shutter,
+      sampler,
+      seed,
+      adaptive,
+    };
+
+    Ok(Camera {
+      center,
+      config,
+      aperture,
+      background,
+      lights,
+    })
+  }
 }
 
 #[cfg(test)]
@@ -115,15 +425,21 @@ mod tests {
     use rand::SeedableRng;
     use rand_chacha::ChaCha8Rng;
 
-    use material::Lambertian;
+    use material::{Lambertian, Metal, Dielectric, DiffuseLight};
+    use aperture::ApertureMask;
+    use light::Lights;
+    use sampler::IndependentSampler;
+
+    use std::f64::consts::PI;
 
     use std::sync::Arc;
+    use std::sync::atomic::{AtomicBool, Ordering};
 
     #[rstest]
-    #[case(10, 16.0/9.0, Vector3::new(0.4, 0.0, 0.0), Vector3::new(0.0, -0.4, 0.0), Point3::new(-1.8, 0.8, -1.0))]
-    #[case(500, 2.0, Vector3::new(0.008, 0.0, 0.0), Vector3::new(0.0, -0.008, 0.0), Point3::new(-1.996, 0.996, -1.0))]
-    #[case(500, 1.0, Vector3::new(0.004, 0.0, 0.0), Vector3::new(0.0, -0.004, 0.0), Point3::new(-0.998, 0.998, -1.0))]
-    #[case(500, 0.5, Vector3::new(0.002, 0.0, 0.0), Vector3::new(0.0, -0.002, 0.0), Point3::new(-0.499, 0.999, -1.0))]
+    #[case(10, 16.0/9.0, Vector3::new(0.39999999999999997, 0.0, 0.0), Vector3::new(0.0, -0.39999999999999997, 0.0), Point3::new(-1.7999999999999998, 0.7999999999999999, -1.0))]
+    #[case(500, 2.0, Vector3::new(0.007999999999999998, 0.0, 0.0), Vector3::new(0.0, -0.007999999999999998, 0.0), Point3::new(-1.9959999999999998, 0.9959999999999999, -1.0))]
+    #[case(500, 1.0, Vector3::new(0.003999999999999999, 0.0, 0.0), Vector3::new(0.0, -0.003999999999999999, 0.0), Point3::new(-0.9979999999999999, 0.9979999999999999, -1.0))]
+    #[case(500, 0.5, Vector3::new(0.0019999999999999996, 0.0, 0.0), Vector3::new(0.0, -0.0019999999999999996, 0.0), Point3::new(-0.49899999999999994, 0.9989999999999999, -1.0))]
     #[should_panic]
     #[case(0, 16.0/9.0, Vector3::new(0.0, 0.0, 0.0), Vector3::new(0.0, 0.0, 0.0), Point3::new(0.0, 0.0, 0.0))]
     fn new(
@@ -140,8 +456,8 @@ mod tests {
     }
 
     #[rstest]
-    #[case((0, 0), Ray::new(Point3::default(), Vector3::new(-1.9454483046154663, 0.9239779673862012, -1.0)))]
-    #[case((49, 24), Ray::new(Point3::default(), Vector3::new(1.9745516953845337, -0.9960220326137987, -1.0)))]
+    #[case((0, 0), Ray::new(Point3::default(), Vector3::new(-1.945448304615466, 0.9239779673862012, -1.0)))]
+    #[case((49, 24), Ray::new(Point3::default(), Vector3::new(1.9745516953845335, -0.9960220326137985, -1.0)))]
     #[case((10, 11), Ray::new(Point3::default(), Vector3::new(-1.1454483046154662, 0.043977967386201244, -1.0)))]
     #[should_panic]
     #[case((50, 0), Ray::new(Point3::default(), Vector3::default()))]
@@ -157,35 +473,34 @@ mod tests {
 
     #[rstest]
     fn ray_colour_toggle() { 
-      struct Toggle(bool);
+      struct Toggle(AtomicBool);
       impl Hittable for Toggle {
-        fn hit(&mut self, _: &Ray, _: Interval, record: &mut HitRecord) -> bool {
+        fn hit(&self, _: &Ray, _: Interval, record: &mut HitRecord) -> bool {
           // ensure HitRecord stays as default!
           *record = HitRecord::default();
           // toggle .0
-          let res = self.0;
-          self.0 = !self.0;
+          let res = self.0.fetch_xor(true, Ordering::Relaxed);
           
           // set material
           record.material = Some(Arc::new(Lambertian::new(Colour::new(0.5, 0.1, 0.1))));
 
           res
         }
+
+        fn bounding_box(&self) -> aabb::Aabb {
+          aabb::UNIVERSE
+        }
       }
 
       let mut rng = ChaCha8Rng::seed_from_u64(4);
       let camera = Camera::new(500, 16.0 / 9.0);
       let ray = Ray::default();
 
-      let mut toggle = Toggle(true);
+      let toggle = Toggle(AtomicBool::new(true));
       for _ in 0..100 {
-        let expected_background = {
-          let unit_direction = ray.direction().to_unit();
-          let a = 0.5*(unit_direction.y + 1.0);
-          (1.0-a)*Colour::new(1.0, 1.0, 1.0) + a*Colour::new(0.5, 0.7, 1.0)
-        };
-        let colour = camera.ray_colour(&mut rng, &ray, &mut toggle, 10);
-        if toggle.0 {
+        let expected_background = camera.background.sample(&ray);
+        let colour = camera.ray_colour(&mut rng, &ray, &toggle, 10);
+        if toggle.0.load(Ordering::Relaxed) {
           assert_ne!(colour, expected_background);
         } else {
           assert_eq!(colour, expected_background);
@@ -193,19 +508,434 @@ mod tests {
       }
     }
 
+    #[rstest]
+    fn ray_colour_background() {
+      struct Nothing;
+      impl Hittable for Nothing {
+        fn hit(&self, _: &Ray, _: Interval, _: &mut HitRecord) -> bool {
+          false
+        }
+
+        fn bounding_box(&self) -> aabb::Aabb {
+          aabb::EMPTY
+        }
+      }
+
+      let mut rng = ChaCha8Rng::seed_from_u64(4);
+      let ray = Ray::new(Point3::default(), Vector3::new(0.0, 1.0, 0.0));
+      let sky = Camera::new(50, 2.0);
+      assert_eq!(sky.ray_colour(&mut rng, &ray, &Nothing, 10), Colour::new(0.5, 0.7, 1.0));
+
+      let background = Colour::new(0.2, 0.0, 0.1);
+      let solid = CameraBuilder::new(50, 2.0).background(Environment::Solid(background)).build().unwrap();
+      assert_eq!(solid.ray_colour(&mut rng, &ray, &Nothing, 10), background);
+    }
+
+    #[rstest]
+    fn ray_colour_emissive() {
+      struct Light;
+      impl Hittable for Light {
+        fn hit(&self, ray: &Ray, _: Interval, record: &mut HitRecord) -> bool {
+          *record = HitRecord::new(ray.at(1.0), -ray.direction(), 1.0);
+          record.material = Some(Arc::new(DiffuseLight::new(Colour::new(4.0, 2.0, 1.0))));
+          true
+        }
+
+        fn bounding_box(&self) -> aabb::Aabb {
+          aabb::UNIVERSE
+        }
+      }
+
+      let mut rng = ChaCha8Rng::seed_from_u64(4);
+      let camera = Camera::new(50, 2.0);
+      let ray = Ray::new(Point3::default(), Vector3::new(0.0, 0.0, -1.0));
+      assert_eq!(camera.ray_colour(&mut rng, &ray, &Light, 10), Colour::new(4.0, 2.0, 1.0));
+      assert_eq!(camera.ray_colour(&mut rng, &ray, &Light, 0), Colour::new(0.0, 0.0, 0.0));
+    }
+
+    #[rstest]
+    fn ray_colour_lights() {
+      // square light of half width s at height h facing a white floor at y = 0
+      struct Square { s: f64, h: f64 }
+      impl Hittable for Square {
+        fn hit(&self, ray: &Ray, ray_i: Interval, record: &mut HitRecord) -> bool {
+          let d = (self.h - ray.position().y) / ray.direction().y;
+          let p = ray.at(d);
+          if !ray_i.surrounds(d) || p.x.abs() > self.s || p.z.abs() > self.s {
+            return false;
+          }
+          *record = HitRecord::new(p, Vector3::default(), d);
+          record.set_face_normal(ray, Vector3::new(0.0, -1.0, 0.0));
+          record.material = Some(Arc::new(DiffuseLight::new(Colour::new(100.0, 100.0, 100.0))));
+          true
+        }
+
+        fn bounding_box(&self) -> aabb::Aabb {
+          aabb::UNIVERSE
+        }
+
+        fn pdf_value(&self, ray: &Ray) -> f64 {
+          let mut record = HitRecord::default();
+          if !self.hit(ray, Interval::new(0.001, f64::INFINITY), &mut record) {
+            return 0.0;
+          }
+          let length = ray.direction().length();
+          let distance_squared = record.d * record.d * length * length;
+          let cos = ray.direction().y.abs() / length;
+          distance_squared / (cos * 4.0 * self.s * self.s)
+        }
+
+        fn random(&self, rng: &mut dyn rand::RngCore, origin: Point3, _: f64) -> Vector3 {
+          Point3::new(rng.gen_range(-self.s..self.s), self.h, rng.gen_range(-self.s..self.s)) - origin
+        }
+      }
+      struct Floor;
+      impl Hittable for Floor {
+        fn hit(&self, ray: &Ray, ray_i: Interval, record: &mut HitRecord) -> bool {
+          let d = -ray.position().y / ray.direction().y;
+          if !ray_i.surrounds(d) {
+            return false;
+          }
+          *record = HitRecord::new(ray.at(d), Vector3::default(), d);
+          record.set_face_normal(ray, Vector3::new(0.0, 1.0, 0.0));
+          record.material = Some(Arc::new(Lambertian::new(Colour::new(0.5, 0.5, 0.5))));
+          true
+        }
+
+        fn bounding_box(&self) -> aabb::Aabb {
+          aabb::UNIVERSE
+        }
+      }
+
+      let light = Arc::new(Square { s: 0.1, h: 1.0 });
+      let world: hittable::VecOfHittable = vec![Box::new(Floor), Box::new(Arc::clone(&light))];
+      let black = Environment::Solid(Colour::new(0.0, 0.0, 0.0));
+      let walk = CameraBuilder::new(50, 2.0).background(black.clone()).build().unwrap();
+      let mis = CameraBuilder::new(50, 2.0)
+        .background(black)
+        .lights(Lights::new(vec![light]))
+        .build().unwrap();
+
+      // form factor from the floor's origin to each quarter of the light
+      let rectangle = |x: f64, y: f64| {
+        let (sx, sy) = ((1.0 + x*x).sqrt(), (1.0 + y*y).sqrt());
+        (x / sx * (y / sx).atan() + y / sy * (x / sy).atan()) / (2.0 * PI)
+      };
+      let expected = 0.5 * 100.0 * 4.0 * rectangle(0.1, 0.1);
+
+      let mut rng = ChaCha8Rng::seed_from_u64(4);
+      let ray = Ray::new(Point3::new(0.0, 0.5, 0.5), Vector3::new(0.0, -0.5, -0.5));
+      let samples = 2000;
+      let mut stats = |camera: &Camera| {
+        let values: Vec<f64> = (0..samples).map(|_| camera.ray_colour(&mut rng, &ray, &world, 2).x).collect();
+        let mean = values.iter().sum::<f64>() / samples as f64;
+        let variance = values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / samples as f64;
+        (mean, variance)
+      };
+      let (mis_mean, mis_variance) = stats(&mis);
+      let (_, walk_variance) = stats(&walk);
+      assert!((mis_mean - expected).abs() < 0.02 * expected, "{mis_mean} != {expected}");
+      assert!(mis_variance < walk_variance / 10.0);
+    }
+
+    #[rstest]
+    fn ray_colour_reproducible() {
+      // ground below y = 0 whose material depends on where it is hit
+      struct Ground;
+      impl Hittable for Ground {
+        fn hit(&self, ray: &Ray, ray_i: Interval, record: &mut HitRecord) -> bool {
+          let d = -ray.position().y / ray.direction().y;
+          if !ray_i.surrounds(d) {
+            return false;
+          }
+          *record = HitRecord::new(ray.at(d), Vector3::default(), d);
+          record.set_face_normal(ray, Vector3::new(0.0, 1.0, 0.0));
+          let material: Arc<dyn Material> = match (record.position.x.floor() as i64).rem_euclid(3) {
+            0 => Arc::new(Lambertian::new(Colour::new(0.5, 0.5, 0.5))),
+            1 => Arc::new(Metal::new(Colour::new(0.8, 0.6, 0.2), 0.5)),
+            _ => Arc::new(Dielectric::new(1.5)),
+          };
+          record.material = Some(material);
+          true
+        }
+
+        fn bounding_box(&self) -> aabb::Aabb {
+          aabb::UNIVERSE
+        }
+      }
+
+      let camera = CameraBuilder::new(50, 2.0).look_from(Point3::new(0.0, 1.0, 0.0)).build().unwrap();
+      let render = |seed: u64| {
+        let mut rng = ChaCha8Rng::seed_from_u64(seed);
+        (0..200)
+          .map(|n| {
+            let ray = camera.get_ray(&mut rng, n % 50, n % 25);
+            camera.ray_colour(&mut rng, &ray, &Ground, 10)
+          })
+          .collect::<Vec<_>>()
+      };
+      assert_eq!(render(4), render(4));
+      assert_ne!(render(4), render(5));
+    }
+
+    #[rstest]
+    fn sample_pixel() {
+      let nothing = hittable::VecOfHittable::new();
+      let sampler = IndependentSampler::new(4);
+      let adaptive = Adaptive::new(8, 64, 0.05);
+
+      let fixed = CameraBuilder::new(50, 2.0).samples_per_pixel(20).build().unwrap();
+      assert_eq!(fixed.sample_pixel(&sampler, &nothing, 10, 11, 10).count(), 20);
+
+      // a flat background converges as soon as it may
+      let flat = CameraBuilder::new(50, 2.0)
+        .background(Environment::Solid(Colour::new(0.5, 0.7, 1.0)))
+        .adaptive(adaptive)
+        .build().unwrap();
+      let stats = flat.sample_pixel(&sampler, &nothing, 10, 11, 10);
+      assert_eq!(stats.count(), 8);
+      assert!(vector::near_zero(stats.mean() - Colour::new(0.5, 0.7, 1.0)));
+
+      // stripes much finer than a pixel leave it half lit and take the cap
+      let striped = CameraBuilder::new(50, 2.0)
+        .background(Environment::closure(|ray| {
+          if (ray.direction().x * 1e4).rem_euclid(1.0) < 0.5 { Colour::new(1.0, 1.0, 1.0) } else { Colour::default() }
+        }))
+        .adaptive(adaptive)
+        .build().unwrap();
+      let stats = striped.sample_pixel(&sampler, &nothing, 10, 11, 10);
+      assert_eq!(stats.count(), 64);
+      assert!(stats.relative_error() > 0.05);
+      assert_eq!(stats, striped.sample_pixel(&sampler, &nothing, 10, 11, 10));
+    }
+
     #[rstest]
     fn pixel_sample_square() {
       let mut rng = ChaCha8Rng::seed_from_u64(4);
       let camera = Camera::new(50, 2.0);
       let expected = vec![
-        Point3::new(0.01810289656719271, 0.03441457162334653, 0.0),
-        Point3::new(0.030279376124643455, -0.02092136846687394, 0.0),
-        Point3::new(-0.024847111286775157, -0.029056899399724348, 0.0),
-        Point3::new(-0.03411305294726887, 0.0015556901526561973, 0.0),
+        Point3::new(0.018102896567192706, 0.034414571623346525, 0.0),
+        Point3::new(0.030279376124643452, -0.020921368466873938, 0.0),
+        Point3::new(-0.024847111286775154, -0.029056899399724345, 0.0),
+        Point3::new(-0.034113052947268865, 0.0015556901526561971, 0.0),
       ];
       for expected in expected {
         assert_eq!(camera.pixel_sample_square(&mut rng), expected);
       }
     }
+
+    #[rstest]
+    #[case(Aperture::Circular)]
+    #[case(Aperture::Polygon { blades: 6, rotation: 0.0 })]
+    #[case(Aperture::Mask(Arc::new(ApertureMask::new(3, 3, &[0.0, 1.0, 0.0, 1.0, 1.0, 1.0, 0.0, 1.0, 0.0]).unwrap())))]
+    fn defocus_disk_sample(#[case] aperture: Aperture) {
+      let camera = CameraBuilder::new(50, 2.0)
+        .look_from(Point3::new(0.0, 0.0, 2.0))
+        .defocus_angle(10.0)
+        .aperture(aperture)
+        .build().unwrap();
+      let radius = camera.config.defocus_disk_u.length();
+      let (mut rng, mut other_rng) = (ChaCha8Rng::seed_from_u64(4), ChaCha8Rng::seed_from_u64(4));
+      for _ in 0..100 {
+        let origin = camera.defocus_disk_sample(&mut rng);
+        let offset = origin - camera.center;
+        assert_eq!(origin, camera.defocus_disk_sample(&mut other_rng));
+        // lens samples stay on the lens plane, within the square bounding the aperture
+        assert!(vector::dot(offset, camera.config.w).abs() < 1e-12);
+        assert!(offset.x.abs() <= radius && offset.y.abs() <= radius);
+      }
+    }
+
+    #[rstest]
+    #[case((0, 0), Ray::new(Point3::new(-0.012682986083269147, 0.02228520357104084, 0.0), Vector3::new(-1.932765318532197, 0.9016927638151604, -1.0)))]
+    #[case((49, 24), Ray::new(Point3::new(-0.012682986083269147, 0.02228520357104084, 0.0), Vector3::new(1.9872346814678026, -1.0183072361848393, -1.0)))]
+    fn get_ray_defocus(#[case] indices: (usize, usize), #[case] expected: Ray) {
+      let mut rng = ChaCha8Rng::seed_from_u64(42);
+      let camera = CameraBuilder::new(50, 2.0).defocus_angle(10.0).build().unwrap();
+      assert_eq!(camera.get_ray(&mut rng, indices.0, indices.1), expected);
+    }
+  }
+
+  mod camera_builder {
+    use super::*;
+
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    use sampler::{PixelSample, SobolSampler};
+
+    fn approx_equal(a: Vector3, b: Vector3) -> bool {
+      vector::near_zero(a - b)
+    }
+
+    #[rstest]
+    fn default() {
+      let camera = CameraBuilder::new(50, 2.0).build().unwrap();
+      assert_eq!(camera, Camera::new(50, 2.0));
+      assert_eq!(camera.center, Point3::new(0.0, 0.0, 0.0));
+      assert_eq!(camera.config.vfov, 90.0);
+      assert_eq!(camera.config.u, Vector3::new(1.0, 0.0, 0.0));
+      assert_eq!(camera.config.v, Vector3::new(0.0, 1.0, 0.0));
+      assert_eq!(camera.config.w, Vector3::new(0.0, 0.0, 1.0));
+    }
+
+    #[rstest]
+    fn vfov() {
+      let explicit = CameraBuilder::new(50, 2.0).vfov(90.0).build().unwrap();
+      let default = Camera::new(50, 2.0);
+      assert!(approx_equal(explicit.config.pixel_dx, default.config.pixel_dx));
+      assert!(approx_equal(explicit.config.pixel_dy, default.config.pixel_dy));
+      assert!(approx_equal(explicit.config.first_pixel, default.config.first_pixel));
+
+      let narrow = CameraBuilder::new(50, 2.0).vfov(20.0).build().unwrap();
+      assert!(narrow.config.viewport_height < default.config.viewport_height);
+      assert!(narrow.config.pixel_dx.length() < default.config.pixel_dx.length());
+    }
+
+    #[rstest]
+    #[case(Point3::new(0.0, 2.0, 0.0), Point3::new(0.0, 0.0, 0.0), Vector3::new(0.0, 1.0, 0.0))]
+    #[case(Point3::new(0.0, 0.0, 0.0), Point3::new(0.0, -1.0, 0.0), Vector3::new(0.0, 1.0, 0.0))]
+    #[case(Point3::new(1.0, 1.0, 1.0), Point3::new(1.0, 1.0, 1.0), Vector3::new(0.0, 1.0, 0.0))]
+    #[case(Point3::new(0.0, 0.0, 0.0), Point3::new(0.0, 0.0, -1.0), Vector3::default())]
+    fn degenerate_basis(#[case] look_from: Point3, #[case] look_at: Point3, #[case] vup: Vector3) {
+      let camera = CameraBuilder::new(50, 2.0).look_from(look_from).look_at(look_at).vup(vup).build();
+      assert!(matches!(camera, Err(RaytracerError::CameraError)));
+    }
+
+    #[rstest]
+    #[case(Point3::new(0.0, 0.0, 0.0), Point3::new(1.0, 0.0, 0.0), Vector3::new(0.0, 1.0, 0.0))]
+    #[case(Point3::new(-2.0, 2.0, 1.0), Point3::new(0.0, 0.0, -1.0), Vector3::new(0.0, 1.0, 0.0))]
+    #[case(Point3::new(3.0, 3.0, 2.0), Point3::new(0.0, 0.0, -1.0), Vector3::new(0.0, 0.0, 1.0))]
+    fn basis(#[case] look_from: Point3, #[case] look_at: Point3, #[case] vup: Vector3) {
+      let camera = CameraBuilder::new(100, 1.0)
+        .look_from(look_from)
+        .look_at(look_at)
+        .vup(vup)
+        .vfov(40.0)
+        .build().unwrap();
+      let Config { u, v, w, focal_length, .. } = camera.config;
+
+      assert_eq!(camera.center, look_from);
+      assert_eq!(focal_length, (look_from - look_at).length());
+      assert!(approx_equal(w, (look_from - look_at).to_unit()));
+      for (a, b) in [(u, v), (v, w), (w, u)] {
+        assert!(vector::dot(a, b).abs() < 1e-8);
+      }
+      for axis in [u, v, w] {
+        assert!((axis.length() - 1.0).abs() < 1e-8);
+      }
+      // vup projects onto the upward half of the image plane
+      assert!(vector::dot(v, vup) > 0.0);
+    }
+
+    #[rstest]
+    fn get_ray_through_look_at() {
+      let mut rng = ChaCha8Rng::seed_from_u64(42);
+      let look_from = Point3::new(-2.0, 2.0, 1.0);
+      let look_at = Point3::new(0.0, 0.0, -1.0);
+      let camera = CameraBuilder::new(101, 1.0)
+        .look_from(look_from)
+        .look_at(look_at)
+        .vfov(30.0)
+        .build().unwrap();
+      let ray = camera.get_ray(&mut rng, 50, 50);
+      let to_target = (look_at - look_from).to_unit();
+      assert_eq!(ray.position(), look_from);
+      // center pixel ray only deviates from the view direction by its jitter
+      assert!(vector::dot(ray.direction().to_unit(), to_target) > 0.9999);
+    }
+
+    #[rstest]
+    fn defocus() {
+      let look_from = Point3::new(0.0, 0.0, 4.0);
+      let pinhole = CameraBuilder::new(50, 2.0).look_from(look_from).build().unwrap();
+      assert_eq!(pinhole.config.defocus_disk_u, Vector3::default());
+      assert_eq!(pinhole.config.focus_dist, pinhole.config.focal_length);
+
+      let camera = CameraBuilder::new(50, 2.0)
+        .look_from(look_from)
+        .defocus_angle(2.0)
+        .focus_dist(10.0)
+        .build().unwrap();
+      let Config { focus_dist, defocus_disk_u, defocus_disk_v, viewport_height, .. } = camera.config;
+      let expected_radius = 10.0 * 1.0_f64.to_radians().tan();
+      assert_eq!(focus_dist, 10.0);
+      assert!((defocus_disk_u.length() - expected_radius).abs() < 1e-12);
+      assert!((defocus_disk_v.length() - expected_radius).abs() < 1e-12);
+      // viewport sits on the focus plane, so it grows with the focus distance
+      assert_eq!(viewport_height, 19.999999999999996);
+    }
+
+    #[rstest]
+    fn samples_per_pixel_seed() {
+      let camera = CameraBuilder::new(50, 2.0).samples_per_pixel(8).seed(7).build().unwrap();
+      assert_eq!(camera.config.samples_per_pixel, 8);
+      assert_eq!(camera.config.seed, 7);
+    }
+
+    #[rstest]
+    fn adaptive() {
+      assert_eq!(Camera::new(50, 2.0).config.adaptive, None);
+      let camera = CameraBuilder::new(50, 2.0).adaptive(Adaptive::default()).build().unwrap();
+      assert_eq!(camera.config.adaptive, Some(Adaptive::new(16, 1024, 0.01)));
+    }
+
+    #[rstest]
+    fn sampler() {
+      assert_eq!(Camera::new(50, 2.0).config.sampler, SamplerKind::Independent);
+      let camera = CameraBuilder::new(50, 2.0).sampler(SamplerKind::Sobol).seed(7).build().unwrap();
+      assert_eq!(camera.config.sampler, SamplerKind::Sobol);
+
+      // rays drawn from a pixel sample use its dimensions in order, pixel offset first
+      let (sampler, expected) = (camera.sampler(), SobolSampler::new(7));
+      let mut sample = PixelSample::new(sampler.as_ref(), 10, 11, 3);
+      let mut expected_sample = PixelSample::new(&expected, 10, 11, 3);
+      let ray = camera.get_ray(&mut sample, 10, 11);
+      let (px, py) = expected_sample.next_2d();
+      let Config { first_pixel, pixel_dx, pixel_dy, .. } = camera.config;
+      let target = first_pixel + (10.0 + px - 0.5) * pixel_dx + (11.0 + py - 0.5) * pixel_dy;
+      assert!(vector::near_zero(ray.direction() - (target - camera.center)));
+      assert_eq!(sample.dimension(), 2);
+    }
+
+    #[rstest]
+    #[case(None, 5)]
+    #[case(Some(Adaptive::new(8, 64, 0.05)), 8)]
+    fn sampler_strata(#[case] adaptive: Option<Adaptive>, #[case] strata: usize) {
+      let mut builder = CameraBuilder::new(50, 2.0).sampler(SamplerKind::Stratified).samples_per_pixel(5).seed(3);
+      if let Some(adaptive) = adaptive {
+        builder = builder.adaptive(adaptive);
+      }
+      let sampler = builder.build().unwrap().sampler();
+      // the first pass puts one sample in each stratum of every dimension
+      for dimension in 0..4 {
+        let mut strata_hit: Vec<usize> = (0..strata)
+          .map(|index| {
+            let mut sample = PixelSample::new(sampler.as_ref(), 4, 9, index);
+            let value = (0..=dimension).map(|_| sample.next_1d()).last().unwrap();
+            (value * strata as f64) as usize
+          })
+          .collect();
+        strata_hit.sort_unstable();
+        assert_eq!(strata_hit, (0..strata).collect::<Vec<_>>());
+      }
+    }
+
+    #[rstest]
+    #[case(0.0, 0.0)]
+    #[case(0.0, 1.0)]
+    #[case(2.0, 1.5)]
+    fn shutter(#[case] open: f64, #[case] close: f64) {
+      let camera = CameraBuilder::new(50, 2.0).shutter(open, close).build().unwrap();
+      let shutter = Interval::new(open.min(close), open.max(close));
+      assert_eq!(camera.config.shutter, shutter);
+
+      let mut rng = ChaCha8Rng::seed_from_u64(4);
+      let times: Vec<f64> = (0..100).map(|_| camera.get_ray(&mut rng, 10, 10).time()).collect();
+      assert!(times.iter().all(|time| shutter.contains(*time)));
+      if open != close {
+        assert!(times.iter().any(|time| *time != times[0]));
+      }
+    }
   }
 }
\ No newline at end of file
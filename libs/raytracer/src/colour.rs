@@ -1,7 +1,7 @@
 
 use crate::*;
 
-use interval::Interval;
+use colourspace::ColourSpace;
 
 #[derive(Debug, Default, Clone, Copy, PartialEq, PartialOrd)]
 pub struct Pixel {
@@ -18,22 +18,34 @@ impl Pixel {
 
 pub type Colour = vector::Vector3;
 
+/// Produces the sRGB encoded pixel of the mean of samples_per_pixel linear samples
 pub fn colour_to_pixel(colour: &Colour, samples_per_pixel: usize) -> Pixel {
-  let interval = Interval::new(0.0, 1.0);
   let scale = 1.0 / samples_per_pixel as f64;
-  let scaled_x = linear_to_gamma(colour.x * scale);
-  let scaled_y = linear_to_gamma(colour.y * scale);
-  let scaled_z = linear_to_gamma(colour.z * scale);
+  ColourSpace::Srgb.to_pixel(scale * *colour)
+}
 
-  let r = (interval.clamp(scaled_x) * 255.0) as u8;
-  let g = (interval.clamp(scaled_y) * 255.0) as u8;
-  let b = (interval.clamp(scaled_z) * 255.0) as u8;
+/// Produces the Rec. 709 luminance of a linear colour
+pub fn luminance(colour: Colour) -> f64 {
+  0.2126 * colour.x + 0.7152 * colour.y + 0.0722 * colour.z
+}
 
-  Pixel::new(r, g, b)
+/// Produces the sRGB encoded value of a linear one, by the piecewise curve
+/// with a linear segment near black
+pub fn srgb_encode(linear: f64) -> f64 {
+  if linear <= 0.0031308 {
+    12.92 * linear
+  } else {
+    1.055 * linear.powf(1.0 / 2.4) - 0.055
+  }
 }
 
-fn linear_to_gamma(lc: f64) -> f64 {
-  lc.sqrt()
+/// Produces the linear value of an sRGB encoded one
+pub fn srgb_decode(encoded: f64) -> f64 {
+  if encoded <= 0.04045 {
+    encoded / 12.92
+  } else {
+    ((encoded + 0.055) / 1.055).powf(2.4)
+  }
 }
 
 #[cfg(test)]
@@ -60,12 +72,36 @@ mod tests {
     #[rstest]
     #[case((0.0, 0.0, 0.0), Pixel::new(0, 0, 0))]
     #[case((1.0, 1.0, 1.0), Pixel::new(255, 255, 255))]
-    #[case((0.33, 0.4, 0.7), Pixel::new(146, 161, 213))]
+    #[case((0.33, 0.4, 0.7), Pixel::new(155, 170, 218))]
     #[case((5.0, 5.0, 5.0), Pixel::new(255, 255, 255))]
     fn colour_to_pixel(#[case] tuple: (f64, f64, f64), #[case] expected: Pixel) {
       let colour = Colour::from(tuple);
       let pixel = super::colour_to_pixel(&colour, 1);
       assert_eq!(pixel, expected);
     }
+
+    #[rstest]
+    fn colour_to_pixel_mean() {
+      let pixel = super::colour_to_pixel(&Colour::new(1.0, 2.0, 4.0), 4);
+      assert_eq!(pixel, super::colour_to_pixel(&Colour::new(0.25, 0.5, 1.0), 1));
+    }
+
+    #[rstest]
+    #[case(0.0, 0.0)]
+    #[case(0.002, 0.02584)]
+    #[case(0.18, 0.46135612950044164)]
+    #[case(1.0, 1.0)]
+    fn srgb_encode_decode(#[case] linear: f64, #[case] encoded: f64) {
+      assert!((super::srgb_encode(linear) - encoded).abs() < 1e-12);
+      assert!((super::srgb_decode(encoded) - linear).abs() < 1e-12);
+    }
+
+    #[rstest]
+    #[case((0.0, 0.0, 0.0), 0.0)]
+    #[case((1.0, 1.0, 1.0), 1.0)]
+    #[case((0.0, 1.0, 0.0), 0.7152)]
+    fn luminance(#[case] tuple: (f64, f64, f64), #[case] expected: f64) {
+      assert!((super::luminance(Colour::from(tuple)) - expected).abs() < 1e-12);
+    }
   }
 }
\ No newline at end of file
@@ -0,0 +1,225 @@
+
+use crate::*;
+
+use colour::{Colour, Pixel};
+use vector::Vector3;
+use transform::Matrix3;
+use interval::Interval;
+
+const D65: (f64, f64) = (0.3127, 0.3290);
+const ACES_WHITE: (f64, f64) = (0.32168, 0.33767);
+
+// cone response matrix used to adapt between white points
+const BRADFORD: Matrix3 = Matrix3::new([
+  [0.8951, 0.2664, -0.1614],
+  [-0.7502, 1.7135, 0.0367],
+  [0.0389, -0.0685, 1.0296],
+]);
+
+// constants of the Rec. 2020 transfer function
+const REC2020_ALPHA: f64 = 1.09929682680944;
+const REC2020_BETA: f64 = 0.018053968510807;
+
+/// RGB colour spaces colours can be rendered in and written out as; each is
+/// a set of primaries with a white point, and a transfer function used when
+/// encoding for display
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ColourSpace {
+  // sRGB, which shares its primaries and white point with Rec. 709
+  #[default]
+  Srgb,
+  // P3 primaries with a D65 white and the sRGB transfer function
+  DisplayP3,
+  Rec2020,
+  // ACES AP1 primaries with a linear transfer, for rendering in rather than viewing
+  AcesCg,
+}
+
+impl ColourSpace {
+  /// Produces the CIE xy chromaticities of the red, green and blue primaries
+  pub fn primaries(&self) -> [(f64, f64); 3] {
+    match self {
+      Self::Srgb => [(0.64, 0.33), (0.30, 0.60), (0.15, 0.06)],
+      Self::DisplayP3 => [(0.680, 0.320), (0.265, 0.690), (0.150, 0.060)],
+      Self::Rec2020 => [(0.708, 0.292), (0.170, 0.797), (0.131, 0.046)],
+      Self::AcesCg => [(0.713, 0.293), (0.165, 0.830), (0.128, 0.044)],
+    }
+  }
+
+  /// Produces the CIE xy chromaticity of the white point
+  pub fn white_point(&self) -> (f64, f64) {
+    match self {
+      Self::AcesCg => ACES_WHITE,
+      _ => D65,
+    }
+  }
+
+  /// Produces the matrix from linear RGB in this space to CIE XYZ, adapted
+  /// to a D65 white so every space shares one XYZ
+  pub fn to_xyz(&self) -> Matrix3 {
+    let [r, g, b] = self.primaries().map(xy_to_xyz);
+    let white = xy_to_xyz(self.white_point());
+    let primaries = Matrix3::from_columns(r, g, b);
+    // primaries are valid chromaticities, so they are never collinear
+    let scale = primaries.inverse().unwrap() * white;
+    let to_xyz = primaries * Matrix3::diagonal(scale);
+    if self.white_point() == D65 {
+      to_xyz
+    } else {
+      chromatic_adaptation(white, xy_to_xyz(D65)) * to_xyz
+    }
+  }
+
+  pub fn from_xyz(&self) -> Matrix3 {
+    self.to_xyz().inverse().unwrap()
+  }
+
+  /// Produces the matrix taking linear RGB in this space to linear RGB in other
+  pub fn conversion_to(&self, other: ColourSpace) -> Matrix3 {
+    if *self == other {
+      Matrix3::IDENTITY
+    } else {
+      other.from_xyz() * self.to_xyz()
+    }
+  }
+
+  pub fn convert(&self, colour: Colour, other: ColourSpace) -> Colour {
+    self.conversion_to(other) * colour
+  }
+
+  /// Produces the display encoded value of a linear one
+  pub fn encode(&self, linear: f64) -> f64 {
+    match self {
+      Self::Srgb | Self::DisplayP3 => colour::srgb_encode(linear),
+      Self::Rec2020 => {
+        if linear < REC2020_BETA {
+          4.5 * linear
+        } else {
+          REC2020_ALPHA * linear.powf(0.45) - (REC2020_ALPHA - 1.0)
+        }
+      },
+      Self::AcesCg => linear,
+    }
+  }
+
+  /// Produces the linear value of a display encoded one
+  pub fn decode(&self, encoded: f64) -> f64 {
+    match self {
+      Self::Srgb | Self::DisplayP3 => colour::srgb_decode(encoded),
+      Self::Rec2020 => {
+        if encoded < 4.5 * REC2020_BETA {
+          encoded / 4.5
+        } else {
+          ((encoded + REC2020_ALPHA - 1.0) / REC2020_ALPHA).powf(1.0 / 0.45)
+        }
+      },
+      Self::AcesCg => encoded,
+    }
+  }
+
+  /// Produces the 8-bit pixel of a linear colour in this space, clamped to
+  /// the displayable range, encoded and rounded
+  pub fn to_pixel(&self, colour: Colour) -> Pixel {
+    let interval = Interval::new(0.0, 1.0);
+    let quantise = |value: f64| (self.encode(interval.clamp(value)) * 255.0).round() as u8;
+    Pixel::new(quantise(colour.x), quantise(colour.y), quantise(colour.z))
+  }
+}
+
+fn xy_to_xyz((x, y): (f64, f64)) -> Vector3 {
+  Vector3::new(x / y, 1.0, (1.0 - x - y) / y)
+}
+
+/// Produces the Bradford transform taking XYZ seen under white from to XYZ
+/// seen under white to
+fn chromatic_adaptation(from: Vector3, to: Vector3) -> Matrix3 {
+  let (from, to) = (BRADFORD * from, BRADFORD * to);
+  let scale = Matrix3::diagonal(Vector3::new(to.x / from.x, to.y / from.y, to.z / from.z));
+  BRADFORD.inverse().unwrap() * scale * BRADFORD
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use rstest::*;
+
+  fn assert_matrix_eq(m: Matrix3, expected: [[f64; 3]; 3], tolerance: f64) {
+    for (row, expected_row) in m.m.iter().zip(expected.iter()) {
+      for (value, expected) in row.iter().zip(expected_row.iter()) {
+        assert!((value - expected).abs() < tolerance, "{m:?} != {expected_row:?}");
+      }
+    }
+  }
+
+  const SPACES: [ColourSpace; 4] = [ColourSpace::Srgb, ColourSpace::DisplayP3, ColourSpace::Rec2020, ColourSpace::AcesCg];
+
+  mod colour_space {
+    use super::*;
+
+    #[rstest]
+    fn to_xyz() {
+      let expected = [[0.4124, 0.3576, 0.1805], [0.2126, 0.7152, 0.0722], [0.0193, 0.1192, 0.9505]];
+      assert_matrix_eq(ColourSpace::Srgb.to_xyz(), expected, 1e-4);
+      // white in every space is the same D65 white in XYZ
+      for space in SPACES {
+        let white = space.to_xyz() * Colour::new(1.0, 1.0, 1.0);
+        assert!(vector::near_zero(white - xy_to_xyz(D65)), "{space:?} white is {white:?}");
+      }
+    }
+
+    #[rstest]
+    #[case(ColourSpace::DisplayP3, [[0.8225, 0.1774, 0.0], [0.0332, 0.9669, 0.0], [0.0171, 0.0724, 0.9108]])]
+    #[case(ColourSpace::Rec2020, [[0.6274, 0.3293, 0.0433], [0.0691, 0.9195, 0.0114], [0.0164, 0.0880, 0.8956]])]
+    #[case(ColourSpace::AcesCg, [[0.6131, 0.3395, 0.0474], [0.0702, 0.9164, 0.0135], [0.0206, 0.1096, 0.8698]])]
+    fn conversion_to(#[case] space: ColourSpace, #[case] expected: [[f64; 3]; 3]) {
+      assert_matrix_eq(ColourSpace::Srgb.conversion_to(space), expected, 1e-3);
+      assert_eq!(space.conversion_to(space), Matrix3::IDENTITY);
+    }
+
+    #[rstest]
+    fn convert() {
+      let colour = Colour::new(0.2, 0.5, 0.9);
+      for from in SPACES {
+        for to in SPACES {
+          let back = to.convert(from.convert(colour, to), from);
+          assert!(vector::near_zero(back - colour), "{from:?} -> {to:?}");
+        }
+        // grey stays grey, since every white point maps to white
+        let grey = ColourSpace::Srgb.convert(Colour::new(0.5, 0.5, 0.5), from);
+        assert!(vector::near_zero(grey - Colour::new(0.5, 0.5, 0.5)));
+      }
+    }
+
+    #[rstest]
+    #[case(ColourSpace::Srgb, 0.5, 0.7353569830524495)]
+    #[case(ColourSpace::Srgb, 0.001, 0.01292)]
+    #[case(ColourSpace::DisplayP3, 0.5, 0.7353569830524495)]
+    #[case(ColourSpace::Rec2020, 0.5, 0.7054355530556183)]
+    #[case(ColourSpace::Rec2020, 0.01, 0.045)]
+    #[case(ColourSpace::AcesCg, 0.5, 0.5)]
+    fn encode_decode(#[case] space: ColourSpace, #[case] linear: f64, #[case] expected: f64) {
+      assert!((space.encode(linear) - expected).abs() < 1e-12);
+      assert!((space.decode(expected) - linear).abs() < 1e-12);
+    }
+
+    #[rstest]
+    fn encode_continuous() {
+      // each piecewise curve meets itself where it switches pieces
+      for space in SPACES {
+        let mut last = 0.0;
+        for n in 1..=1000 {
+          let value = space.encode(n as f64 / 1000.0);
+          assert!(value > last && value - last < 0.02, "{space:?} jumps at {n}");
+          last = value;
+        }
+        assert!((last - 1.0).abs() < 1e-9);
+      }
+    }
+
+    #[rstest]
+    fn to_pixel() {
+      assert_eq!(ColourSpace::Srgb.to_pixel(Colour::new(0.0, 0.5, 2.0)), Pixel::new(0, 188, 255));
+      assert_eq!(ColourSpace::AcesCg.to_pixel(Colour::new(-1.0, 0.5, 1.0)), Pixel::new(0, 128, 255));
+    }
+  }
+}
@@ -0,0 +1,69 @@
+
+use crate::*;
+
+use framebuffer::Framebuffer;
+
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+
+/// Writes framebuffers out in one image format
+pub trait ImageEncoder {
+  /// Produces the file extension the format is saved with
+  fn extension(&self) -> &'static str;
+
+  fn encode(&self, framebuffer: &Framebuffer, writer: &mut dyn Write) -> Result<(), RaytracerError>;
+
+  fn encode_to_vec(&self, framebuffer: &Framebuffer) -> Result<Vec<u8>, RaytracerError> {
+    let mut bytes = Vec::new();
+    self.encode(framebuffer, &mut bytes)?;
+    Ok(bytes)
+  }
+
+  /// Produces number of bytes written
+  fn save_to_path(&self, framebuffer: &Framebuffer, path: &Path) -> Result<usize, RaytracerError> {
+    let bytes = self.encode_to_vec(framebuffer)?;
+    fs::write(path, &bytes)?;
+    Ok(bytes.len())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use rstest::*;
+
+  use colour::Colour;
+
+  use std::env;
+
+  // writes each pixel's sample count as one byte
+  struct Counts;
+  impl ImageEncoder for Counts {
+    fn extension(&self) -> &'static str {
+      "counts"
+    }
+
+    fn encode(&self, framebuffer: &Framebuffer, writer: &mut dyn Write) -> Result<(), RaytracerError> {
+      let bytes = framebuffer.sample_counts().iter().map(|&count| count as u8).collect::<Vec<_>>();
+      writer.write_all(&bytes)?;
+      Ok(())
+    }
+  }
+
+  #[rstest]
+  fn encode_to_vec() {
+    let mut framebuffer = Framebuffer::new(2, 2);
+    framebuffer.set(1, 0, Colour::default(), 3);
+    assert_eq!(Counts.encode_to_vec(&framebuffer).unwrap(), vec![0, 3, 0, 0]);
+  }
+
+  #[rstest]
+  fn save_to_path() {
+    let framebuffer = Framebuffer::from_pixels(3, 1, vec![Colour::default(); 3]).unwrap();
+    let path = env::temp_dir().join(format!("encoder_save_to_path.{}", Counts.extension()));
+    assert_eq!(Counts.save_to_path(&framebuffer, &path).unwrap(), 3);
+    assert_eq!(fs::read(&path).unwrap(), vec![1, 1, 1]);
+    fs::remove_file(&path).unwrap();
+  }
+}
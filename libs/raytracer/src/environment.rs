@@ -0,0 +1,225 @@
+
+use crate::*;
+
+use ray::Ray;
+use colour::Colour;
+use vector::Vector3;
+
+use std::fmt;
+use std::sync::Arc;
+use std::f64::consts::PI;
+
+/// Radiance arriving along rays that escape the scene
+#[derive(Clone)]
+pub enum Environment {
+  Solid(Colour),
+  /// Vertical blend from bottom (straight down) to top (straight up)
+  Gradient { bottom: Colour, top: Colour },
+  Closure(Arc<dyn Fn(&Ray) -> Colour + Send + Sync>),
+  Map(Arc<EnvironmentMap>),
+}
+
+impl Environment {
+  /// Produces the blue-white sky gradient
+  pub fn sky() -> Self {
+    Self::Gradient {
+      bottom: Colour::new(1.0, 1.0, 1.0),
+      top: Colour::new(0.5, 0.7, 1.0),
+    }
+  }
+
+  pub fn closure(f: impl Fn(&Ray) -> Colour + Send + Sync + 'static) -> Self {
+    Self::Closure(Arc::new(f))
+  }
+
+  pub fn sample(&self, ray: &Ray) -> Colour {
+    match self {
+      Self::Solid(colour) => *colour,
+      Self::Gradient { bottom, top } => {
+        let unit_direction = ray.direction().to_unit();
+        let a = 0.5*(unit_direction.y + 1.0);
+        (1.0-a)*(*bottom) + a*(*top)
+      },
+      Self::Closure(f) => f(ray),
+      Self::Map(map) => map.sample(ray.direction()),
+    }
+  }
+}
+
+impl Default for Environment {
+  fn default() -> Self {
+    Self::sky()
+  }
+}
+
+impl fmt::Debug for Environment {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::Solid(colour) => f.debug_tuple("Solid").field(colour).finish(),
+      Self::Gradient { bottom, top } => f
+        .debug_struct("Gradient")
+        .field("bottom", bottom)
+        .field("top", top)
+        .finish(),
+      Self::Closure(_) => f.write_str("Closure(..)"),
+      Self::Map(map) => f.debug_tuple("Map").field(map).finish(),
+    }
+  }
+}
+
+impl PartialEq for Environment {
+  fn eq(&self, other: &Self) -> bool {
+    match (self, other) {
+      (Self::Solid(a), Self::Solid(b)) => a == b,
+      (Self::Gradient { bottom: a0, top: a1 }, Self::Gradient { bottom: b0, top: b1 }) => a0 == b0 && a1 == b1,
+      // closures cannot be compared, only identified
+      (Self::Closure(a), Self::Closure(b)) => Arc::ptr_eq(a, b),
+      (Self::Map(a), Self::Map(b)) => a == b,
+      _ => false,
+    }
+  }
+}
+
+/// Equirectangular (latitude-longitude) image of linear radiance, top row first
+#[derive(Debug, Clone, PartialEq)]
+pub struct EnvironmentMap {
+  width: usize,
+  height: usize,
+  pixels: Vec<Colour>,
+}
+
+impl EnvironmentMap {
+  pub fn new(width: usize, height: usize, pixels: Vec<Colour>) -> Result<Self, RaytracerError> {
+    if width == 0 || height == 0 || pixels.len() != width * height {
+      return Err(RaytracerError::EnvironmentMapError);
+    }
+    Ok(Self { width, height, pixels, })
+  }
+
+  pub fn width(&self) -> usize {
+    self.width
+  }
+
+  pub fn height(&self) -> usize {
+    self.height
+  }
+
+  /// Produces the bilinearly filtered radiance seen looking along direction
+  pub fn sample(&self, direction: Vector3) -> Colour {
+    let (u, v) = direction_to_uv(direction.to_unit());
+
+    // texel centres sit at half-integer coordinates
+    let x = u * self.width as f64 - 0.5;
+    let y = (1.0 - v) * self.height as f64 - 0.5;
+    let (x0, y0) = (x.floor(), y.floor());
+    let (tx, ty) = (x - x0, y - y0);
+
+    let texel = |i: f64, j: f64| -> Colour {
+      // longitude wraps around, latitude clamps at the poles
+      let i = (i as i64).rem_euclid(self.width as i64) as usize;
+      let j = (j as i64).clamp(0, self.height as i64 - 1) as usize;
+      self.pixels[j * self.width + i]
+    };
+
+    let top = (1.0-tx)*texel(x0, y0) + tx*texel(x0 + 1.0, y0);
+    let bottom = (1.0-tx)*texel(x0, y0 + 1.0) + tx*texel(x0 + 1.0, y0 + 1.0);
+    (1.0-ty)*top + ty*bottom
+  }
+}
+
+/// Produces the equirectangular coordinates of a unit direction; u wraps
+/// around the y axis starting from -x, v runs from 0 (down) to 1 (up)
+pub fn direction_to_uv(direction: Vector3) -> (f64, f64) {
+  let theta = (-direction.y).clamp(-1.0, 1.0).acos();
+  let phi = (-direction.z).atan2(direction.x) + PI;
+  (phi / (2.0*PI), theta / PI)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use rstest::*;
+
+  use point::Point3;
+
+  mod environment {
+    use super::*;
+
+    #[rstest]
+    #[case(Vector3::new(0.0, 1.0, 0.0), Colour::new(0.5, 0.7, 1.0))]
+    #[case(Vector3::new(0.0, -1.0, 0.0), Colour::new(1.0, 1.0, 1.0))]
+    #[case(Vector3::new(1.0, 0.0, 0.0), Colour::new(0.75, 0.85, 1.0))]
+    fn sky(#[case] direction: Vector3, #[case] expected: Colour) {
+      let ray = Ray::new(Point3::default(), direction);
+      assert_eq!(Environment::sky().sample(&ray), expected);
+      assert_eq!(Environment::default(), Environment::sky());
+    }
+
+    #[rstest]
+    fn solid() {
+      let colour = Colour::new(0.1, 0.2, 0.3);
+      let environment = Environment::Solid(colour);
+      for direction in [Vector3::new(0.0, 1.0, 0.0), Vector3::new(-1.0, -1.0, 0.5)] {
+        assert_eq!(environment.sample(&Ray::new(Point3::default(), direction)), colour);
+      }
+    }
+
+    #[rstest]
+    fn closure() {
+      let environment = Environment::closure(|ray: &Ray| {
+        if ray.direction().y > 0.0 { Colour::new(1.0, 1.0, 1.0) } else { Colour::default() }
+      });
+      let up = Ray::new(Point3::default(), Vector3::new(0.0, 1.0, 0.0));
+      let down = Ray::new(Point3::default(), Vector3::new(0.0, -1.0, 0.0));
+      assert_eq!(environment.sample(&up), Colour::new(1.0, 1.0, 1.0));
+      assert_eq!(environment.sample(&down), Colour::default());
+      assert_eq!(environment, environment.clone());
+      assert_ne!(environment, Environment::closure(|_: &Ray| Colour::default()));
+    }
+
+    #[rstest]
+    fn map() {
+      // upper half bright, lower half dark
+      let (bright, dark) = (Colour::new(2.0, 2.0, 2.0), Colour::new(0.0, 0.0, 0.0));
+      let pixels = vec![bright, bright, bright, bright, dark, dark, dark, dark];
+      let environment = Environment::Map(Arc::new(EnvironmentMap::new(4, 2, pixels).unwrap()));
+      let up = Ray::new(Point3::default(), Vector3::new(0.0, 1.0, 0.0));
+      let down = Ray::new(Point3::default(), Vector3::new(0.3, -1.0, 0.2));
+      assert_eq!(environment.sample(&up), bright);
+      assert_eq!(environment.sample(&down), dark);
+    }
+  }
+
+  mod environment_map {
+    use super::*;
+
+    #[rstest]
+    #[case(2, 1, 2, true)]
+    #[case(2, 1, 3, false)]
+    #[case(0, 0, 0, false)]
+    fn new(#[case] width: usize, #[case] height: usize, #[case] count: usize, #[case] expected: bool) {
+      let map = EnvironmentMap::new(width, height, vec![Colour::default(); count]);
+      assert_eq!(map.is_ok(), expected);
+    }
+
+    #[rstest]
+    fn sample_wraps() {
+      // a single column seen from every longitude is constant
+      let pixels = vec![Colour::new(1.0, 0.0, 0.0), Colour::new(0.0, 0.0, 1.0)];
+      let map = EnvironmentMap::new(1, 2, pixels).unwrap();
+      let a = map.sample(Vector3::new(1.0, 0.1, 0.0));
+      let b = map.sample(Vector3::new(0.0, 0.1, -1.0));
+      assert!(vector::near_zero(a - b));
+    }
+
+    #[rstest]
+    #[case(Vector3::new(0.0, 1.0, 0.0), 1.0)]
+    #[case(Vector3::new(0.0, -1.0, 0.0), 0.0)]
+    #[case(Vector3::new(1.0, 0.0, 0.0), 0.5)]
+    fn direction_to_uv(#[case] direction: Vector3, #[case] expected_v: f64) {
+      let (u, v) = super::direction_to_uv(direction);
+      assert!((0.0..=1.0).contains(&u));
+      assert!((v - expected_v).abs() < 1e-12);
+    }
+  }
+}
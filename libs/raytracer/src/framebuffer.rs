@@ -0,0 +1,234 @@
+
+use crate::*;
+
+use colour::{Colour, Pixel};
+use tonemap::ToneMap;
+use colourspace::ColourSpace;
+
+/// Linear radiance of a rendered image, top row first, with how many samples
+/// each pixel took and how it is tone mapped and converted for output
+#[derive(Debug, Clone, PartialEq)]
+pub struct Framebuffer {
+  width: usize,
+  height: usize,
+  pixels: Vec<Colour>,
+  sample_counts: Vec<usize>,
+  tone_map: ToneMap,
+  // space the pixels were rendered in
+  colour_space: ColourSpace,
+  // space the pixels are written out in
+  output_space: ColourSpace,
+}
+
+impl Framebuffer {
+  /// Produces a black framebuffer with no samples taken
+  pub fn new(width: usize, height: usize) -> Self {
+    Self {
+      width,
+      height,
+      pixels: vec![Colour::default(); width * height],
+      sample_counts: vec![0; width * height],
+      tone_map: ToneMap::default(),
+      colour_space: ColourSpace::default(),
+      output_space: ColourSpace::default(),
+    }
+  }
+
+  /// Produces a framebuffer holding pixels, each counted as one sample
+  pub fn from_pixels(width: usize, height: usize, pixels: Vec<Colour>) -> Result<Self, RaytracerError> {
+    if width == 0 || height == 0 || pixels.len() != width * height {
+      return Err(RaytracerError::FramebufferError);
+    }
+    let mut framebuffer = Self::new(width, height);
+    framebuffer.pixels = pixels;
+    framebuffer.sample_counts.fill(1);
+    Ok(framebuffer)
+  }
+
+  pub fn width(&self) -> usize {
+    self.width
+  }
+
+  pub fn height(&self) -> usize {
+    self.height
+  }
+
+  pub fn pixels(&self) -> &[Colour] {
+    &self.pixels
+  }
+
+  pub fn sample_counts(&self) -> &[usize] {
+    &self.sample_counts
+  }
+
+  pub fn tone_map(&self) -> ToneMap {
+    self.tone_map
+  }
+
+  pub fn set_tone_map(&mut self, tone_map: ToneMap) {
+    self.tone_map = tone_map;
+  }
+
+  pub fn colour_space(&self) -> ColourSpace {
+    self.colour_space
+  }
+
+  pub fn set_colour_space(&mut self, colour_space: ColourSpace) {
+    self.colour_space = colour_space;
+  }
+
+  pub fn output_space(&self) -> ColourSpace {
+    self.output_space
+  }
+
+  pub fn set_output_space(&mut self, output_space: ColourSpace) {
+    self.output_space = output_space;
+  }
+
+  pub fn get(&self, i: usize, j: usize) -> Colour {
+    self.pixels[self.index(i, j)]
+  }
+
+  pub fn set(&mut self, i: usize, j: usize, colour: Colour, sample_count: usize) {
+    let index = self.index(i, j);
+    self.pixels[index] = colour;
+    self.sample_counts[index] = sample_count;
+  }
+
+  /// Produces the linear radiance converted to the output space, top row first
+  pub fn to_output(&self) -> Vec<Colour> {
+    let conversion = self.colour_space.conversion_to(self.output_space);
+    self.pixels.iter().map(|&colour| conversion * colour).collect()
+  }
+
+  /// Produces the tone mapped colours converted to the output space, still
+  /// linear, top row first
+  pub fn tone_mapped(&self) -> Vec<Colour> {
+    let conversion = self.colour_space.conversion_to(self.output_space);
+    self.pixels.iter().map(|&colour| conversion * self.tone_map.apply(colour)).collect()
+  }
+
+  /// Produces the tone mapped 8-bit pixels, converted to the output space and
+  /// encoded with its transfer function, top row first
+  pub fn to_pixels(&self) -> Vec<Pixel> {
+    self.tone_mapped().into_iter().map(|colour| self.output_space.to_pixel(colour)).collect()
+  }
+
+  /// Produces an sRGB image, shown as is, of how many samples each pixel
+  /// took, scaled to the most any pixel took
+  pub fn heatmap(&self) -> Framebuffer {
+    let max = self.sample_counts.iter().copied().max().unwrap_or(0);
+    let mut heatmap = Self::new(self.width, self.height);
+    heatmap.pixels = self.sample_counts.iter().map(|&count| adaptive::heatmap_colour(count, max)).collect();
+    heatmap.sample_counts.clone_from(&self.sample_counts);
+    heatmap
+  }
+
+  fn index(&self, i: usize, j: usize) -> usize {
+    assert!(i < self.width && j < self.height, "pixel ({i}, {j}) is outside the framebuffer");
+    j * self.width + i
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use rstest::*;
+
+  mod framebuffer {
+    use super::*;
+
+    use tonemap::ToneMapOperator;
+
+    #[rstest]
+    fn new() {
+      let framebuffer = Framebuffer::new(3, 2);
+      assert_eq!(framebuffer.width(), 3);
+      assert_eq!(framebuffer.height(), 2);
+      assert_eq!(framebuffer.pixels(), &[Colour::default(); 6]);
+      assert_eq!(framebuffer.sample_counts(), &[0; 6]);
+    }
+
+    #[rstest]
+    #[case(2, 2, 4, true)]
+    #[case(2, 2, 3, false)]
+    #[case(0, 2, 0, false)]
+    fn from_pixels(#[case] width: usize, #[case] height: usize, #[case] count: usize, #[case] expected: bool) {
+      let framebuffer = Framebuffer::from_pixels(width, height, vec![Colour::new(0.5, 0.5, 0.5); count]);
+      assert_eq!(framebuffer.is_ok(), expected);
+      if let Ok(framebuffer) = framebuffer {
+        assert_eq!(framebuffer.sample_counts(), &[1; 4]);
+      }
+    }
+
+    #[rstest]
+    fn get_set() {
+      let mut framebuffer = Framebuffer::new(3, 2);
+      framebuffer.set(2, 1, Colour::new(1.0, 0.5, 0.25), 7);
+      assert_eq!(framebuffer.get(2, 1), Colour::new(1.0, 0.5, 0.25));
+      assert_eq!(framebuffer.pixels()[5], Colour::new(1.0, 0.5, 0.25));
+      assert_eq!(framebuffer.sample_counts()[5], 7);
+      assert_eq!(framebuffer.get(1, 1), Colour::default());
+    }
+
+    #[rstest]
+    #[should_panic]
+    fn get_outside() {
+      Framebuffer::new(3, 2).get(3, 0);
+    }
+
+    #[rstest]
+    fn to_pixels() {
+      let pixels = vec![Colour::new(0.0, 0.25, 1.0), Colour::new(4.0, 1.0, 0.0)];
+      let framebuffer = Framebuffer::from_pixels(2, 1, pixels).unwrap();
+      assert_eq!(framebuffer.to_pixels(), vec![Pixel::new(0, 137, 255), Pixel::new(255, 255, 0)]);
+    }
+
+    #[rstest]
+    fn to_pixels_tone_map() {
+      let pixels = vec![Colour::new(0.0, 0.25, 1.0), Colour::new(4.0, 1.0, 0.0)];
+      let mut framebuffer = Framebuffer::from_pixels(2, 1, pixels).unwrap();
+      assert_eq!(framebuffer.tone_map(), ToneMap::default());
+      framebuffer.set_tone_map(ToneMap::new(ToneMapOperator::Reinhard, 1.0));
+      // doubled, then x / (1 + x): 0.5 -> 1/3, 2 -> 2/3, 8 -> 8/9
+      assert_eq!(framebuffer.to_pixels(), vec![Pixel::new(0, 156, 213), Pixel::new(242, 213, 0)]);
+      // the linear radiance is kept as rendered
+      assert_eq!(framebuffer.get(0, 0), Colour::new(0.0, 0.25, 1.0));
+      assert_eq!(framebuffer.tone_mapped()[1], Colour::new(8.0 / 9.0, 2.0 / 3.0, 0.0));
+    }
+
+    #[rstest]
+    fn colour_spaces() {
+      let pixels = vec![Colour::new(1.0, 0.0, 0.0), Colour::new(0.4, 0.4, 0.4)];
+      let mut framebuffer = Framebuffer::from_pixels(2, 1, pixels.clone()).unwrap();
+      assert_eq!(framebuffer.colour_space(), ColourSpace::Srgb);
+      assert_eq!(framebuffer.output_space(), ColourSpace::Srgb);
+      assert_eq!(framebuffer.to_output(), pixels);
+
+      framebuffer.set_output_space(ColourSpace::AcesCg);
+      let output = framebuffer.to_output();
+      assert_eq!(output[0], ColourSpace::Srgb.convert(pixels[0], ColourSpace::AcesCg));
+      assert!(vector::near_zero(output[1] - pixels[1]));
+      // acescg is linear, so pixels are only clamped
+      assert_eq!(framebuffer.to_pixels()[1], Pixel::new(102, 102, 102));
+
+      framebuffer.set_colour_space(ColourSpace::AcesCg);
+      assert_eq!(framebuffer.to_output(), pixels);
+    }
+
+    #[rstest]
+    fn heatmap() {
+      let mut framebuffer = Framebuffer::new(2, 1);
+      framebuffer.set(0, 0, Colour::default(), 4);
+      framebuffer.set(1, 0, Colour::default(), 16);
+      framebuffer.set_tone_map(ToneMap::new(ToneMapOperator::Aces, 2.0));
+      framebuffer.set_output_space(ColourSpace::Rec2020);
+      let heatmap = framebuffer.heatmap();
+      assert_eq!(heatmap.tone_map(), ToneMap::default());
+      assert_eq!(heatmap.output_space(), ColourSpace::Srgb);
+      assert_eq!(heatmap.get(0, 0), adaptive::heatmap_colour(1, 4));
+      assert_eq!(heatmap.get(1, 0), adaptive::heatmap_colour(1, 1));
+      assert_eq!(heatmap.sample_counts(), framebuffer.sample_counts());
+    }
+  }
+}
@@ -1,7 +1,10 @@
 
+use rand::{Rng, RngCore};
+
 use crate::*;
 
 use ray::Ray;
+use aabb::Aabb;
 use point::Point3;
 use vector::Vector3;
 use interval::Interval;
@@ -18,6 +21,9 @@ pub struct HitRecord {
   // scale distance of ray
   pub d: f64,
   pub front_face: bool,
+  // surface coordinates at position
+  pub u: f64,
+  pub v: f64,
   pub material: Option<Arc<dyn Material>>,
 }
 
@@ -28,6 +34,8 @@ impl HitRecord {
       normal,
       d,
       front_face: false,
+      u: 0.0,
+      v: 0.0,
       material: None,
     }
   }
@@ -38,15 +46,49 @@ impl HitRecord {
   }
 }
 
-pub trait Hittable {
-  fn hit(&mut self, ray: &Ray, ray_i: Interval, record: &mut HitRecord) -> bool;
+/// Geometry that can be intersected by rays; shared between render threads
+pub trait Hittable: Send + Sync {
+  fn hit(&self, ray: &Ray, ray_i: Interval, record: &mut HitRecord) -> bool;
+
+  /// Produces a box enclosing every point the hittable can be hit at
+  fn bounding_box(&self) -> Aabb;
+
+  /// Produces the density, per unit solid angle, with which random picks the
+  /// direction of ray from its position; 0 for hittables that cannot be sampled
+  fn pdf_value(&self, _ray: &Ray) -> f64 {
+    0.0
+  }
+
+  /// Produces a direction from origin towards a random point on the hittable
+  /// as it is at time
+  fn random(&self, _rng: &mut dyn RngCore, _origin: Point3, _time: f64) -> Vector3 {
+    Vector3::new(1.0, 0.0, 0.0)
+  }
+}
+
+impl<T: Hittable + ?Sized> Hittable for Arc<T> {
+  fn hit(&self, ray: &Ray, ray_i: Interval, record: &mut HitRecord) -> bool {
+    self.as_ref().hit(ray, ray_i, record)
+  }
+
+  fn bounding_box(&self) -> Aabb {
+    self.as_ref().bounding_box()
+  }
+
+  fn pdf_value(&self, ray: &Ray) -> f64 {
+    self.as_ref().pdf_value(ray)
+  }
+
+  fn random(&self, rng: &mut dyn RngCore, origin: Point3, time: f64) -> Vector3 {
+    self.as_ref().random(rng, origin, time)
+  }
 }
 
 /// Default type for Vec of hittables
 pub type VecOfHittable = Vec<Box<dyn Hittable>>;
 
 impl Hittable for VecOfHittable {
-  fn hit(&mut self, ray: &Ray, ray_i: Interval, record: &mut HitRecord) -> bool {
+  fn hit(&self, ray: &Ray, ray_i: Interval, record: &mut HitRecord) -> bool {
     let mut temp_record = HitRecord::default();
     let mut hit_anything = false;
     let mut closest_so_far = ray_i.max;
@@ -61,6 +103,42 @@ impl Hittable for VecOfHittable {
 
     hit_anything
   }
+
+  fn bounding_box(&self) -> Aabb {
+    self
+      .iter()
+      .fold(aabb::EMPTY, |bbox, object| Aabb::surrounding(bbox, object.bounding_box()))
+  }
+
+  fn pdf_value(&self, ray: &Ray) -> f64 {
+    mixture_pdf_value(self, ray)
+  }
+
+  fn random(&self, rng: &mut dyn RngCore, origin: Point3, time: f64) -> Vector3 {
+    mixture_random(self, rng, origin, time)
+  }
+}
+
+/// Produces the density with which mixture_random picks the direction of ray
+pub fn mixture_pdf_value(objects: &[impl AsRef<dyn Hittable>], ray: &Ray) -> f64 {
+  if objects.is_empty() {
+    return 0.0;
+  }
+  objects.iter().map(|object| object.as_ref().pdf_value(ray)).sum::<f64>() / objects.len() as f64
+}
+
+/// Produces a direction from origin towards one of objects, each picked
+/// equally often
+pub fn mixture_random(
+  objects: &[impl AsRef<dyn Hittable>],
+  rng: &mut dyn RngCore,
+  origin: Point3,
+  time: f64,
+) -> Vector3 {
+  if objects.is_empty() {
+    return Vector3::new(1.0, 0.0, 0.0);
+  }
+  objects[rng.gen_range(0..objects.len())].as_ref().random(rng, origin, time)
 }
 
 #[cfg(test)]
@@ -0,0 +1,323 @@
+
+use crate::*;
+
+use ray::Ray;
+use aabb::Aabb;
+use point::Point3;
+use vector::Vector3;
+use interval::Interval;
+use transform::Transform;
+use motion::{Keyframes, Pose};
+use hittable::{Hittable, HitRecord};
+
+use rand::RngCore;
+
+use std::sync::Arc;
+
+fn hit_transformed(
+  object: &dyn Hittable,
+  transform: &Transform,
+  ray: &Ray,
+  ray_i: Interval,
+  record: &mut HitRecord,
+) -> bool {
+  // distances along the object space ray match those along the world ray
+  let object_ray = transform.inverse().ray(ray);
+  if !object.hit(&object_ray, ray_i, record) {
+    return false;
+  }
+
+  record.position = transform.point(record.position);
+  record.normal = transform.normal(record.normal);
+  true
+}
+
+fn pdf_transformed(object: &dyn Hittable, transform: &Transform, ray: &Ray) -> f64 {
+  let inverse = transform.inverse();
+  let pdf = object.pdf_value(&inverse.ray(ray));
+  if pdf <= 0.0 {
+    return 0.0;
+  }
+
+  // mapping unit world directions w to A w / |A w| in object space scales
+  // solid angle by |det A| / |A w|^3, A being the inverse linear part
+  let linear = inverse.linear();
+  let stretch = (linear * ray.direction().to_unit()).length();
+  pdf * linear.determinant().abs() / stretch.powi(3)
+}
+
+fn random_transformed(
+  object: &dyn Hittable,
+  transform: &Transform,
+  rng: &mut dyn RngCore,
+  origin: Point3,
+  time: f64,
+) -> Vector3 {
+  let direction = object.random(rng, transform.inverse().point(origin), time);
+  transform.vector(direction)
+}
+
+/// Shared hittable placed in the world by a transform; the same object may
+/// back any number of instances
+#[derive(Clone)]
+pub struct Instance {
+  object: Arc<dyn Hittable>,
+  transform: Transform,
+  bbox: Aabb,
+}
+
+impl Instance {
+  pub fn new(object: Arc<dyn Hittable>, transform: Transform) -> Self {
+    let bbox = transform.bounding_box(&object.bounding_box());
+    Self { object, transform, bbox, }
+  }
+
+  pub fn transform(&self) -> &Transform {
+    &self.transform
+  }
+}
+
+impl Hittable for Instance {
+  fn hit(&self, ray: &Ray, ray_i: Interval, record: &mut HitRecord) -> bool {
+    hit_transformed(self.object.as_ref(), &self.transform, ray, ray_i, record)
+  }
+
+  fn bounding_box(&self) -> Aabb {
+    self.bbox
+  }
+
+  fn pdf_value(&self, ray: &Ray) -> f64 {
+    pdf_transformed(self.object.as_ref(), &self.transform, ray)
+  }
+
+  fn random(&self, rng: &mut dyn RngCore, origin: Point3, time: f64) -> Vector3 {
+    random_transformed(self.object.as_ref(), &self.transform, rng, origin, time)
+  }
+}
+
+/// Shared hittable whose placement changes over time, posed at each ray's
+/// time; renders with motion blur when the camera shutter is open over the motion
+#[derive(Clone)]
+pub struct MovingInstance {
+  object: Arc<dyn Hittable>,
+  motion: Keyframes<Pose>,
+  bbox: Aabb,
+}
+
+impl MovingInstance {
+  pub fn new(object: Arc<dyn Hittable>, motion: Keyframes<Pose>) -> Self {
+    let bbox = Self::swept_box(&object.bounding_box(), &motion);
+    Self { object, motion, bbox, }
+  }
+
+  pub fn motion(&self) -> &Keyframes<Pose> {
+    &self.motion
+  }
+
+  /// Produces a box enclosing the object over the whole motion
+  fn swept_box(bbox: &Aabb, motion: &Keyframes<Pose>) -> Aabb {
+    let keys = motion.keys();
+    let mut swept = keys
+      .iter()
+      .fold(aabb::EMPTY, |swept, (_, pose)| Aabb::surrounding(swept, pose.transform().bounding_box(bbox)));
+
+    // with rotation fixed, every point moves linearly between keyframes so the
+    // keyframe boxes suffice; otherwise bound the object by a sphere about its
+    // origin, which only the translation can move
+    let reach = [bbox.x, bbox.y, bbox.z]
+      .iter()
+      .map(|axis| axis.min.abs().max(axis.max.abs()).powi(2))
+      .sum::<f64>()
+      .sqrt();
+    for pair in keys.windows(2) {
+      let ((_, a), (_, b)) = (&pair[0], &pair[1]);
+      if a.rotation() == b.rotation() {
+        continue;
+      }
+      let scale = [a.scale, b.scale]
+        .iter()
+        .map(|s| s.x.abs().max(s.y.abs()).max(s.z.abs()))
+        .fold(0.0, f64::max);
+      let radius = scale * reach;
+      let radii = Vector3::new(radius, radius, radius);
+      let sphere_path = Aabb::surrounding(
+        Aabb::from_points(a.translation - radii, a.translation + radii),
+        Aabb::from_points(b.translation - radii, b.translation + radii),
+      );
+      swept = Aabb::surrounding(swept, sphere_path);
+    }
+
+    if [swept.x, swept.y, swept.z].iter().any(|axis| axis.min.is_nan() || axis.max.is_nan()) {
+      aabb::UNIVERSE
+    } else {
+      swept
+    }
+  }
+}
+
+impl Hittable for MovingInstance {
+  fn hit(&self, ray: &Ray, ray_i: Interval, record: &mut HitRecord) -> bool {
+    let transform = self.motion.at(ray.time()).transform();
+    hit_transformed(self.object.as_ref(), &transform, ray, ray_i, record)
+  }
+
+  fn bounding_box(&self) -> Aabb {
+    self.bbox
+  }
+
+  fn pdf_value(&self, ray: &Ray) -> f64 {
+    let transform = self.motion.at(ray.time()).transform();
+    pdf_transformed(self.object.as_ref(), &transform, ray)
+  }
+
+  fn random(&self, rng: &mut dyn RngCore, origin: Point3, time: f64) -> Vector3 {
+    let transform = self.motion.at(time).transform();
+    random_transformed(self.object.as_ref(), &transform, rng, origin, time)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use rstest::*;
+
+  use testing::{UnitSphere, assert_pdf_integrates};
+
+  mod instance {
+    use super::*;
+
+    fn ellipsoid() -> Instance {
+      let transform = Transform::identity()
+        .scale(Vector3::new(2.0, 1.0, 1.0))
+        .translate(Vector3::new(0.0, 0.0, -5.0));
+      Instance::new(Arc::new(UnitSphere), transform)
+    }
+
+    #[rstest]
+    fn bounding_box() {
+      let bbox = ellipsoid().bounding_box();
+      assert_eq!(bbox, Aabb::from_points(Point3::new(-2.0, -1.0, -6.0), Point3::new(2.0, 1.0, -4.0)));
+    }
+
+    #[rstest]
+    #[case(Ray::new(Point3::new(1.9, 0.0, 0.0), Vector3::new(0.0, 0.0, -1.0)), true)]
+    #[case(Ray::new(Point3::new(-1.9, 0.0, 0.0), Vector3::new(0.0, 0.0, -1.0)), true)]
+    #[case(Ray::new(Point3::new(0.0, 1.1, 0.0), Vector3::new(0.0, 0.0, -1.0)), false)]
+    #[case(Ray::new(Point3::new(2.1, 0.0, 0.0), Vector3::new(0.0, 0.0, -1.0)), false)]
+    fn hit(#[case] ray: Ray, #[case] expected: bool) {
+      let mut record = HitRecord::default();
+      assert_eq!(ellipsoid().hit(&ray, Interval::new(0.001, f64::INFINITY), &mut record), expected);
+    }
+
+    #[rstest]
+    fn hit_record() {
+      let ray = Ray::new(Point3::new(0.0, 0.0, 0.0), Vector3::new(0.0, 0.0, -2.0));
+      let mut record = HitRecord::default();
+      assert!(ellipsoid().hit(&ray, Interval::new(0.001, f64::INFINITY), &mut record));
+      assert_eq!(record.d, 2.0);
+      assert_eq!(record.position, Point3::new(0.0, 0.0, -4.0));
+      assert_eq!(record.normal, Vector3::new(0.0, 0.0, 1.0));
+      assert!(record.front_face);
+    }
+
+    #[rstest]
+    fn hit_normal_non_uniform() {
+      // off axis, the ellipsoid's normal is not the scaled sphere normal
+      let ray = Ray::new(Point3::new(1.0, 0.0, 0.0), Vector3::new(0.0, 0.0, -1.0));
+      let mut record = HitRecord::default();
+      assert!(ellipsoid().hit(&ray, Interval::new(0.001, f64::INFINITY), &mut record));
+
+      let p = record.position - Point3::new(0.0, 0.0, -5.0);
+      // gradient of (x/2)^2 + y^2 + z^2
+      let expected = Vector3::new(p.x / 4.0, p.y, p.z).to_unit();
+      assert!((record.normal - expected).length() < 1e-12);
+      assert!(((p.x / 2.0).powi(2) + p.y*p.y + p.z*p.z - 1.0).abs() < 1e-12);
+    }
+
+    #[rstest]
+    #[case(Point3::new(0.0, 0.0, 0.0))]
+    #[case(Point3::new(3.0, 2.0, -2.0))]
+    fn pdf_non_uniform(#[case] origin: Point3) {
+      let stretched = Instance::new(Arc::new(UnitSphere), ellipsoid().transform().rotate_y(30.0));
+      assert_pdf_integrates(&stretched, origin, 0.0);
+    }
+
+    #[rstest]
+    fn shared_object() {
+      let object: Arc<dyn Hittable> = Arc::new(UnitSphere);
+      let left = Instance::new(Arc::clone(&object), Transform::identity().translate(Vector3::new(-3.0, 0.0, 0.0)));
+      let right = Instance::new(object, Transform::identity().translate(Vector3::new(3.0, 0.0, 0.0)));
+      let ray = Ray::new(Point3::new(3.0, 0.0, 5.0), Vector3::new(0.0, 0.0, -1.0));
+      let mut record = HitRecord::default();
+      assert!(!left.hit(&ray, Interval::new(0.001, f64::INFINITY), &mut record));
+      assert!(right.hit(&ray, Interval::new(0.001, f64::INFINITY), &mut record));
+      assert_eq!(record.position, Point3::new(3.0, 0.0, 1.0));
+    }
+  }
+
+  mod moving_instance {
+    use super::*;
+
+    fn sliding() -> MovingInstance {
+      let motion = Keyframes::linear(
+        Pose::default().with_translation(Vector3::new(-2.0, 0.0, -5.0)),
+        Pose::default().with_translation(Vector3::new(2.0, 0.0, -5.0)),
+      );
+      MovingInstance::new(Arc::new(UnitSphere), motion)
+    }
+
+    #[rstest]
+    #[case(0.0, -2.0, true)]
+    #[case(0.0, 2.0, false)]
+    #[case(1.0, 2.0, true)]
+    #[case(0.5, 0.0, true)]
+    #[case(0.5, 2.0, false)]
+    fn hit(#[case] time: f64, #[case] x: f64, #[case] expected: bool) {
+      let ray = Ray::with_time(Point3::new(x, 0.0, 0.0), Vector3::new(0.0, 0.0, -1.0), time);
+      let mut record = HitRecord::default();
+      assert_eq!(sliding().hit(&ray, Interval::new(0.001, f64::INFINITY), &mut record), expected);
+      if expected {
+        assert_eq!(record.position, Point3::new(x, 0.0, -4.0));
+        assert_eq!(record.normal, Vector3::new(0.0, 0.0, 1.0));
+      }
+    }
+
+    #[rstest]
+    #[case(0.0)]
+    #[case(0.5)]
+    #[case(1.0)]
+    fn pdf(#[case] time: f64) {
+      assert_pdf_integrates(&sliding(), Point3::new(0.0, 0.5, -3.0), time);
+    }
+
+    #[rstest]
+    fn bounding_box_translation() {
+      let expected = Aabb::from_points(Point3::new(-3.0, -1.0, -6.0), Point3::new(3.0, 1.0, -4.0));
+      assert_eq!(sliding().bounding_box(), expected);
+    }
+
+    #[rstest]
+    fn bounding_box_rotation() {
+      // a long thin box spun a quarter turn sweeps past both keyframe boxes
+      struct Rod;
+      impl Hittable for Rod {
+        fn hit(&self, _: &Ray, _: Interval, _: &mut HitRecord) -> bool {
+          false
+        }
+
+        fn bounding_box(&self) -> Aabb {
+          Aabb::from_points(Point3::new(-2.0, -0.1, -0.1), Point3::new(2.0, 0.1, 0.1))
+        }
+      }
+      let axis = Vector3::new(0.0, 1.0, 0.0);
+      let motion = Keyframes::linear(Pose::default(), Pose::default().with_rotation(axis, 90.0));
+      let bbox = MovingInstance::new(Arc::new(Rod), motion.clone()).bounding_box();
+
+      let corner = Point3::new(2.0, 0.1, 0.1);
+      for step in 0..=20 {
+        let p = motion.at(step as f64 / 20.0).transform().point(corner);
+        assert!(bbox.x.contains(p.x) && bbox.y.contains(p.y) && bbox.z.contains(p.z));
+      }
+    }
+  }
+}
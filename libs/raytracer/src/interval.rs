@@ -13,6 +13,20 @@ impl Interval {
     Self { min, max, }
   }
 
+  /// Produces the tightest interval containing both a and b
+  pub fn enclosing(a: Interval, b: Interval) -> Self {
+    Self::new(a.min.min(b.min), a.max.max(b.max))
+  }
+
+  pub fn size(&self) -> f64 {
+    self.max - self.min
+  }
+
+  pub fn expand(&self, delta: f64) -> Self {
+    let padding = delta / 2.0;
+    Self::new(self.min - padding, self.max + padding)
+  }
+
   pub fn contains(&self, x: f64) -> bool {
     self.min <= x && x <= self.max
   }
@@ -65,5 +79,32 @@ mod tests {
       assert_eq!(interval.max, f64::INFINITY);
       assert_eq!(interval, UNIVERSE);
     }
+
+    #[rstest]
+    #[case(Interval::new(0.0, 1.0), Interval::new(2.0, 3.0), Interval::new(0.0, 3.0))]
+    #[case(Interval::new(-1.0, 5.0), Interval::new(0.0, 1.0), Interval::new(-1.0, 5.0))]
+    #[case(EMPTY, Interval::new(0.0, 1.0), Interval::new(0.0, 1.0))]
+    #[case(UNIVERSE, Interval::new(0.0, 1.0), UNIVERSE)]
+    fn enclosing(#[case] a: Interval, #[case] b: Interval, #[case] expected: Interval) {
+      assert_eq!(Interval::enclosing(a, b), expected);
+      assert_eq!(Interval::enclosing(b, a), expected);
+    }
+
+    #[rstest]
+    #[case(Interval::new(0.0, 0.0), 0.0)]
+    #[case(Interval::new(-1.0, 1.0), 2.0)]
+    #[case(UNIVERSE, f64::INFINITY)]
+    #[case(EMPTY, f64::NEG_INFINITY)]
+    fn size(#[case] interval: Interval, #[case] expected: f64) {
+      assert_eq!(interval.size(), expected);
+    }
+
+    #[rstest]
+    #[case(Interval::new(0.0, 0.0), 1.0, Interval::new(-0.5, 0.5))]
+    #[case(Interval::new(-1.0, 1.0), 2.0, Interval::new(-2.0, 2.0))]
+    #[case(Interval::new(3.0, 4.0), 0.0, Interval::new(3.0, 4.0))]
+    fn expand(#[case] interval: Interval, #[case] delta: f64, #[case] expected: Interval) {
+      assert_eq!(interval.expand(delta), expected);
+    }
   }
 }
\ No newline at end of file
@@ -4,27 +4,72 @@ use thiserror::Error;
 use std::io;
 
 pub mod ray;
+pub mod bvh;
+pub mod aabb;
+pub mod aperture;
 pub mod point;
-pub mod scene;
+pub mod tonemap;
+pub mod framebuffer;
+pub mod renderer;
+pub mod encoder;
+pub mod noise;
 pub mod camera;
 pub mod colour;
+pub mod colourspace;
 pub mod vector;
 pub mod interval;
+pub mod environment;
 pub mod material;
 pub mod hittable;
+pub mod texture;
+pub mod transform;
+pub mod instance;
+pub mod medium;
+pub mod motion;
+pub mod light;
+pub mod sampler;
+pub mod adaptive;
+
+#[cfg(test)]
+mod testing;
 
 pub mod prelude {
   #[allow(unused_imports)]
   pub use super::{
     ray::*,
+    bvh::*,
+    aabb::Aabb,
+    aperture::*,
     point::*,
-    scene::*,
+    tonemap::*,
+    framebuffer::*,
+    renderer::*,
+    encoder::*,
+    noise::*,
     camera::*,
     colour::*,
+    colourspace::*,
     vector::*,
     interval::*,
+    environment::*,
     material::*,
     hittable::*,
+    texture::*,
+    transform::{Matrix3, Matrix4, Transform},
+    instance::*,
+    medium::*,
+    motion::*,
+    light::*,
+    sampler::{
+      Sampler,
+      SamplerKind,
+      PixelSample,
+      IndependentSampler,
+      StratifiedSampler,
+      HaltonSampler,
+      SobolSampler,
+    },
+    adaptive::*,
     RaytracerError,
   };
 }
@@ -36,8 +81,52 @@ pub enum RaytracerError {
     #[from]
     source: io::Error,
   },
-  #[error("unable to render scene")]
-  SceneRenderError,
   #[error("unable to save scene")]
   SceneSaveError,
+  #[error("unable to encode image - {message}")]
+  ImageEncodeError {
+    message: String,
+  },
+  #[error("no encoder saves images with extension '{extension}'")]
+  UnknownExtensionError {
+    extension: String,
+  },
+  #[error("camera must look at a point other than its position, with vup not parallel to the view")]
+  CameraError,
+  #[error("aperture mask must have one non-negative weight per pixel, and some non-zero")]
+  ApertureMaskError,
+  #[error("environment map must have one colour per pixel")]
+  EnvironmentMapError,
+  #[error("framebuffer must have one colour per pixel")]
+  FramebufferError,
+  #[error("unable to load image")]
+  ImageLoadError,
+  #[error("unable to read image {file} - {message}")]
+  ImageParseError {
+    file: String,
+    message: String,
+  },
+  #[error("image texture must have one colour per pixel")]
+  ImageTextureError,
+  #[error("mesh indices, normals, texcoords or materials do not match its vertices and faces")]
+  MeshError,
+  #[error("transform matrix is not invertible")]
+  TransformError,
+  #[error("planar shape must have a non-zero normal and span a non-zero area")]
+  PlanarError,
+  #[error("density grid must have one finite, non-negative density per voxel")]
+  DensityGridError,
+  #[error("unable to load volume {file} - {message}")]
+  VolumeLoadError {
+    file: String,
+    message: String,
+  },
+  #[error("keyframes must be non-empty, at finite and strictly increasing times")]
+  KeyframeError,
+  #[error("unable to parse {file} on line {line} - {message}")]
+  ParseError {
+    file: String,
+    line: usize,
+    message: String,
+  },
 }
\ No newline at end of file
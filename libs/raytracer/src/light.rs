@@ -0,0 +1,150 @@
+
+use rand::RngCore;
+
+use crate::*;
+
+use ray::Ray;
+use point::Point3;
+use vector::Vector3;
+use hittable::Hittable;
+
+use std::fmt;
+use std::sync::Arc;
+
+/// Produces the power heuristic weight, with exponent 2, for a sample drawn
+/// with density pdf that another strategy could have drawn with other_pdf
+pub fn power_heuristic(pdf: f64, other_pdf: f64) -> f64 {
+  let (a, b) = (pdf * pdf, other_pdf * other_pdf);
+  if a + b > 0.0 { a / (a + b) } else { 0.0 }
+}
+
+/// Light emitting hittables sampled directly at each diffuse bounce; they
+/// must also be in the world so rays can hit them
+#[derive(Clone, Default)]
+pub struct Lights {
+  objects: Vec<Arc<dyn Hittable>>,
+}
+
+impl Lights {
+  pub fn new(objects: Vec<Arc<dyn Hittable>>) -> Self {
+    Self { objects, }
+  }
+
+  pub fn push(&mut self, object: Arc<dyn Hittable>) {
+    self.objects.push(object);
+  }
+
+  pub fn objects(&self) -> &[Arc<dyn Hittable>] {
+    &self.objects
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.objects.is_empty()
+  }
+
+  /// Produces the density with which random picks the direction of ray, with
+  /// each light picked equally often
+  pub fn pdf_value(&self, ray: &Ray) -> f64 {
+    hittable::mixture_pdf_value(&self.objects, ray)
+  }
+
+  /// Produces a direction from origin towards a random point on a random light
+  pub fn random(&self, rng: &mut dyn RngCore, origin: Point3, time: f64) -> Vector3 {
+    hittable::mixture_random(&self.objects, rng, origin, time)
+  }
+}
+
+impl fmt::Debug for Lights {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "Lights({})", self.objects.len())
+  }
+}
+
+impl PartialEq for Lights {
+  fn eq(&self, other: &Self) -> bool {
+    // hittables cannot be compared, only identified
+    self.objects.len() == other.objects.len()
+      && self.objects.iter().zip(other.objects.iter()).all(|(a, b)| Arc::ptr_eq(a, b))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use rstest::*;
+
+  use aabb::Aabb;
+  use interval::Interval;
+  use hittable::HitRecord;
+
+  use rand::SeedableRng;
+  use rand_chacha::ChaCha8Rng;
+
+  // light sampled as a single point
+  struct Point(Point3);
+  impl Hittable for Point {
+    fn hit(&self, _: &Ray, _: Interval, _: &mut HitRecord) -> bool {
+      false
+    }
+
+    fn bounding_box(&self) -> Aabb {
+      Aabb::from_points(self.0, self.0)
+    }
+
+    fn pdf_value(&self, ray: &Ray) -> f64 {
+      if vector::near_zero(ray.direction().to_unit() - (self.0 - ray.position()).to_unit()) { 1.0 } else { 0.0 }
+    }
+
+    fn random(&self, _: &mut dyn RngCore, origin: Point3, _: f64) -> Vector3 {
+      self.0 - origin
+    }
+  }
+
+  #[rstest]
+  #[case(1.0, 1.0, 0.5)]
+  #[case(3.0, 1.0, 0.9)]
+  #[case(0.0, 2.0, 0.0)]
+  #[case(0.0, 0.0, 0.0)]
+  fn power_heuristic(#[case] pdf: f64, #[case] other_pdf: f64, #[case] expected: f64) {
+    assert!((super::power_heuristic(pdf, other_pdf) - expected).abs() < 1e-15);
+  }
+
+  mod lights {
+    use super::*;
+
+    #[rstest]
+    fn empty() {
+      let mut rng = ChaCha8Rng::seed_from_u64(4);
+      let lights = Lights::default();
+      assert!(lights.is_empty());
+      let direction = lights.random(&mut rng, Point3::default(), 0.0);
+      assert_eq!(lights.pdf_value(&Ray::new(Point3::default(), direction)), 0.0);
+    }
+
+    #[rstest]
+    fn random() {
+      let mut rng = ChaCha8Rng::seed_from_u64(4);
+      let (a, b) = (Point3::new(0.0, 2.0, 0.0), Point3::new(3.0, 0.0, 0.0));
+      let lights = Lights::new(vec![Arc::new(Point(a)), Arc::new(Point(b))]);
+      let origin = Point3::default();
+      let mut counts = [0, 0];
+      for _ in 0..1000 {
+        let direction = lights.random(&mut rng, origin, 0.0);
+        if direction == a { counts[0] += 1 } else if direction == b { counts[1] += 1 }
+        // each light is picked half the time
+        assert_eq!(lights.pdf_value(&Ray::new(origin, direction)), 0.5);
+      }
+      assert_eq!(counts[0] + counts[1], 1000);
+      assert!(counts[0] > 400 && counts[1] > 400);
+    }
+
+    #[rstest]
+    fn eq() {
+      let light: Arc<dyn Hittable> = Arc::new(Point(Point3::default()));
+      let lights = Lights::new(vec![Arc::clone(&light)]);
+      assert_eq!(lights, lights.clone());
+      assert_ne!(lights, Lights::new(vec![Arc::new(Point(Point3::default()))]));
+      assert_ne!(lights, Lights::default());
+    }
+  }
+}
@@ -1,82 +1,125 @@
 
-use rand::Rng;
+use rand::{Rng, RngCore};
 
 use crate::*;
 
 use ray::Ray;
+use point::Point3;
 use colour::Colour;
 use vector::Vector3;
 use hittable::HitRecord;
+use texture::{Texture, SolidColour};
 
-pub trait Material {
-  /// Produces whether the ray scatters
-  fn scatter(
-    &self,
-    in_ray: &Ray, 
-    record: &HitRecord, 
-    attenuation: &mut Colour, 
-    scattered: &mut Ray
-  ) -> bool;
+use std::sync::Arc;
+use std::f64::consts::PI;
+
+/// Outcome of a ray scattering off a material
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ScatterRecord {
+  // eval / pdf for the scattered direction; the fraction of light it carries back
+  pub attenuation: Colour,
+  pub scattered: Ray,
+  // density scattered's direction was picked with; 0 when specular
+  pub pdf: f64,
+  // the direction was picked deterministically, by a mirror or glass, so no
+  // other direction carries light and eval and pdf are 0
+  pub specular: bool,
+}
+
+/// Surface response to light; directions wi (towards the incoming light) and
+/// wo (towards the outgoing light, opposite the ray) are unit vectors
+/// pointing away from the hit
+pub trait Material: Send + Sync {
+  /// Produces the scattered ray, if the ray scatters, drawing any randomness
+  /// from rng so renders repeat exactly for a seed
+  fn scatter(&self, rng: &mut dyn RngCore, ray_in: &Ray, record: &HitRecord) -> Option<ScatterRecord>;
+
+  /// Produces the bsdf times the cosine of wi to the normal; 0 for specular
+  /// materials
+  fn eval(&self, _record: &HitRecord, _wi: Vector3, _wo: Vector3) -> Colour {
+    Colour::new(0.0, 0.0, 0.0)
+  }
+
+  /// Produces the density, per unit solid angle, with which scatter picks wi
+  /// given wo; 0 for specular materials
+  fn pdf(&self, _record: &HitRecord, _wi: Vector3, _wo: Vector3) -> f64 {
+    0.0
+  }
+
+  /// Produces the light given off at surface coordinates (u, v) and point
+  fn emitted(&self, _u: f64, _v: f64, _point: Point3) -> Colour {
+    Colour::new(0.0, 0.0, 0.0)
+  }
 }
 
 pub struct Lambertian {
-  pub albedo: Colour,
+  pub albedo: Arc<dyn Texture>,
 }
 
 impl Lambertian {
   pub fn new(albedo: Colour) -> Self {
+    Self::from_texture(Arc::new(SolidColour::new(albedo)))
+  }
+
+  pub fn from_texture(albedo: Arc<dyn Texture>) -> Self {
     Self { albedo, }
   }
 }
 
 impl Material for Lambertian {
-  fn scatter(
-    &self,
-    _: &Ray, 
-    record: &HitRecord,
-    attenuation: &mut Colour, 
-    scattered: &mut Ray,
-  ) -> bool {
-    let mut rng = rand::thread_rng();
-    let mut direction = record.normal + Vector3::random_unit_vector(&mut rng);
-    if vector::near_zero(direction) {
-      direction = record.normal;
-    }
-    
-    *scattered = Ray::new(record.position, direction);
-    *attenuation = self.albedo;
-    
-    true
+  fn scatter(&self, mut rng: &mut dyn RngCore, ray_in: &Ray, record: &HitRecord) -> Option<ScatterRecord> {
+    let direction = Vector3::random_cosine_direction(&mut rng, record.normal);
+    let scattered = Ray::with_time(record.position, direction, ray_in.time());
+    let wo = -ray_in.direction().to_unit();
+    Some(ScatterRecord {
+      // cosine weighting cancels against the bsdf, leaving the albedo
+      attenuation: self.albedo.value(record.u, record.v, record.position),
+      pdf: self.pdf(record, direction.to_unit(), wo),
+      scattered,
+      specular: false,
+    })
+  }
+
+  fn eval(&self, record: &HitRecord, wi: Vector3, wo: Vector3) -> Colour {
+    self.pdf(record, wi, wo) * self.albedo.value(record.u, record.v, record.position)
+  }
+
+  fn pdf(&self, record: &HitRecord, wi: Vector3, _: Vector3) -> f64 {
+    // cosine weighted about the normal
+    vector::dot(record.normal, wi).max(0.0) / PI
   }
 }
 
 pub struct Metal {
-  pub albedo: Colour,
+  pub albedo: Arc<dyn Texture>,
   pub fuzz_radius: f64,
 }
 
 impl Metal {
   pub fn new(albedo: Colour, fuzz_radius: f64) -> Self {
+    Self::from_texture(Arc::new(SolidColour::new(albedo)), fuzz_radius)
+  }
+
+  pub fn from_texture(albedo: Arc<dyn Texture>, fuzz_radius: f64) -> Self {
     Self { albedo, fuzz_radius, }
   }
 }
 
 impl Material for Metal {
-  fn scatter(
-    &self,
-    ray_in: &Ray, 
-    record: &HitRecord,
-    attenuation: &mut Colour, 
-    scattered: &mut Ray,
-  ) -> bool {
-    let mut rng = rand::thread_rng();
+  fn scatter(&self, mut rng: &mut dyn RngCore, ray_in: &Ray, record: &HitRecord) -> Option<ScatterRecord> {
     let reflected = vector::reflect(ray_in.direction().to_unit(), record.normal);
     let direction = reflected + self.fuzz_radius*Vector3::random_unit_vector(&mut rng);
-    
-    *scattered = Ray::new(record.position, direction);
-    *attenuation = self.albedo;
+    if vector::dot(direction, record.normal) <= 0.0 {
+      return None;
+    }
 
-    vector::dot(scattered.direction(), record.normal) > 0.0
+    // fuzzed reflections have no closed form density, so are treated as specular
+    Some(ScatterRecord {
+      attenuation: self.albedo.value(record.u, record.v, record.position),
+      scattered: Ray::with_time(record.position, direction, ray_in.time()),
+      pdf: 0.0,
+      specular: true,
+    })
   }
 }
 
@@ -91,19 +134,12 @@ impl Dielectric {
 }
 
 impl Material for Dielectric {
-  fn scatter(
-    &self,
-    ray_in: &Ray, 
-    record: &HitRecord,
-    attenuation: &mut Colour, 
-    scattered: &mut Ray,
-  ) -> bool {
+  fn scatter(&self, rng: &mut dyn RngCore, ray_in: &Ray, record: &HitRecord) -> Option<ScatterRecord> {
     let schlick_reflectance = |cos: f64, ref_index: f64| -> f64 {
       let r0 = (1.0-ref_index) / (1.0+ref_index);
       let r0 = r0 * r0;
       r0 + (1.0-r0)*(1.0-cos).powi(5)
     };
-    let mut rng = rand::thread_rng();
     let refraction_ratio = if record.front_face { 
       1.0/self.refraction_index 
     } else { 
@@ -122,11 +158,131 @@ impl Material for Dielectric {
       vector::refract(unit_direction, record.normal, refraction_ratio)
     };
 
+    Some(ScatterRecord {
+      attenuation: Colour::new(1.0, 1.0, 1.0),
+      scattered: Ray::with_time(record.position, direction, ray_in.time()),
+      pdf: 0.0,
+      specular: true,
+    })
+  }
+}
 
-    *attenuation = Colour::new(1.0, 1.0, 1.0);
-    *scattered = Ray::new(record.position, direction);
-    
-    true
+pub struct DiffuseLight {
+  pub emit: Colour,
+}
+
+impl DiffuseLight {
+  pub fn new(emit: Colour) -> Self {
+    Self { emit, }
+  }
+}
+
+impl Material for DiffuseLight {
+  fn scatter(&self, _: &mut dyn RngCore, _: &Ray, _: &HitRecord) -> Option<ScatterRecord> {
+    None
+  }
+
+  fn emitted(&self, _u: f64, _v: f64, _point: Point3) -> Colour {
+    self.emit
+  }
+}
+
+/// Produces the Henyey-Greenstein phase function for light turned through an
+/// angle with cosine cos_theta; g in (-1, 1) is its mean cosine, positive
+/// for forward scattering and zero for isotropic
+pub fn henyey_greenstein(cos_theta: f64, g: f64) -> f64 {
+  let denom = 1.0 + g*g - 2.0*g*cos_theta;
+  (1.0 - g*g) / (4.0 * PI * denom * denom.sqrt())
+}
+
+/// Produces a unit direction scattered from direction with density
+/// henyey_greenstein(g)
+pub fn sample_henyey_greenstein(rng: &mut impl Rng, direction: Vector3, g: f64) -> Vector3 {
+  let (xi1, xi2) = (rng.gen::<f64>(), rng.gen::<f64>());
+  let cos_theta = if g.abs() < 1e-3 {
+    1.0 - 2.0*xi1
+  } else {
+    let s = (1.0 - g*g) / (1.0 - g + 2.0*g*xi1);
+    ((1.0 + g*g - s*s) / (2.0*g)).clamp(-1.0, 1.0)
+  };
+  let sin_theta = (1.0 - cos_theta*cos_theta).max(0.0).sqrt();
+  let phi = 2.0 * PI * xi2;
+
+  let w = direction.to_unit();
+  let (u, v) = vector::tangents(w);
+  sin_theta*phi.cos()*u + sin_theta*phi.sin()*v + cos_theta*w
+}
+
+/// Phase function scattering equally in every direction, for use inside media
+pub struct Isotropic {
+  pub albedo: Arc<dyn Texture>,
+}
+
+impl Isotropic {
+  pub fn new(albedo: Colour) -> Self {
+    Self::from_texture(Arc::new(SolidColour::new(albedo)))
+  }
+
+  pub fn from_texture(albedo: Arc<dyn Texture>) -> Self {
+    Self { albedo, }
+  }
+}
+
+impl Material for Isotropic {
+  fn scatter(&self, mut rng: &mut dyn RngCore, ray_in: &Ray, record: &HitRecord) -> Option<ScatterRecord> {
+    Some(ScatterRecord {
+      attenuation: self.albedo.value(record.u, record.v, record.position),
+      scattered: Ray::with_time(record.position, Vector3::random_unit_vector(&mut rng), ray_in.time()),
+      pdf: 1.0 / (4.0 * PI),
+      specular: false,
+    })
+  }
+
+  fn eval(&self, record: &HitRecord, wi: Vector3, wo: Vector3) -> Colour {
+    self.pdf(record, wi, wo) * self.albedo.value(record.u, record.v, record.position)
+  }
+
+  fn pdf(&self, _: &HitRecord, _: Vector3, _: Vector3) -> f64 {
+    1.0 / (4.0 * PI)
+  }
+}
+
+/// Phase function favouring forward (g > 0) or backward (g < 0) scattering,
+/// for use inside media
+pub struct HenyeyGreenstein {
+  pub albedo: Arc<dyn Texture>,
+  pub g: f64,
+}
+
+impl HenyeyGreenstein {
+  pub fn new(albedo: Colour, g: f64) -> Self {
+    Self::from_texture(Arc::new(SolidColour::new(albedo)), g)
+  }
+
+  pub fn from_texture(albedo: Arc<dyn Texture>, g: f64) -> Self {
+    // g of magnitude 1 collapses to a delta
+    Self { albedo, g: g.clamp(-0.999, 0.999), }
+  }
+}
+
+impl Material for HenyeyGreenstein {
+  fn scatter(&self, mut rng: &mut dyn RngCore, ray_in: &Ray, record: &HitRecord) -> Option<ScatterRecord> {
+    let direction = sample_henyey_greenstein(&mut rng, ray_in.direction(), self.g);
+    Some(ScatterRecord {
+      attenuation: self.albedo.value(record.u, record.v, record.position),
+      pdf: self.pdf(record, direction, -ray_in.direction().to_unit()),
+      scattered: Ray::with_time(record.position, direction, ray_in.time()),
+      specular: false,
+    })
+  }
+
+  fn eval(&self, record: &HitRecord, wi: Vector3, wo: Vector3) -> Colour {
+    self.pdf(record, wi, wo) * self.albedo.value(record.u, record.v, record.position)
+  }
+
+  fn pdf(&self, _: &HitRecord, wi: Vector3, wo: Vector3) -> f64 {
+    // the ray travels along -wo, and is turned to travel along wi
+    henyey_greenstein(vector::dot(-wo, wi), self.g)
   }
 }
 
@@ -141,14 +297,35 @@ mod tests {
     #[allow(unused_imports)]
     use super::*;
 
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
     #[rstest]
     fn new() {
-      todo!()
+      let albedo = Colour::new(0.1, 0.2, 0.3);
+      let material = Lambertian::new(albedo);
+      assert_eq!(material.albedo.value(0.5, 0.5, Point3::new(1.0, 2.0, 3.0)), albedo);
     }
 
     #[rstest]
     fn scatter() {
-      todo!()
+      let albedo = Colour::new(0.1, 0.2, 0.3);
+      let material = Lambertian::new(albedo);
+      let ray = Ray::with_time(Point3::new(1.0, 1.0, 0.0), Vector3::new(-1.0, -1.0, 0.0), 0.5);
+      let record = HitRecord::new(Point3::default(), Vector3::new(0.0, 1.0, 0.0), 1.0);
+      let (mut rng, mut other_rng) = (ChaCha8Rng::seed_from_u64(4), ChaCha8Rng::seed_from_u64(4));
+      for _ in 0..100 {
+        let srec = material.scatter(&mut rng, &ray, &record).unwrap();
+        assert_eq!(Some(srec.clone()), material.scatter(&mut other_rng, &ray, &record));
+        assert_eq!(srec.attenuation, albedo);
+        assert_eq!(srec.scattered.position(), record.position);
+        assert_eq!(srec.scattered.time(), ray.time());
+        assert!(!srec.specular);
+        // scattered about the normal, with the density pdf reports
+        let wi = srec.scattered.direction().to_unit();
+        assert!(vector::dot(wi, record.normal) >= 0.0);
+        assert!((srec.pdf - material.pdf(&record, wi, -ray.direction().to_unit())).abs() < 1e-12);
+      }
     }
   }
 
@@ -156,14 +333,40 @@ mod tests {
     #[allow(unused_imports)]
     use super::*;
 
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
     #[rstest]
     fn new() {
-      todo!()
+      let albedo = Colour::new(0.8, 0.6, 0.2);
+      let material = Metal::new(albedo, 0.3);
+      assert_eq!(material.albedo.value(0.0, 0.0, Point3::default()), albedo);
+      assert_eq!(material.fuzz_radius, 0.3);
     }
 
     #[rstest]
-    fn scatter() {
-      todo!()
+    #[case(0.0)]
+    #[case(0.3)]
+    #[case(1.0)]
+    fn scatter(#[case] fuzz_radius: f64) {
+      let albedo = Colour::new(0.8, 0.6, 0.2);
+      let material = Metal::new(albedo, fuzz_radius);
+      let ray = Ray::new(Point3::new(-1.0, 1.0, 0.0), Vector3::new(1.0, -1.0, 0.0));
+      let record = HitRecord::new(Point3::default(), Vector3::new(0.0, 1.0, 0.0), 1.0);
+      let reflected = Vector3::new(1.0, 1.0, 0.0).to_unit();
+      let (mut rng, mut other_rng) = (ChaCha8Rng::seed_from_u64(4), ChaCha8Rng::seed_from_u64(4));
+      for _ in 0..100 {
+        let srec = material.scatter(&mut rng, &ray, &record);
+        assert_eq!(srec, material.scatter(&mut other_rng, &ray, &record));
+        // fuzzed below the surface, the ray is absorbed
+        let Some(srec) = srec else {
+          continue;
+        };
+        assert_eq!(srec.attenuation, albedo);
+        assert!(srec.specular);
+        assert!(vector::dot(srec.scattered.direction(), record.normal) > 0.0);
+        assert!((srec.scattered.direction() - reflected).length() <= fuzz_radius + 1e-12);
+      }
     }
   }
 
@@ -171,14 +374,206 @@ mod tests {
     #[allow(unused_imports)]
     use super::*;
 
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    #[rstest]
+    fn new() {
+      assert_eq!(Dielectric::new(1.5).refraction_index, 1.5);
+    }
+
+    #[rstest]
+    fn scatter() {
+      let material = Dielectric::new(1.5);
+      let ray = Ray::new(Point3::new(0.0, 1.0, 0.0), Vector3::new(0.0, -1.0, 0.0));
+      let mut record = HitRecord::new(Point3::default(), Vector3::default(), 1.0);
+      record.set_face_normal(&ray, Vector3::new(0.0, 1.0, 0.0));
+      let (mut rng, mut other_rng) = (ChaCha8Rng::seed_from_u64(4), ChaCha8Rng::seed_from_u64(4));
+
+      // head on, 4% reflects straight back and the rest passes straight through
+      let samples = 10_000;
+      let mut reflected = 0;
+      for _ in 0..samples {
+        let srec = material.scatter(&mut rng, &ray, &record).unwrap();
+        assert_eq!(Some(srec.clone()), material.scatter(&mut other_rng, &ray, &record));
+        assert_eq!(srec.attenuation, Colour::new(1.0, 1.0, 1.0));
+        assert!(srec.specular);
+        let direction = srec.scattered.direction();
+        if direction == Vector3::new(0.0, 1.0, 0.0) {
+          reflected += 1;
+        } else {
+          assert!((direction - Vector3::new(0.0, -1.0, 0.0)).length() < 1e-12);
+        }
+      }
+      assert!((reflected as f64 / samples as f64 - 0.04).abs() < 0.01);
+    }
+
+    #[rstest]
+    fn scatter_total_internal_reflection() {
+      let material = Dielectric::new(1.5);
+      // leaving the glass at a grazing angle, no refracted ray exists
+      let ray = Ray::new(Point3::new(-1.0, -0.2, 0.0), Vector3::new(1.0, 0.2, 0.0));
+      let mut record = HitRecord::new(Point3::default(), Vector3::default(), 1.0);
+      record.set_face_normal(&ray, Vector3::new(0.0, 1.0, 0.0));
+      assert!(!record.front_face);
+      let mut rng = ChaCha8Rng::seed_from_u64(4);
+      for _ in 0..100 {
+        let srec = material.scatter(&mut rng, &ray, &record).unwrap();
+        assert!(vector::near_zero(srec.scattered.direction() - Vector3::new(1.0, -0.2, 0.0).to_unit()));
+      }
+    }
+  }
+
+  mod diffuse_light {
+    use super::*;
+
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
     #[rstest]
     fn new() {
-      todo!()
+      let emit = Colour::new(4.0, 4.0, 4.0);
+      let light = DiffuseLight::new(emit);
+      assert_eq!(light.emit, emit);
+    }
+
+    #[rstest]
+    fn scatter() {
+      let light = DiffuseLight::new(Colour::new(4.0, 4.0, 4.0));
+      let ray = Ray::new(Point3::new(0.0, 0.0, 1.0), Vector3::new(0.0, 0.0, -1.0));
+      let record = HitRecord::new(Point3::default(), Vector3::new(0.0, 0.0, 1.0), 1.0);
+      let mut rng = ChaCha8Rng::seed_from_u64(4);
+      assert_eq!(light.scatter(&mut rng, &ray, &record), None);
+    }
+
+    #[rstest]
+    #[case(0.0, 0.0, Point3::new(0.0, 0.0, 0.0))]
+    #[case(0.5, 1.0, Point3::new(1.0, -2.0, 3.0))]
+    fn emitted(#[case] u: f64, #[case] v: f64, #[case] point: Point3) {
+      let emit = Colour::new(15.0, 10.0, 5.0);
+      assert_eq!(DiffuseLight::new(emit).emitted(u, v, point), emit);
+      // non-emissive materials stay dark
+      assert_eq!(Lambertian::new(emit).emitted(u, v, point), Colour::default());
+    }
+  }
+
+  mod phase {
+    use super::*;
+
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    #[rstest]
+    #[case(0.0)]
+    #[case(0.5)]
+    #[case(-0.7)]
+    fn henyey_greenstein_normalised(#[case] g: f64) {
+      // integral over the sphere, which is 2 pi times the integral over cos
+      let steps = 100_000;
+      let integral: f64 = (0..steps)
+        .map(|i| -1.0 + (i as f64 + 0.5) * 2.0 / steps as f64)
+        .map(|cos| super::henyey_greenstein(cos, g) * 2.0 / steps as f64)
+        .sum::<f64>() * 2.0 * PI;
+      assert!((integral - 1.0).abs() < 1e-3);
+    }
+
+    #[rstest]
+    fn henyey_greenstein_isotropic() {
+      assert!((super::henyey_greenstein(0.3, 0.0) - 1.0 / (4.0 * PI)).abs() < 1e-15);
+    }
+
+    #[rstest]
+    #[case(0.0)]
+    #[case(0.3)]
+    #[case(0.8)]
+    #[case(-0.6)]
+    fn sample_henyey_greenstein(#[case] g: f64) {
+      let mut rng = ChaCha8Rng::seed_from_u64(4);
+      let direction = Vector3::new(1.0, 2.0, -0.5);
+      let samples = 20_000;
+      let mut mean_cos = 0.0;
+      for _ in 0..samples {
+        let scattered = super::sample_henyey_greenstein(&mut rng, direction, g);
+        assert!((scattered.length() - 1.0).abs() < 1e-9);
+        mean_cos += vector::dot(scattered, direction.to_unit()) / samples as f64;
+      }
+      // the mean cosine of the phase function is g
+      assert!((mean_cos - g).abs() < 0.02);
     }
 
     #[rstest]
     fn scatter() {
-      todo!()
+      let albedo = Colour::new(0.2, 0.4, 0.6);
+      let ray = Ray::new(Point3::new(0.0, 0.0, 1.0), Vector3::new(0.0, 0.0, -1.0));
+      let record = HitRecord::new(Point3::new(0.0, 0.0, 0.5), Vector3::new(1.0, 0.0, 0.0), 0.5);
+      let materials: [Box<dyn Material>; 2] = [
+        Box::new(Isotropic::new(albedo)),
+        Box::new(HenyeyGreenstein::new(albedo, 0.5)),
+      ];
+      let mut rng = ChaCha8Rng::seed_from_u64(4);
+      for material in materials {
+        let ScatterRecord { attenuation, scattered, pdf, specular } = material.scatter(&mut rng, &ray, &record).unwrap();
+        assert_eq!(attenuation, albedo);
+        assert_eq!(scattered.position(), record.position);
+        assert!((scattered.direction().length() - 1.0).abs() < 1e-9);
+        assert!(!specular);
+        let wo = -ray.direction().to_unit();
+        assert!((pdf - material.pdf(&record, scattered.direction(), wo)).abs() < 1e-12);
+      }
     }
   }
-}
\ No newline at end of file
+
+  mod eval_pdf {
+    use super::*;
+
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    #[rstest]
+    fn lambertian() {
+      let albedo = Colour::new(0.5, 0.25, 0.5);
+      let material = Lambertian::new(albedo);
+      let record = HitRecord::new(Point3::default(), Vector3::new(0.0, 1.0, 0.0), 1.0);
+      let wo = Vector3::new(0.0, 1.0, 0.0);
+      let oblique = Vector3::new(1.0, 1.0, 0.0).to_unit();
+      assert!((material.pdf(&record, wo, wo) - 1.0 / PI).abs() < 1e-15);
+      assert!((material.pdf(&record, oblique, wo) - 0.5_f64.sqrt() / PI).abs() < 1e-15);
+      assert_eq!(material.pdf(&record, -wo, wo), 0.0);
+      assert_eq!(material.eval(&record, oblique, wo), material.pdf(&record, oblique, wo) * albedo);
+      assert_eq!(material.eval(&record, -wo, wo), Colour::default());
+    }
+
+    #[rstest]
+    fn phase() {
+      let record = HitRecord::default();
+      let wo = Vector3::new(0.0, 0.0, 1.0);
+      let wi = Vector3::new(0.0, 1.0, -1.0).to_unit();
+      let isotropic = Isotropic::new(Colour::new(1.0, 1.0, 1.0));
+      let forward = HenyeyGreenstein::new(Colour::new(1.0, 1.0, 1.0), 0.5);
+      assert_eq!(isotropic.pdf(&record, wi, wo), 1.0 / (4.0 * PI));
+      let expected = super::henyey_greenstein(0.5_f64.sqrt(), 0.5);
+      assert!((forward.pdf(&record, wi, wo) - expected).abs() < 1e-12);
+      assert_eq!(forward.eval(&record, wi, wo), forward.pdf(&record, wi, wo) * Colour::new(1.0, 1.0, 1.0));
+    }
+
+    #[rstest]
+    fn specular() {
+      // mirrors and glass only scatter into directions lights cannot sample
+      let ray = Ray::new(Point3::new(0.0, 1.0, 0.0), Vector3::new(0.0, -1.0, 0.0));
+      let record = HitRecord::new(Point3::default(), Vector3::new(0.0, 1.0, 0.0), 1.0);
+      let up = Vector3::new(0.0, 1.0, 0.0);
+      let materials: [Box<dyn Material>; 2] = [
+        Box::new(Metal::new(Colour::new(1.0, 1.0, 1.0), 0.0)),
+        Box::new(Dielectric::new(1.5)),
+      ];
+      let mut rng = ChaCha8Rng::seed_from_u64(4);
+      for material in materials {
+        let srec = material.scatter(&mut rng, &ray, &record).unwrap();
+        assert!(srec.specular);
+        assert_eq!(srec.pdf, 0.0);
+        assert_eq!(material.pdf(&record, up, up), 0.0);
+        assert_eq!(material.eval(&record, up, up), Colour::default());
+      }
+    }
+  }
+}
@@ -0,0 +1,463 @@
+
+use rand::{Error, Rng, RngCore};
+
+use crate::*;
+
+use ray::Ray;
+use aabb::Aabb;
+use point::Point3;
+use colour::Colour;
+use vector::Vector3;
+use interval::Interval;
+use material::{Material, Isotropic};
+use hittable::{Hittable, HitRecord};
+
+use std::sync::Arc;
+
+/// Hash stream keyed by a ray, so a medium's hit stays a pure function of the ray.
+struct RayNoise {
+  key: u64,
+  counter: u64,
+}
+
+impl RayNoise {
+  fn new(ray: &Ray) -> Self {
+    let (p, v) = (ray.position(), ray.direction());
+    let words = [p.x, p.y, p.z, v.x, v.y, v.z, ray.time()].map(f64::to_bits);
+    Self { key: sampler::hash(&words), counter: 0, }
+  }
+}
+
+impl RngCore for RayNoise {
+  fn next_u32(&mut self) -> u32 {
+    (self.next_u64() >> 32) as u32
+  }
+
+  fn next_u64(&mut self) -> u64 {
+    self.counter += 1;
+    sampler::hash(&[self.key, self.counter])
+  }
+
+  fn fill_bytes(&mut self, dest: &mut [u8]) {
+    for chunk in dest.chunks_mut(8) {
+      let bytes = self.next_u64().to_le_bytes();
+      chunk.copy_from_slice(&bytes[..chunk.len()]);
+    }
+  }
+
+  fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+    self.fill_bytes(dest);
+    Ok(())
+  }
+}
+
+/// Volume of uniform density filling a closed boundary; rays travelling
+/// through it scatter at exponentially distributed distances, off the phase
+/// function material
+pub struct ConstantMedium {
+  boundary: Arc<dyn Hittable>,
+  neg_inv_density: f64,
+  phase_function: Arc<dyn Material>,
+}
+
+impl ConstantMedium {
+  pub fn new(boundary: Arc<dyn Hittable>, density: f64, phase_function: Arc<dyn Material>) -> Self {
+    Self { boundary, neg_inv_density: -1.0 / density, phase_function, }
+  }
+
+  /// Produces the medium scattering equally in every direction
+  pub fn isotropic(boundary: Arc<dyn Hittable>, density: f64, albedo: Colour) -> Self {
+    Self::new(boundary, density, Arc::new(Isotropic::new(albedo)))
+  }
+}
+
+impl Hittable for ConstantMedium {
+  fn hit(&self, ray: &Ray, ray_i: Interval, record: &mut HitRecord) -> bool {
+    // find where the whole line enters and leaves the boundary, then clip
+    // that span to ray_i
+    let mut entry = HitRecord::default();
+    let mut exit = HitRecord::default();
+    if !self.boundary.hit(ray, interval::UNIVERSE, &mut entry) {
+      return false;
+    }
+    if !self.boundary.hit(ray, Interval::new(entry.d + 0.0001, f64::INFINITY), &mut exit) {
+      return false;
+    }
+
+    let d_enter = entry.d.max(ray_i.min).max(0.0);
+    let d_exit = exit.d.min(ray_i.max);
+    if d_enter >= d_exit {
+      return false;
+    }
+
+    let ray_length = ray.direction().length();
+    let distance_inside = (d_exit - d_enter) * ray_length;
+    let mut rng = RayNoise::new(ray);
+    let hit_distance = self.neg_inv_density * rng.gen::<f64>().ln();
+    if hit_distance > distance_inside {
+      return false;
+    }
+
+    record.d = d_enter + hit_distance / ray_length;
+    record.position = ray.at(record.d);
+    // scattering inside a volume has no surface, so normal and face are arbitrary
+    record.normal = Vector3::new(1.0, 0.0, 0.0);
+    record.front_face = true;
+    (record.u, record.v) = (0.0, 0.0);
+    record.material = Some(Arc::clone(&self.phase_function));
+
+    true
+  }
+
+  fn bounding_box(&self) -> Aabb {
+    self.boundary.bounding_box()
+  }
+}
+
+/// Voxel grid of densities, x fastest then y then z, sampled with trilinear
+/// interpolation between voxel centers
+#[derive(Debug, Clone, PartialEq)]
+pub struct DensityGrid {
+  resolution: [usize; 3],
+  densities: Vec<f64>,
+  max_density: f64,
+}
+
+impl DensityGrid {
+  pub fn new(resolution: [usize; 3], densities: Vec<f64>) -> Result<Self, RaytracerError> {
+    let [nx, ny, nz] = resolution;
+    let bad_values = densities.iter().any(|density| !density.is_finite() || *density < 0.0);
+    if nx == 0 || ny == 0 || nz == 0 || densities.len() != nx * ny * nz || bad_values {
+      return Err(RaytracerError::DensityGridError);
+    }
+    let max_density = densities.iter().copied().fold(0.0, f64::max);
+    Ok(Self { resolution, densities, max_density, })
+  }
+
+  pub fn resolution(&self) -> [usize; 3] {
+    self.resolution
+  }
+
+  pub fn max_density(&self) -> f64 {
+    self.max_density
+  }
+
+  fn voxel(&self, i: i64, j: i64, k: i64) -> f64 {
+    // clamp to the edge voxels
+    let [nx, ny, nz] = self.resolution;
+    let i = i.clamp(0, nx as i64 - 1) as usize;
+    let j = j.clamp(0, ny as i64 - 1) as usize;
+    let k = k.clamp(0, nz as i64 - 1) as usize;
+    self.densities[(k * ny + j) * nx + i]
+  }
+
+  /// Produces the density at p, with the grid spanning [0, 1] on every axis
+  pub fn sample(&self, p: Point3) -> f64 {
+    let [nx, ny, nz] = self.resolution;
+    let x = p.x * nx as f64 - 0.5;
+    let y = p.y * ny as f64 - 0.5;
+    let z = p.z * nz as f64 - 0.5;
+    let (x0, y0, z0) = (x.floor(), y.floor(), z.floor());
+    let (tx, ty, tz) = (x - x0, y - y0, z - z0);
+    let (i, j, k) = (x0 as i64, y0 as i64, z0 as i64);
+
+    let lerp = |a: f64, b: f64, t: f64| (1.0-t)*a + t*b;
+    let plane = |k: i64| lerp(
+      lerp(self.voxel(i, j, k), self.voxel(i + 1, j, k), tx),
+      lerp(self.voxel(i, j + 1, k), self.voxel(i + 1, j + 1, k), tx),
+      ty,
+    );
+    lerp(plane(k), plane(k + 1), tz)
+  }
+}
+
+/// Volume whose density varies through a grid stretched over bbox; free flight
+/// is sampled by delta tracking against the grid's largest density
+pub struct GridMedium {
+  bbox: Aabb,
+  grid: Arc<DensityGrid>,
+  density_scale: f64,
+  phase_function: Arc<dyn Material>,
+}
+
+impl GridMedium {
+  pub fn new(bbox: Aabb, grid: Arc<DensityGrid>, density_scale: f64, phase_function: Arc<dyn Material>) -> Self {
+    Self { bbox, grid, density_scale, phase_function, }
+  }
+
+  /// Produces the medium scattering equally in every direction
+  pub fn isotropic(bbox: Aabb, grid: Arc<DensityGrid>, density_scale: f64, albedo: Colour) -> Self {
+    Self::new(bbox, grid, density_scale, Arc::new(Isotropic::new(albedo)))
+  }
+
+  /// Produces the scaled density at world point p
+  pub fn density(&self, p: Point3) -> f64 {
+    let local = Point3::new(
+      (p.x - self.bbox.x.min) / self.bbox.x.size(),
+      (p.y - self.bbox.y.min) / self.bbox.y.size(),
+      (p.z - self.bbox.z.min) / self.bbox.z.size(),
+    );
+    self.density_scale * self.grid.sample(local)
+  }
+
+  fn majorant(&self) -> f64 {
+    self.density_scale * self.grid.max_density()
+  }
+}
+
+impl Hittable for GridMedium {
+  fn hit(&self, ray: &Ray, ray_i: Interval, record: &mut HitRecord) -> bool {
+    let majorant = self.majorant();
+    let Some(span) = self.bbox.clip(ray, ray_i) else {
+      return false;
+    };
+    if majorant <= 0.0 {
+      return false;
+    }
+
+    // take exponential steps as if the whole grid were at the majorant, and
+    // accept each as a real collision with probability density / majorant
+    let mut rng = RayNoise::new(ray);
+    let step = 1.0 / (majorant * ray.direction().length());
+    let mut d = span.min;
+    loop {
+      d -= (1.0 - rng.gen::<f64>()).ln() * step;
+      if d >= span.max {
+        return false;
+      }
+      if rng.gen::<f64>() * majorant < self.density(ray.at(d)) {
+        break;
+      }
+    }
+
+    record.d = d;
+    record.position = ray.at(d);
+    // scattering inside a volume has no surface, so normal and face are arbitrary
+    record.normal = Vector3::new(1.0, 0.0, 0.0);
+    record.front_face = true;
+    (record.u, record.v) = (0.0, 0.0);
+    record.material = Some(Arc::clone(&self.phase_function));
+
+    true
+  }
+
+  fn bounding_box(&self) -> Aabb {
+    self.bbox
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use rstest::*;
+
+  use testing::UnitSphere;
+
+  mod ray_noise {
+    use super::*;
+
+    #[rstest]
+    fn keyed_by_ray() {
+      let ray = Ray::new(Point3::new(0.0, 0.0, 5.0), Vector3::new(0.0, 0.0, -1.0));
+      let values: Vec<f64> = (0..4).scan(RayNoise::new(&ray), |rng, _| Some(rng.gen())).collect();
+      let again: Vec<f64> = (0..4).scan(RayNoise::new(&ray), |rng, _| Some(rng.gen())).collect();
+      assert_eq!(values, again);
+      let later = Ray::with_time(ray.position(), ray.direction(), 1.0);
+      assert_ne!(RayNoise::new(&later).gen::<f64>(), values[0]);
+    }
+
+    #[rstest]
+    fn uniform() {
+      let ray = Ray::new(Point3::new(1.0, 2.0, 3.0), Vector3::new(0.0, 1.0, 0.0));
+      let mut rng = RayNoise::new(&ray);
+      let n = 10_000;
+      let mut buckets = [0; 10];
+      for _ in 0..n {
+        buckets[(rng.gen::<f64>() * 10.0) as usize] += 1;
+      }
+      assert!(buckets.iter().all(|&count| (count as f64 / n as f64 - 0.1).abs() < 0.015), "{buckets:?}");
+    }
+  }
+
+  mod constant_medium {
+    use super::*;
+
+    fn medium(density: f64) -> ConstantMedium {
+      ConstantMedium::isotropic(Arc::new(UnitSphere), density, Colour::new(0.5, 0.5, 0.5))
+    }
+
+    #[rstest]
+    #[case(Point3::new(0.0, 0.0, 5.0), Vector3::new(0.0, 0.0, -1.0))]
+    #[case(Point3::new(0.0, 0.0, 0.0), Vector3::new(0.0, 0.0, -1.0))]
+    #[case(Point3::new(0.5, 0.5, 5.0), Vector3::new(0.0, 0.0, -2.0))]
+    fn hit_dense(#[case] position: Point3, #[case] direction: Vector3) {
+      let ray = Ray::new(position, direction);
+      let mut record = HitRecord::default();
+      for _ in 0..100 {
+        assert!(medium(1e9).hit(&ray, Interval::new(0.001, f64::INFINITY), &mut record));
+        assert!((record.position - ray.at(record.d)).length() < 1e-12);
+        // scatters as soon as it is inside
+        let radius = record.position.length();
+        assert!(radius <= 1.0 + 1e-6);
+        assert!(radius >= 1.0 - 1e-6 || position.length() < 1.0);
+        assert!(record.material.is_some());
+      }
+    }
+
+    #[rstest]
+    fn hit_sparse() {
+      let ray = Ray::new(Point3::new(0.0, 0.0, 5.0), Vector3::new(0.0, 0.0, -1.0));
+      let mut record = HitRecord::default();
+      for _ in 0..100 {
+        assert!(!medium(1e-12).hit(&ray, Interval::new(0.001, f64::INFINITY), &mut record));
+      }
+    }
+
+    #[rstest]
+    #[case(Ray::new(Point3::new(0.0, 2.0, 5.0), Vector3::new(0.0, 0.0, -1.0)))]
+    #[case(Ray::new(Point3::new(0.0, 0.0, 5.0), Vector3::new(0.0, 0.0, 1.0)))]
+    fn miss(#[case] ray: Ray) {
+      let mut record = HitRecord::default();
+      assert!(!medium(1e9).hit(&ray, Interval::new(0.001, f64::INFINITY), &mut record));
+    }
+
+    #[rstest]
+    fn hit_clipped() {
+      // the span inside the sphere starts beyond ray_i
+      let ray = Ray::new(Point3::new(0.0, 0.0, 5.0), Vector3::new(0.0, 0.0, -1.0));
+      let mut record = HitRecord::default();
+      assert!(!medium(1e9).hit(&ray, Interval::new(0.001, 3.0), &mut record));
+    }
+
+    #[rstest]
+    fn transmittance() {
+      // the fraction passing straight through a chord of length 2 is exp(-2 density)
+      let density = 0.5;
+      let mut record = HitRecord::default();
+      let trials = 20_000;
+      // collisions are seeded by the ray, so each trial casts at its own time
+      let passed = (0..trials)
+        .map(|n| Ray::with_time(Point3::new(0.0, 0.0, 5.0), Vector3::new(0.0, 0.0, -1.0), n as f64))
+        .filter(|ray| !medium(density).hit(ray, Interval::new(0.001, f64::INFINITY), &mut record))
+        .count();
+      let expected = (-2.0 * density).exp();
+      assert!((passed as f64 / trials as f64 - expected).abs() < 0.02);
+    }
+
+    #[rstest]
+    fn hit_reproducible() {
+      let ray = Ray::new(Point3::new(0.0, 0.0, 5.0), Vector3::new(0.0, 0.0, -1.0));
+      let (mut record, mut other_record) = (HitRecord::default(), HitRecord::default());
+      let hit = medium(1.0).hit(&ray, Interval::new(0.001, f64::INFINITY), &mut record);
+      for _ in 0..10 {
+        assert_eq!(medium(1.0).hit(&ray, Interval::new(0.001, f64::INFINITY), &mut other_record), hit);
+        assert_eq!(other_record.d, record.d);
+      }
+    }
+
+    #[rstest]
+    fn bounding_box() {
+      assert_eq!(medium(1.0).bounding_box(), UnitSphere.bounding_box());
+    }
+  }
+
+  mod density_grid {
+    use super::*;
+
+    #[rstest]
+    #[case([2, 2, 2], 8, true)]
+    #[case([2, 2, 2], 7, false)]
+    #[case([0, 1, 1], 0, false)]
+    fn new(#[case] resolution: [usize; 3], #[case] count: usize, #[case] expected: bool) {
+      assert_eq!(DensityGrid::new(resolution, vec![0.5; count]).is_ok(), expected);
+    }
+
+    #[rstest]
+    #[case(-1.0)]
+    #[case(f64::NAN)]
+    fn new_invalid(#[case] density: f64) {
+      assert!(DensityGrid::new([1, 1, 2], vec![0.5, density]).is_err());
+    }
+
+    #[rstest]
+    #[case(Point3::new(0.25, 0.5, 0.5), 0.0)]
+    #[case(Point3::new(0.75, 0.5, 0.5), 1.0)]
+    #[case(Point3::new(0.5, 0.5, 0.5), 0.5)]
+    #[case(Point3::new(0.375, 0.1, 0.9), 0.25)]
+    #[case(Point3::new(0.0, 0.0, 0.0), 0.0)]
+    #[case(Point3::new(1.0, 1.0, 1.0), 1.0)]
+    fn sample(#[case] p: Point3, #[case] expected: f64) {
+      // density ramps from 0 to 1 along x, constant in y and z
+      let grid = DensityGrid::new([2, 2, 2], vec![0.0, 1.0, 0.0, 1.0, 0.0, 1.0, 0.0, 1.0]).unwrap();
+      assert!((grid.sample(p) - expected).abs() < 1e-12);
+      assert_eq!(grid.max_density(), 1.0);
+    }
+  }
+
+  mod grid_medium {
+    use super::*;
+
+    fn cube() -> Aabb {
+      Aabb::from_points(Point3::new(-1.0, -1.0, -1.0), Point3::new(1.0, 1.0, 1.0))
+    }
+
+    fn medium(densities: Vec<f64>, resolution: [usize; 3], scale: f64) -> GridMedium {
+      let grid = Arc::new(DensityGrid::new(resolution, densities).unwrap());
+      GridMedium::isotropic(cube(), grid, scale, Colour::new(0.8, 0.8, 0.8))
+    }
+
+    #[rstest]
+    fn density() {
+      let medium = medium(vec![0.0, 1.0], [2, 1, 1], 3.0);
+      assert!((medium.density(Point3::new(-0.5, 0.0, 0.0))).abs() < 1e-12);
+      assert!((medium.density(Point3::new(0.5, 0.0, 0.0)) - 3.0).abs() < 1e-12);
+    }
+
+    #[rstest]
+    fn hit_empty() {
+      let medium = medium(vec![0.0; 8], [2, 2, 2], 1.0);
+      let ray = Ray::new(Point3::new(0.0, 0.0, 5.0), Vector3::new(0.0, 0.0, -1.0));
+      let mut record = HitRecord::default();
+      assert!(!medium.hit(&ray, Interval::new(0.001, f64::INFINITY), &mut record));
+    }
+
+    #[rstest]
+    fn hit_only_where_dense() {
+      // only the half with x > 0 holds any density
+      let medium = medium(vec![0.0, 1.0, 0.0, 1.0, 0.0, 1.0, 0.0, 1.0], [2, 2, 2], 1e3);
+      let mut record = HitRecord::default();
+      let empty = Ray::new(Point3::new(-0.9, 0.0, 5.0), Vector3::new(0.0, 0.0, -1.0));
+      let dense = Ray::new(Point3::new(0.9, 0.0, 5.0), Vector3::new(0.0, 0.0, -1.0));
+      for _ in 0..100 {
+        assert!(!medium.hit(&empty, Interval::new(0.001, f64::INFINITY), &mut record));
+        assert!(medium.hit(&dense, Interval::new(0.001, f64::INFINITY), &mut record));
+        assert!(cube().hit(&Ray::new(record.position, Vector3::new(0.0, 0.0, 1.0)), Interval::new(-1e-6, 1e-6)));
+      }
+    }
+
+    #[rstest]
+    #[case(vec![0.5; 8], [2, 2, 2])]
+    #[case(vec![0.2, 0.8, 0.5, 0.5, 0.9, 0.1, 0.3, 0.7], [2, 2, 2])]
+    fn transmittance(#[case] densities: Vec<f64>, #[case] resolution: [usize; 3]) {
+      let medium = medium(densities, resolution, 1.0);
+      let ray = Ray::new(Point3::new(0.1, -0.2, 5.0), Vector3::new(0.0, 0.0, -1.0));
+      let ray_i = Interval::new(0.001, f64::INFINITY);
+
+      // optical depth along the chord, by midpoint quadrature
+      let steps = 10_000;
+      let optical_depth: f64 = (0..steps)
+        .map(|i| medium.density(ray.at(4.0 + 2.0 * (i as f64 + 0.5) / steps as f64)) * 2.0 / steps as f64)
+        .sum();
+      let expected = (-optical_depth).exp();
+
+      // shadow rays see the medium through hit, so the fraction passing it is its transmittance
+      let trials = 20_000;
+      let mut record = HitRecord::default();
+      let passed = (0..trials)
+        .map(|n| Ray::with_time(ray.position(), ray.direction(), n as f64))
+        .filter(|ray| !medium.hit(ray, ray_i, &mut record))
+        .count();
+      assert!((passed as f64 / trials as f64 - expected).abs() < 0.02);
+    }
+  }
+}
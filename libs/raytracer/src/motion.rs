@@ -0,0 +1,266 @@
+
+use crate::*;
+
+use vector::Vector3;
+use transform::Transform;
+
+/// Values that can be blended linearly between two keyframes
+pub trait Lerp {
+  /// Produces a at t = 0 through to b at t = 1
+  fn lerp(a: &Self, b: &Self, t: f64) -> Self;
+}
+
+impl Lerp for Vector3 {
+  fn lerp(a: &Self, b: &Self, t: f64) -> Self {
+    (1.0-t)*(*a) + t*(*b)
+  }
+}
+
+/// Values at increasing times, interpolated in between and held constant
+/// before the first and after the last
+#[derive(Debug, Clone, PartialEq)]
+pub struct Keyframes<T> {
+  keys: Vec<(f64, T)>,
+}
+
+impl<T: Lerp + Clone> Keyframes<T> {
+  pub fn new(keys: Vec<(f64, T)>) -> Result<Self, RaytracerError> {
+    let increasing = keys.windows(2).all(|pair| pair[0].0 < pair[1].0);
+    if keys.is_empty() || !increasing || keys.iter().any(|(time, _)| !time.is_finite()) {
+      return Err(RaytracerError::KeyframeError);
+    }
+    Ok(Self { keys, })
+  }
+
+  /// Produces the value that holds at every time
+  pub fn constant(value: T) -> Self {
+    Self { keys: vec![(0.0, value)], }
+  }
+
+  /// Produces value a at time 0 moving to b at time 1
+  pub fn linear(a: T, b: T) -> Self {
+    Self { keys: vec![(0.0, a), (1.0, b)], }
+  }
+
+  pub fn keys(&self) -> &[(f64, T)] {
+    &self.keys
+  }
+
+  pub fn is_constant(&self) -> bool {
+    self.keys.len() == 1
+  }
+
+  pub fn at(&self, time: f64) -> T {
+    let next = self.keys.partition_point(|(key_time, _)| *key_time <= time);
+    if next == 0 {
+      return self.keys[0].1.clone();
+    }
+    if next == self.keys.len() {
+      return self.keys[next - 1].1.clone();
+    }
+
+    let (t0, ref a) = self.keys[next - 1];
+    let (t1, ref b) = self.keys[next];
+    T::lerp(a, b, (time - t0) / (t1 - t0))
+  }
+}
+
+/// Placement of an object by scale, then rotation about an axis through the
+/// origin, then translation; poses blend component-wise, with rotations
+/// taking the shortest path between them
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Pose {
+  pub scale: Vector3,
+  // unit quaternion (w, x, y, z)
+  rotation: [f64; 4],
+  pub translation: Vector3,
+}
+
+impl Pose {
+  pub fn with_scale(mut self, scale: Vector3) -> Self {
+    self.scale = scale;
+    self
+  }
+
+  /// Rotation by degrees about axis, replacing any earlier rotation
+  pub fn with_rotation(mut self, axis: Vector3, degrees: f64) -> Self {
+    let axis = axis.to_unit();
+    let (sin, cos) = (degrees.to_radians() / 2.0).sin_cos();
+    self.rotation = [cos, sin * axis.x, sin * axis.y, sin * axis.z];
+    self
+  }
+
+  pub fn with_translation(mut self, translation: Vector3) -> Self {
+    self.translation = translation;
+    self
+  }
+
+  /// Produces the (axis, degrees) of the rotation
+  pub fn rotation(&self) -> (Vector3, f64) {
+    let [w, x, y, z] = self.rotation;
+    let axis = Vector3::new(x, y, z);
+    let sin = axis.length();
+    if sin < 1e-12 {
+      (Vector3::new(0.0, 1.0, 0.0), 0.0)
+    } else {
+      (axis / sin, 2.0 * sin.atan2(w).to_degrees())
+    }
+  }
+
+  pub fn transform(&self) -> Transform {
+    let (axis, degrees) = self.rotation();
+    Transform::identity()
+      .scale(self.scale)
+      .rotate(axis, degrees)
+      .translate(self.translation)
+  }
+}
+
+impl Default for Pose {
+  fn default() -> Self {
+    Self {
+      scale: Vector3::new(1.0, 1.0, 1.0),
+      rotation: [1.0, 0.0, 0.0, 0.0],
+      translation: Vector3::default(),
+    }
+  }
+}
+
+impl Lerp for Pose {
+  fn lerp(a: &Self, b: &Self, t: f64) -> Self {
+    // slerp, through the nearer of b's two equivalent quaternions
+    let (qa, mut qb) = (a.rotation, b.rotation);
+    let mut cos: f64 = qa.iter().zip(qb.iter()).map(|(x, y)| x * y).sum();
+    if cos < 0.0 {
+      qb = qb.map(|x| -x);
+      cos = -cos;
+    }
+    let (wa, wb) = if cos > 0.9995 {
+      // nearly parallel, where slerp loses precision but lerp is as good
+      (1.0 - t, t)
+    } else {
+      let theta = cos.acos();
+      let sin = theta.sin();
+      (((1.0 - t) * theta).sin() / sin, (t * theta).sin() / sin)
+    };
+    let mut rotation = [0.0; 4];
+    for (n, value) in rotation.iter_mut().enumerate() {
+      *value = wa * qa[n] + wb * qb[n];
+    }
+    let norm = rotation.iter().map(|x| x * x).sum::<f64>().sqrt();
+
+    Self {
+      scale: Vector3::lerp(&a.scale, &b.scale, t),
+      rotation: rotation.map(|x| x / norm),
+      translation: Vector3::lerp(&a.translation, &b.translation, t),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use rstest::*;
+
+  use point::Point3;
+  use testing::assert_near;
+
+  mod keyframes {
+    use super::*;
+
+    #[rstest]
+    #[case(vec![(0.0, 1.0)], true)]
+    #[case(vec![(0.0, 1.0), (1.0, 2.0)], true)]
+    #[case(vec![], false)]
+    #[case(vec![(1.0, 1.0), (0.0, 2.0)], false)]
+    #[case(vec![(0.0, 1.0), (0.0, 2.0)], false)]
+    #[case(vec![(f64::NAN, 1.0)], false)]
+    fn new(#[case] keys: Vec<(f64, f64)>, #[case] expected: bool) {
+      let keys = keys.into_iter().map(|(time, x)| (time, Vector3::new(x, 0.0, 0.0))).collect();
+      assert_eq!(Keyframes::new(keys).is_ok(), expected);
+    }
+
+    #[rstest]
+    #[case(-1.0, 0.0)]
+    #[case(0.0, 0.0)]
+    #[case(0.5, 1.0)]
+    #[case(1.0, 2.0)]
+    #[case(2.0, 0.0)]
+    #[case(2.5, 0.0)]
+    fn at(#[case] time: f64, #[case] expected: f64) {
+      let keys = Keyframes::new(vec![
+        (0.0, Vector3::new(0.0, 0.0, 0.0)),
+        (1.0, Vector3::new(2.0, 0.0, 0.0)),
+        (2.0, Vector3::new(0.0, 0.0, 0.0)),
+      ]).unwrap();
+      assert_eq!(keys.at(time), Vector3::new(expected, 0.0, 0.0));
+    }
+
+    #[rstest]
+    fn constant_linear() {
+      let (a, b) = (Vector3::new(1.0, 2.0, 3.0), Vector3::new(3.0, 2.0, 1.0));
+      let constant = Keyframes::constant(a);
+      assert!(constant.is_constant());
+      assert_eq!(constant.at(-4.0), a);
+      assert_eq!(constant.at(4.0), a);
+      assert_eq!(Keyframes::linear(a, b).at(0.5), Vector3::new(2.0, 2.0, 2.0));
+    }
+  }
+
+  mod pose {
+    use super::*;
+
+    #[rstest]
+    fn transform() {
+      let pose = Pose::default()
+        .with_scale(Vector3::new(2.0, 2.0, 2.0))
+        .with_rotation(Vector3::new(0.0, 0.0, 1.0), 90.0)
+        .with_translation(Vector3::new(0.0, 0.0, -5.0));
+      assert_near(pose.transform().point(Point3::new(1.0, 0.0, 0.0)), Point3::new(0.0, 2.0, -5.0));
+      assert_eq!(Pose::default().transform(), Transform::identity());
+    }
+
+    #[rstest]
+    #[case(Vector3::new(0.0, 1.0, 0.0), 30.0)]
+    #[case(Vector3::new(1.0, 1.0, 0.0).to_unit(), 135.0)]
+    fn rotation(#[case] axis: Vector3, #[case] degrees: f64) {
+      let (result_axis, result_degrees) = Pose::default().with_rotation(axis, degrees).rotation();
+      assert_near(result_axis, axis);
+      assert!((result_degrees - degrees).abs() < 1e-9);
+    }
+
+    #[rstest]
+    #[case(0.0, 0.0)]
+    #[case(0.25, 22.5)]
+    #[case(0.5, 45.0)]
+    #[case(1.0, 90.0)]
+    fn lerp_rotation(#[case] t: f64, #[case] expected: f64) {
+      let axis = Vector3::new(0.0, 1.0, 0.0);
+      let (a, b) = (Pose::default(), Pose::default().with_rotation(axis, 90.0));
+      let expected = Pose::default().with_rotation(axis, expected).transform();
+      let p = Point3::new(1.0, 0.0, 0.0);
+      assert_near(Pose::lerp(&a, &b, t).transform().point(p), expected.point(p));
+    }
+
+    #[rstest]
+    fn lerp_shortest_path() {
+      // 350 degrees is 10 degrees the other way round
+      let axis = Vector3::new(0.0, 0.0, 1.0);
+      let (a, b) = (Pose::default(), Pose::default().with_rotation(axis, 350.0));
+      let halfway = Pose::lerp(&a, &b, 0.5).transform().point(Point3::new(1.0, 0.0, 0.0));
+      let expected = Pose::default().with_rotation(axis, -5.0).transform().point(Point3::new(1.0, 0.0, 0.0));
+      assert_near(halfway, expected);
+    }
+
+    #[rstest]
+    fn lerp_scale_translation() {
+      let a = Pose::default().with_translation(Vector3::new(0.0, 0.0, 0.0));
+      let b = Pose::default()
+        .with_scale(Vector3::new(3.0, 1.0, 1.0))
+        .with_translation(Vector3::new(4.0, 0.0, 0.0));
+      let halfway = Pose::lerp(&a, &b, 0.5);
+      assert_eq!(halfway.scale, Vector3::new(2.0, 1.0, 1.0));
+      assert_eq!(halfway.translation, Vector3::new(2.0, 0.0, 0.0));
+    }
+  }
+}
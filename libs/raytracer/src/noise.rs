@@ -0,0 +1,346 @@
+
+use rand::{Rng, SeedableRng};
+use rand::seq::SliceRandom;
+use rand_chacha::ChaCha8Rng;
+
+use crate::*;
+
+use point::Point3;
+use colour::Colour;
+use vector::Vector3;
+use texture::Texture;
+
+const POINT_COUNT: usize = 256;
+
+/// Perlin gradient noise; identical seeds produce identical noise
+#[derive(Debug, Clone, PartialEq)]
+pub struct Perlin {
+  gradients: Vec<Vector3>,
+  perm_x: Vec<usize>,
+  perm_y: Vec<usize>,
+  perm_z: Vec<usize>,
+}
+
+impl Perlin {
+  pub fn new(seed: u64) -> Self {
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+    let gradients = (0..POINT_COUNT)
+      .map(|_| Vector3::random_unit_vector(&mut rng))
+      .collect();
+    let perm_x = Self::permutation(&mut rng);
+    let perm_y = Self::permutation(&mut rng);
+    let perm_z = Self::permutation(&mut rng);
+
+    Self { gradients, perm_x, perm_y, perm_z, }
+  }
+
+  fn permutation(rng: &mut impl Rng) -> Vec<usize> {
+    let mut perm: Vec<usize> = (0..POINT_COUNT).collect();
+    perm.shuffle(rng);
+    perm
+  }
+
+  /// Produces smoothly varying noise in [-1, 1], zero at every lattice point
+  pub fn noise(&self, p: Point3) -> f64 {
+    let (fx, fy, fz) = (p.x.floor(), p.y.floor(), p.z.floor());
+    let (u, v, w) = (p.x - fx, p.y - fy, p.z - fz);
+    let (i, j, k) = (fx as i64, fy as i64, fz as i64);
+
+    let mut corners = [[[Vector3::default(); 2]; 2]; 2];
+    for (di, plane) in corners.iter_mut().enumerate() {
+      for (dj, row) in plane.iter_mut().enumerate() {
+        for (dk, corner) in row.iter_mut().enumerate() {
+          let index = self.perm_x[Self::wrap(i + di as i64)]
+            ^ self.perm_y[Self::wrap(j + dj as i64)]
+            ^ self.perm_z[Self::wrap(k + dk as i64)];
+          *corner = self.gradients[index];
+        }
+      }
+    }
+
+    Self::interpolate(&corners, u, v, w)
+  }
+
+  fn wrap(n: i64) -> usize {
+    n.rem_euclid(POINT_COUNT as i64) as usize
+  }
+
+  fn interpolate(corners: &[[[Vector3; 2]; 2]; 2], u: f64, v: f64, w: f64) -> f64 {
+    // hermite smoothing hides the lattice
+    let uu = u*u*(3.0 - 2.0*u);
+    let vv = v*v*(3.0 - 2.0*v);
+    let ww = w*w*(3.0 - 2.0*w);
+
+    let mut accum = 0.0;
+    for (i, plane) in corners.iter().enumerate() {
+      for (j, row) in plane.iter().enumerate() {
+        for (k, gradient) in row.iter().enumerate() {
+          let (fi, fj, fk) = (i as f64, j as f64, k as f64);
+          let weight = Vector3::new(u - fi, v - fj, w - fk);
+          accum += (fi*uu + (1.0-fi)*(1.0-uu))
+            * (fj*vv + (1.0-fj)*(1.0-vv))
+            * (fk*ww + (1.0-fk)*(1.0-ww))
+            * vector::dot(*gradient, weight);
+        }
+      }
+    }
+
+    accum
+  }
+
+  /// Produces the sum of |noise| over octaves, each at double the frequency
+  /// and half the weight of the last
+  pub fn turbulence(&self, p: Point3, octaves: usize) -> f64 {
+    let (mut accum, mut point, mut weight) = (0.0, p, 1.0);
+    for _ in 0..octaves {
+      accum += weight * self.noise(point).abs();
+      weight *= 0.5;
+      point *= 2.0;
+    }
+    accum
+  }
+
+  /// Produces fractal brownian motion; octaves of signed noise, each scaled in
+  /// frequency by lacunarity and in amplitude by gain
+  pub fn fbm(&self, p: Point3, octaves: usize, lacunarity: f64, gain: f64) -> f64 {
+    let (mut accum, mut point, mut weight) = (0.0, p, 1.0);
+    for _ in 0..octaves {
+      accum += weight * self.noise(point);
+      weight *= gain;
+      point *= lacunarity;
+    }
+    accum
+  }
+}
+
+fn lerp(a: Colour, b: Colour, t: f64) -> Colour {
+  (1.0-t)*a + t*b
+}
+
+/// Greyscale noise, remapped to [0, 1]
+pub struct NoiseTexture {
+  pub noise: Perlin,
+  pub scale: f64,
+}
+
+impl NoiseTexture {
+  pub fn new(seed: u64, scale: f64) -> Self {
+    Self { noise: Perlin::new(seed), scale, }
+  }
+}
+
+impl Texture for NoiseTexture {
+  fn value(&self, _u: f64, _v: f64, point: Point3) -> Colour {
+    let t = 0.5 * (1.0 + self.noise.noise(self.scale * point));
+    Colour::new(t, t, t)
+  }
+}
+
+/// Veins running along z, distorted by turbulence
+pub struct Marble {
+  pub noise: Perlin,
+  pub scale: f64,
+  pub vein: Colour,
+  pub base: Colour,
+}
+
+impl Marble {
+  pub fn new(seed: u64, scale: f64, vein: Colour, base: Colour) -> Self {
+    Self { noise: Perlin::new(seed), scale, vein, base, }
+  }
+}
+
+impl Texture for Marble {
+  fn value(&self, _u: f64, _v: f64, point: Point3) -> Colour {
+    let p = self.scale * point;
+    let t = 0.5 * (1.0 + (p.z + 10.0 * self.noise.turbulence(p, 7)).sin());
+    lerp(self.vein, self.base, t)
+  }
+}
+
+/// Concentric growth rings around the y axis, wobbled by noise
+pub struct Wood {
+  pub noise: Perlin,
+  pub scale: f64,
+  pub early: Colour,
+  pub late: Colour,
+}
+
+impl Wood {
+  pub fn new(seed: u64, scale: f64, early: Colour, late: Colour) -> Self {
+    Self { noise: Perlin::new(seed), scale, early, late, }
+  }
+}
+
+impl Texture for Wood {
+  fn value(&self, _u: f64, _v: f64, point: Point3) -> Colour {
+    let p = self.scale * point;
+    let radius = (p.x*p.x + p.z*p.z).sqrt() + 0.5 * self.noise.fbm(p, 4, 2.0, 0.5);
+    let ring = radius.rem_euclid(1.0);
+    // sharp transition into the dark latewood at the end of each ring
+    let t = (ring * 4.0).min(1.0).powi(2);
+    lerp(self.late, self.early, t)
+  }
+}
+
+/// Soft-edged clouds over a sky colour
+pub struct Clouds {
+  pub noise: Perlin,
+  pub scale: f64,
+  pub sky: Colour,
+  pub cloud: Colour,
+  // fraction of the surface that is cloud free, in [0, 1]
+  pub coverage: f64,
+}
+
+impl Clouds {
+  pub fn new(seed: u64, scale: f64, sky: Colour, cloud: Colour, coverage: f64) -> Self {
+    Self { noise: Perlin::new(seed), scale, sky, cloud, coverage, }
+  }
+}
+
+impl Texture for Clouds {
+  fn value(&self, _u: f64, _v: f64, point: Point3) -> Colour {
+    let density = 0.5 * (1.0 + self.noise.fbm(self.scale * point, 6, 2.0, 0.5));
+    let edge = self.coverage.clamp(0.0, 1.0);
+    let t = ((density - edge) / (1.0 - edge).max(1e-8)).clamp(0.0, 1.0);
+    // smoothstep
+    let t = t*t*(3.0 - 2.0*t);
+    lerp(self.sky, self.cloud, t)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use rstest::*;
+
+  fn points(count: usize) -> Vec<Point3> {
+    let mut rng = ChaCha8Rng::seed_from_u64(4);
+    (0..count).map(|_| Point3::random_range(&mut rng, -20.0, 20.0)).collect()
+  }
+
+  fn within(colour: Colour, a: Colour, b: Colour) -> bool {
+    (0..3).all(|n| {
+      let (lo, hi) = (a[n].min(b[n]), a[n].max(b[n]));
+      lo - 1e-12 <= colour[n] && colour[n] <= hi + 1e-12
+    })
+  }
+
+  mod perlin {
+    use super::*;
+
+    #[rstest]
+    fn deterministic() {
+      let (a, b, c) = (Perlin::new(7), Perlin::new(7), Perlin::new(8));
+      assert_eq!(a, b);
+      assert_ne!(a, c);
+      for p in points(50) {
+        assert_eq!(a.noise(p), b.noise(p));
+      }
+      assert!(points(50).iter().any(|p| a.noise(*p) != c.noise(*p)));
+    }
+
+    #[rstest]
+    #[case(Point3::new(0.0, 0.0, 0.0))]
+    #[case(Point3::new(1.0, -3.0, 7.0))]
+    #[case(Point3::new(-300.0, 256.0, 12.0))]
+    fn noise_lattice(#[case] p: Point3) {
+      assert_eq!(Perlin::new(1).noise(p), 0.0);
+    }
+
+    #[rstest]
+    fn noise_range() {
+      let perlin = Perlin::new(1);
+      for p in points(500) {
+        let n = perlin.noise(p);
+        assert!((-1.0..=1.0).contains(&n));
+      }
+    }
+
+    #[rstest]
+    fn noise_continuous() {
+      let perlin = Perlin::new(1);
+      let delta = Vector3::new(1e-6, 1e-6, 1e-6);
+      for p in points(100) {
+        assert!((perlin.noise(p) - perlin.noise(p + delta)).abs() < 1e-4);
+      }
+    }
+
+    #[rstest]
+    #[case(1)]
+    #[case(4)]
+    #[case(7)]
+    fn turbulence(#[case] octaves: usize) {
+      let perlin = Perlin::new(1);
+      for p in points(100) {
+        let t = perlin.turbulence(p, octaves);
+        assert!(t >= 0.0);
+        assert!(t <= 2.0);
+      }
+      assert_eq!(perlin.turbulence(Point3::new(0.3, 0.2, 0.1), 0), 0.0);
+    }
+
+    #[rstest]
+    fn fbm() {
+      let perlin = Perlin::new(1);
+      for p in points(100) {
+        assert_eq!(perlin.fbm(p, 1, 2.0, 0.5), perlin.noise(p));
+        assert!(perlin.fbm(p, 6, 2.0, 0.5).abs() <= 2.0);
+      }
+    }
+  }
+
+  mod presets {
+    use super::*;
+
+    #[rstest]
+    fn noise_texture() {
+      let texture = NoiseTexture::new(3, 4.0);
+      for p in points(100) {
+        let colour = texture.value(0.0, 0.0, p);
+        assert!(within(colour, Colour::new(0.0, 0.0, 0.0), Colour::new(1.0, 1.0, 1.0)));
+        assert_eq!(colour.x, colour.y);
+      }
+    }
+
+    #[rstest]
+    fn marble() {
+      let (vein, base) = (Colour::new(0.1, 0.1, 0.1), Colour::new(0.9, 0.9, 0.8));
+      let marble = Marble::new(3, 4.0, vein, base);
+      for p in points(100) {
+        assert!(within(marble.value(0.0, 0.0, p), vein, base));
+      }
+    }
+
+    #[rstest]
+    fn wood() {
+      let (early, late) = (Colour::new(0.8, 0.6, 0.4), Colour::new(0.4, 0.2, 0.1));
+      let wood = Wood::new(3, 2.0, early, late);
+      for p in points(100) {
+        assert!(within(wood.value(0.0, 0.0, p), early, late));
+      }
+    }
+
+    #[rstest]
+    #[case(0.0)]
+    #[case(0.5)]
+    #[case(1.0)]
+    fn clouds(#[case] coverage: f64) {
+      let (sky, cloud) = (Colour::new(0.3, 0.5, 0.9), Colour::new(1.0, 1.0, 1.0));
+      let clouds = Clouds::new(3, 0.5, sky, cloud, coverage);
+      for p in points(100) {
+        assert!(within(clouds.value(0.0, 0.0, p), sky, cloud));
+      }
+    }
+
+    #[rstest]
+    fn deterministic() {
+      let (a, b) = (Colour::new(0.0, 0.0, 0.0), Colour::new(1.0, 1.0, 1.0));
+      let (first, second) = (Marble::new(11, 4.0, a, b), Marble::new(11, 4.0, a, b));
+      for p in points(50) {
+        assert_eq!(first.value(0.0, 0.0, p), second.value(0.0, 0.0, p));
+      }
+    }
+  }
+}
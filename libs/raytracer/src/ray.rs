@@ -8,11 +8,17 @@ use vector::Vector3;
 pub struct Ray {
   position: Point3,
   direction: Vector3,
+  // instant the ray is cast at, within the camera shutter
+  time: f64,
 }
 
 impl Ray {
   pub fn new(position: Point3, direction: Vector3) -> Self {
-    Self { position, direction, }
+    Self::with_time(position, direction, 0.0)
+  }
+
+  pub fn with_time(position: Point3, direction: Vector3, time: f64) -> Self {
+    Self { position, direction, time, }
   }
 
   pub fn position(&self) -> Point3 {
@@ -23,6 +29,10 @@ impl Ray {
     self.direction
   }
 
+  pub fn time(&self) -> f64 {
+    self.time
+  }
+
   pub fn at(&self, d: f64) -> Point3 {
     self.position() + d * self.direction()
   }
@@ -43,6 +53,20 @@ mod tests {
       let ray = Ray::new(position, direction);
       assert_eq!(ray.position, position);
       assert_eq!(ray.direction, direction);
+      assert_eq!(ray.time, 0.0);
+    }
+
+    #[rstest]
+    #[case(0.0)]
+    #[case(0.5)]
+    #[case(-2.0)]
+    fn with_time(#[case] time: f64) {
+      let position = Point3::new(0.0, 0.0, 1.0);
+      let direction = Vector3::new(0.0, 1.0, 0.0);
+      let ray = Ray::with_time(position, direction, time);
+      assert_eq!(ray.position(), position);
+      assert_eq!(ray.direction(), direction);
+      assert_eq!(ray.time(), time);
     }
 
     #[rstest]
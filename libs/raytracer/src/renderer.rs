@@ -0,0 +1,144 @@
+
+use rayon::prelude::*;
+
+use crate::*;
+
+use camera::Camera;
+use hittable::Hittable;
+use tonemap::ToneMap;
+use colourspace::ColourSpace;
+use framebuffer::Framebuffer;
+
+/// Renders what a camera sees of a hittable into a framebuffer, rows in parallel
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Renderer {
+  // bounces a path may take before it is cut off
+  pub max_depth: usize,
+  // how the framebuffer is shown once encoded for display
+  pub tone_map: ToneMap,
+  // space the scene's colours are given, and so rendered, in
+  pub colour_space: ColourSpace,
+  // space the framebuffer is written out in
+  pub output_space: ColourSpace,
+}
+
+impl Renderer {
+  pub fn new(max_depth: usize) -> Self {
+    Self {
+      max_depth,
+      tone_map: ToneMap::default(),
+      colour_space: ColourSpace::default(),
+      output_space: ColourSpace::default(),
+    }
+  }
+
+  pub fn tone_map(mut self, tone_map: ToneMap) -> Self {
+    self.tone_map = tone_map;
+    self
+  }
+
+  pub fn colour_space(mut self, colour_space: ColourSpace) -> Self {
+    self.colour_space = colour_space;
+    self
+  }
+
+  pub fn output_space(mut self, output_space: ColourSpace) -> Self {
+    self.output_space = output_space;
+    self
+  }
+
+  pub fn render(&self, camera: &Camera, hittable: &impl Hittable) -> Framebuffer {
+    let (width, height) = (camera.config.image_width, camera.config.image_height);
+    let sampler = camera.sampler();
+
+    let stats = (0..height)
+      .into_par_iter()
+      .flat_map_iter(|j| {
+        let sampler = sampler.as_ref();
+        (0..width).map(move |i| camera.sample_pixel(sampler, hittable, i, j, self.max_depth))
+      })
+      .collect::<Vec<_>>();
+
+    let mut framebuffer = Framebuffer::new(width, height);
+    framebuffer.set_tone_map(self.tone_map);
+    framebuffer.set_colour_space(self.colour_space);
+    framebuffer.set_output_space(self.output_space);
+    for (n, stats) in stats.iter().enumerate() {
+      framebuffer.set(n % width, n / width, stats.mean(), stats.count());
+    }
+    framebuffer
+  }
+}
+
+impl Default for Renderer {
+  fn default() -> Self {
+    Self::new(50)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use rstest::*;
+
+  use colour::Colour;
+  use camera::CameraBuilder;
+  use adaptive::Adaptive;
+  use environment::Environment;
+  use hittable::VecOfHittable;
+  use tonemap::ToneMapOperator;
+  use colourspace::ColourSpace;
+
+  mod renderer {
+    use super::*;
+
+    #[rstest]
+    fn default() {
+      assert_eq!(Renderer::default(), Renderer::new(50));
+    }
+
+    #[rstest]
+    fn render() {
+      let camera = CameraBuilder::new(8, 2.0)
+        .background(Environment::sky())
+        .samples_per_pixel(4)
+        .seed(3)
+        .build().unwrap();
+      let nothing = VecOfHittable::new();
+      let renderer = Renderer::new(5);
+      let framebuffer = renderer.render(&camera, &nothing);
+      assert_eq!((framebuffer.width(), framebuffer.height()), (8, 4));
+      assert_eq!(framebuffer.sample_counts(), &[4; 32]);
+
+      // each pixel holds the mean of the samples the camera takes for it
+      let sampler = camera.sampler();
+      for (i, j) in [(0, 0), (7, 3), (3, 2)] {
+        let stats = camera.sample_pixel(sampler.as_ref(), &nothing, i, j, 5);
+        assert_eq!(framebuffer.get(i, j), stats.mean());
+      }
+      assert_eq!(framebuffer, renderer.render(&camera, &nothing));
+      assert_eq!(framebuffer.tone_map(), ToneMap::default());
+
+      let tone_map = ToneMap::new(ToneMapOperator::Aces, -1.0);
+      let tone_mapped = renderer.tone_map(tone_map).render(&camera, &nothing);
+      assert_eq!(tone_mapped.tone_map(), tone_map);
+      assert_eq!(tone_mapped.pixels(), framebuffer.pixels());
+
+      let spaces = renderer.colour_space(ColourSpace::AcesCg).output_space(ColourSpace::DisplayP3);
+      let converted = spaces.render(&camera, &nothing);
+      assert_eq!(converted.colour_space(), ColourSpace::AcesCg);
+      assert_eq!(converted.output_space(), ColourSpace::DisplayP3);
+      assert_eq!(converted.pixels(), framebuffer.pixels());
+    }
+
+    #[rstest]
+    fn render_adaptive() {
+      let camera = CameraBuilder::new(8, 2.0)
+        .background(Environment::Solid(Colour::new(0.2, 0.2, 0.2)))
+        .adaptive(Adaptive::new(4, 32, 0.01))
+        .build().unwrap();
+      let framebuffer = Renderer::default().render(&camera, &VecOfHittable::new());
+      assert_eq!(framebuffer.sample_counts(), &[4; 32]);
+    }
+  }
+}
@@ -0,0 +1,463 @@
+
+use rand::{Error, RngCore};
+
+#[allow(unused_imports)]
+use crate::*;
+
+use std::fmt;
+
+/// Produces a well mixed hash of words, folding each in with the splitmix64
+/// finaliser
+pub fn hash(words: &[u64]) -> u64 {
+  words.iter().fold(0x9e37_79b9_7f4a_7c15_u64, |hash, word| {
+    let mut z = (hash ^ word).wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+  })
+}
+
+// uniform in [0, 1) from the top 53 bits of a hash
+fn to_unit(bits: u64) -> f64 {
+  (bits >> 11) as f64 * (1.0 / (1u64 << 53) as f64)
+}
+
+/// Source of sample values in [0, 1). Each sample of each pixel is a point
+/// with as many dimensions as the path drawing it needs, taken in order:
+/// pixel position, lens, time, then two or so per bounce. Samplers hold no
+/// state, so one is shared by every render thread
+pub trait Sampler: Send + Sync {
+  /// Produces the value of dimension of sample index of the pixel with key pixel
+  fn sample(&self, pixel: u64, index: u64, dimension: u64) -> f64;
+}
+
+/// Sequence a camera draws its samples from
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum SamplerKind {
+  /// Uniform random values
+  #[default]
+  Independent,
+  /// Every dimension jittered within its own shuffled strata
+  Stratified,
+  /// Halton sequence with a random shift for each pixel and dimension
+  Halton,
+  /// Sobol sequence with hash based Owen scrambling
+  Sobol,
+}
+
+impl SamplerKind {
+  pub fn build(self, seed: u64, samples_per_pixel: usize) -> Box<dyn Sampler> {
+    match self {
+      Self::Independent => Box::new(IndependentSampler::new(seed)),
+      Self::Stratified => Box::new(StratifiedSampler::new(seed, samples_per_pixel)),
+      Self::Halton => Box::new(HaltonSampler::new(seed)),
+      Self::Sobol => Box::new(SobolSampler::new(seed)),
+    }
+  }
+}
+
+/// The dimensions of one sample of one pixel, drawn in order; as an RngCore
+/// it stands in for an rng anywhere one is taken
+pub struct PixelSample<'a> {
+  sampler: &'a dyn Sampler,
+  pixel: u64,
+  index: u64,
+  dimension: u64,
+}
+
+impl<'a> PixelSample<'a> {
+  /// Produces sample index of pixel (i, j), from its first dimension
+  pub fn new(sampler: &'a dyn Sampler, i: usize, j: usize, index: usize) -> Self {
+    Self { sampler, pixel: hash(&[i as u64, j as u64]), index: index as u64, dimension: 0, }
+  }
+
+  /// Produces the next dimension
+  pub fn next_1d(&mut self) -> f64 {
+    let value = self.sampler.sample(self.pixel, self.index, self.dimension);
+    self.dimension += 1;
+    value
+  }
+
+  pub fn next_2d(&mut self) -> (f64, f64) {
+    (self.next_1d(), self.next_1d())
+  }
+
+  pub fn dimension(&self) -> u64 {
+    self.dimension
+  }
+}
+
+impl fmt::Debug for PixelSample<'_> {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("PixelSample")
+      .field("pixel", &self.pixel)
+      .field("index", &self.index)
+      .field("dimension", &self.dimension)
+      .finish()
+  }
+}
+
+impl RngCore for PixelSample<'_> {
+  fn next_u32(&mut self) -> u32 {
+    (self.next_u64() >> 32) as u32
+  }
+
+  fn next_u64(&mut self) -> u64 {
+    // uniform floats are built from the top 53 bits, which keep the value exactly
+    let value = self.next_1d();
+    ((value * (1u64 << 53) as f64) as u64) << 11
+  }
+
+  fn fill_bytes(&mut self, dest: &mut [u8]) {
+    for chunk in dest.chunks_mut(8) {
+      let bytes = self.next_u64().to_le_bytes();
+      chunk.copy_from_slice(&bytes[..chunk.len()]);
+    }
+  }
+
+  fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+    self.fill_bytes(dest);
+    Ok(())
+  }
+}
+
+/// Uniform random values, independent across every pixel, sample and dimension
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct IndependentSampler {
+  seed: u64,
+}
+
+impl IndependentSampler {
+  pub fn new(seed: u64) -> Self {
+    Self { seed, }
+  }
+}
+
+impl Sampler for IndependentSampler {
+  fn sample(&self, pixel: u64, index: u64, dimension: u64) -> f64 {
+    to_unit(hash(&[self.seed, pixel, index, dimension]))
+  }
+}
+
+/// Produces element i of a random permutation of 0..length chosen by seed,
+/// without building it (Kensler, Correlated Multi-Jittered Sampling)
+pub fn permute(mut i: u32, length: u32, seed: u32) -> u32 {
+  let mut w = length.wrapping_sub(1);
+  w |= w >> 1;
+  w |= w >> 2;
+  w |= w >> 4;
+  w |= w >> 8;
+  w |= w >> 16;
+  // cycle walk until the hash lands back inside 0..length
+  loop {
+    i ^= seed;
+    i = i.wrapping_mul(0xe170893d);
+    i ^= seed >> 16;
+    i ^= (i & w) >> 4;
+    i ^= seed >> 8;
+    i = i.wrapping_mul(0x0929eb3f);
+    i ^= seed >> 23;
+    i ^= (i & w) >> 1;
+    i = i.wrapping_mul(1 | seed >> 27);
+    i = i.wrapping_mul(0x6935fa69);
+    i ^= (i & w) >> 11;
+    i = i.wrapping_mul(0x74dcb303);
+    i ^= (i & w) >> 2;
+    i = i.wrapping_mul(0x9e501cc3);
+    i ^= (i & w) >> 2;
+    i = i.wrapping_mul(0xc860a3df);
+    i &= w;
+    i ^= i >> 5;
+    if i < length {
+      break;
+    }
+  }
+  i.wrapping_add(seed) % length
+}
+
+/// Splits every dimension into samples_per_pixel strata and puts each
+/// sample of a pixel in its own stratum, jittered within it; strata are
+/// shuffled separately for each pixel and dimension, so dimensions do not
+/// correlate
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct StratifiedSampler {
+  seed: u64,
+  samples_per_pixel: usize,
+}
+
+impl StratifiedSampler {
+  pub fn new(seed: u64, samples_per_pixel: usize) -> Self {
+    Self { seed, samples_per_pixel: samples_per_pixel.max(1), }
+  }
+}
+
+impl Sampler for StratifiedSampler {
+  fn sample(&self, pixel: u64, index: u64, dimension: u64) -> f64 {
+    let strata = self.samples_per_pixel as u64;
+    let shuffle = hash(&[self.seed, pixel, dimension]);
+    // samples beyond the first pass start over in freshly shuffled strata
+    let pass = index / strata;
+    let stratum = permute((index % strata) as u32, strata as u32, (shuffle ^ hash(&[pass])) as u32);
+    let jitter = to_unit(hash(&[self.seed, pixel, index, dimension, 1]));
+    (stratum as f64 + jitter) / strata as f64
+  }
+}
+
+// bases for the Halton dimensions; later dimensions are independent
+const PRIMES: [u64; 32] = [
+  2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53,
+  59, 61, 67, 71, 73, 79, 83, 89, 97, 101, 103, 107, 109, 113, 127, 131,
+];
+
+/// Produces index with its base digits mirrored about the radix point
+pub fn radical_inverse(base: u64, mut index: u64) -> f64 {
+  let inv_base = 1.0 / base as f64;
+  let (mut reversed, mut scale) = (0.0, inv_base);
+  while index > 0 {
+    reversed += (index % base) as f64 * scale;
+    index /= base;
+    scale *= inv_base;
+  }
+  reversed.min(1.0 - f64::EPSILON / 2.0)
+}
+
+/// Halton sequence over each pixel's samples, dimension n in the nth prime
+/// base, toroidally shifted by a random offset for each pixel and dimension
+/// (Cranley-Patterson rotation) so pixels do not repeat one another
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct HaltonSampler {
+  seed: u64,
+}
+
+impl HaltonSampler {
+  pub fn new(seed: u64) -> Self {
+    Self { seed, }
+  }
+}
+
+impl Sampler for HaltonSampler {
+  fn sample(&self, pixel: u64, index: u64, dimension: u64) -> f64 {
+    let shift = to_unit(hash(&[self.seed, pixel, dimension]));
+    let Some(&base) = PRIMES.get(dimension as usize) else {
+      return to_unit(hash(&[self.seed, pixel, index, dimension]));
+    };
+    let value = radical_inverse(base, index) + shift;
+    if value >= 1.0 { value - 1.0 } else { value }
+  }
+}
+
+// primitive polynomial degree, coefficients and initial direction numbers of
+// Sobol dimensions 1 to 3 (Joe and Kuo); dimension 0 is van der Corput
+const SOBOL_PARAMETERS: [(usize, u32, [u32; 3]); 3] = [
+  (1, 0, [1, 0, 0]),
+  (2, 1, [1, 3, 0]),
+  (3, 1, [1, 3, 1]),
+];
+
+const fn sobol_directions() -> [[u32; 32]; 4] {
+  let mut directions = [[0; 32]; 4];
+  let mut bit = 0;
+  while bit < 32 {
+    directions[0][bit] = 1 << (31 - bit);
+    bit += 1;
+  }
+
+  let mut dimension = 1;
+  while dimension < 4 {
+    let (degree, coefficients, initial) = SOBOL_PARAMETERS[dimension - 1];
+    let v = &mut directions[dimension];
+    let mut i = 0;
+    while i < 32 {
+      if i < degree {
+        v[i] = initial[i] << (31 - i);
+      } else {
+        v[i] = v[i - degree] ^ (v[i - degree] >> degree);
+        let mut k = 1;
+        while k < degree {
+          v[i] ^= ((coefficients >> (degree - 1 - k)) & 1) * v[i - k];
+          k += 1;
+        }
+      }
+      i += 1;
+    }
+    dimension += 1;
+  }
+  directions
+}
+
+const SOBOL_DIRECTIONS: [[u32; 32]; 4] = sobol_directions();
+
+/// Produces point index of Sobol dimension 0 to 3, as 32 bits of fraction
+pub fn sobol(index: u32, dimension: usize) -> u32 {
+  let directions = &SOBOL_DIRECTIONS[dimension];
+  (0..32)
+    .filter(|bit| (index >> bit) & 1 == 1)
+    .fold(0, |value, bit| value ^ directions[bit])
+}
+
+/// Produces x with a random nested uniform (Owen) scramble of its bits, so
+/// each bit flips depending only on the bits above it (Burley, Practical
+/// Hash-based Owen Scrambling)
+pub fn owen_scramble(x: u32, seed: u32) -> u32 {
+  let mut x = x.reverse_bits();
+  x = x.wrapping_add(seed);
+  x ^= x.wrapping_mul(0x6c50b47c);
+  x ^= x.wrapping_mul(0xb82f1e52);
+  x ^= x.wrapping_mul(0xc7afe638);
+  x ^= x.wrapping_mul(0x8d22f6e6);
+  x.reverse_bits()
+}
+
+/// Owen scrambled Sobol points in four dimensions at a time; each group of
+/// four shuffles the order of the points on its own, which pads the 4D sets
+/// into as many dimensions as a path uses
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct SobolSampler {
+  seed: u64,
+}
+
+impl SobolSampler {
+  pub fn new(seed: u64) -> Self {
+    Self { seed, }
+  }
+}
+
+impl Sampler for SobolSampler {
+  fn sample(&self, pixel: u64, index: u64, dimension: u64) -> f64 {
+    let (group, component) = (dimension / 4, (dimension % 4) as usize);
+    let shuffled = owen_scramble(index as u32, hash(&[self.seed, pixel, group]) as u32);
+    let value = sobol(shuffled, component);
+    let scrambled = owen_scramble(value, hash(&[self.seed, pixel, group, component as u64 + 1]) as u32);
+    scrambled as f64 / (1u64 << 32) as f64
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use rstest::*;
+
+  use rand::Rng;
+
+  // one sampler of each kind
+  fn samplers(samples_per_pixel: usize) -> Vec<(SamplerKind, Box<dyn Sampler>)> {
+    [SamplerKind::Independent, SamplerKind::Stratified, SamplerKind::Halton, SamplerKind::Sobol]
+      .into_iter()
+      .map(|kind| (kind, kind.build(7, samples_per_pixel)))
+      .collect()
+  }
+
+  #[rstest]
+  fn range_and_reproducible() {
+    for (_, sampler) in samplers(16) {
+      for (pixel, index, dimension) in (0..2000).map(|n| (n % 7, n % 31, n % 13)) {
+        let value = sampler.sample(pixel, index, dimension);
+        assert!((0.0..1.0).contains(&value));
+        assert_eq!(value, sampler.sample(pixel, index, dimension));
+      }
+    }
+  }
+
+  #[rstest]
+  #[case(SamplerKind::Stratified)]
+  #[case(SamplerKind::Sobol)]
+  fn stratified(#[case] kind: SamplerKind) {
+    // each dimension of a pixel's samples puts one in every stratum
+    let sampler = kind.build(7, 16);
+    for pixel in 0..10 {
+      for dimension in 0..12 {
+        let mut strata: Vec<usize> = (0..16).map(|index| (sampler.sample(pixel, index, dimension) * 16.0) as usize).collect();
+        strata.sort();
+        assert_eq!(strata, (0..16).collect::<Vec<_>>());
+      }
+    }
+  }
+
+  #[rstest]
+  fn sobol_2d_net() {
+    // the first two scrambled dimensions of 16 points put one in every 4x4 cell
+    let sampler = SobolSampler::new(3);
+    let mut cells: Vec<(usize, usize)> = (0..16)
+      .map(|index| ((sampler.sample(0, index, 0) * 4.0) as usize, (sampler.sample(0, index, 1) * 4.0) as usize))
+      .collect();
+    cells.sort();
+    cells.dedup();
+    assert_eq!(cells.len(), 16);
+  }
+
+  #[rstest]
+  #[case(2, 1, 0.5)]
+  #[case(2, 6, 0.375)]
+  #[case(3, 1, 1.0 / 3.0)]
+  #[case(3, 5, 7.0 / 9.0)]
+  fn radical_inverse(#[case] base: u64, #[case] index: u64, #[case] expected: f64) {
+    assert!((super::radical_inverse(base, index) - expected).abs() < 1e-15);
+  }
+
+  #[rstest]
+  fn sobol() {
+    let first: Vec<(u32, u32)> = (0..4).map(|index| (super::sobol(index, 0), super::sobol(index, 1))).collect();
+    assert_eq!(first, vec![(0, 0), (1 << 31, 1 << 31), (1 << 30, 3 << 30), (3 << 30, 1 << 30)]);
+  }
+
+  #[rstest]
+  #[case(1)]
+  #[case(7)]
+  #[case(16)]
+  #[case(100)]
+  fn permute(#[case] length: u32) {
+    for seed in [0, 1, 0xdead_beef] {
+      let mut values: Vec<u32> = (0..length).map(|i| super::permute(i, length, seed)).collect();
+      values.sort();
+      assert_eq!(values, (0..length).collect::<Vec<_>>());
+    }
+  }
+
+  #[rstest]
+  fn owen_scramble() {
+    // scrambling only permutes aligned blocks, so 8 values stay a block of 8
+    let mut values: Vec<u32> = (0..8).map(|x| super::owen_scramble(x << 29, 12345) >> 29).collect();
+    values.sort();
+    assert_eq!(values, (0..8).collect::<Vec<_>>());
+  }
+
+  #[rstest]
+  fn pixel_sample() {
+    let sampler = HaltonSampler::new(1);
+    let mut sample = PixelSample::new(&sampler, 3, 4, 5);
+    let pixel = hash(&[3, 4]);
+    assert_eq!(sample.next_1d(), sampler.sample(pixel, 5, 0));
+    assert_eq!(sample.next_2d(), (sampler.sample(pixel, 5, 1), sampler.sample(pixel, 5, 2)));
+    // drawing through the rng interface keeps the value and moves on a dimension
+    assert_eq!(sample.gen::<f64>(), sampler.sample(pixel, 5, 3));
+    assert_eq!(sample.dimension(), 4);
+  }
+
+  #[rstest]
+  fn lower_error() {
+    // error in the mean over a pixel of a smooth function of four dimensions,
+    // as a path might be
+    let f = |x: [f64; 4]| (x[0] * 3.0).sin() * x[1] + x[2] * x[2] * (1.0 + x[3]);
+    let expected = (1.0 - 3.0_f64.cos()) / 3.0 * 0.5 + 1.0 / 3.0 * 1.5;
+    let (pixels, samples_per_pixel) = (200, 64);
+    let errors: Vec<(SamplerKind, f64)> = samplers(samples_per_pixel)
+      .into_iter()
+      .map(|(kind, sampler)| {
+        let squared_error: f64 = (0..pixels)
+          .map(|pixel| {
+            let mean = (0..samples_per_pixel as u64)
+              .map(|index| f([0, 1, 2, 3].map(|dimension| sampler.sample(pixel, index, dimension))))
+              .sum::<f64>() / samples_per_pixel as f64;
+            (mean - expected).powi(2)
+          })
+          .sum();
+        (kind, (squared_error / pixels as f64).sqrt())
+      })
+      .collect();
+
+    let independent = errors[0].1;
+    for (kind, error) in &errors[1..] {
+      assert!(*error < independent / 2.0, "{kind:?} error {error} against independent {independent}");
+    }
+  }
+}
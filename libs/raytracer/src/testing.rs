@@ -0,0 +1,105 @@
+
+use rand::{RngCore, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+use crate::*;
+
+use ray::Ray;
+use aabb::Aabb;
+use point::Point3;
+use vector::Vector3;
+use interval::Interval;
+use hittable::{Hittable, HitRecord};
+
+use std::f64::consts::PI;
+
+pub fn assert_near(a: Vector3, b: Vector3) {
+  assert!((a - b).length() < 1e-9, "{a} != {b}");
+}
+
+/// Sphere of radius 1 at the origin, sampled uniformly over the cone it subtends
+pub struct UnitSphere;
+
+impl Hittable for UnitSphere {
+  fn hit(&self, ray: &Ray, ray_i: Interval, record: &mut HitRecord) -> bool {
+    let oc = ray.position();
+    let a = ray.direction().length_squared();
+    let half_b = vector::dot(oc, ray.direction());
+    let c = oc.length_squared() - 1.0;
+    let discriminant = half_b*half_b - a*c;
+    if discriminant < 0.0 {
+      return false;
+    }
+    let sqrtd = discriminant.sqrt();
+    let mut d = (-half_b - sqrtd) / a;
+    if !ray_i.surrounds(d) {
+      d = (-half_b + sqrtd) / a;
+      if !ray_i.surrounds(d) {
+        return false;
+      }
+    }
+    record.d = d;
+    record.position = ray.at(d);
+    record.set_face_normal(ray, record.position);
+    true
+  }
+
+  fn bounding_box(&self) -> Aabb {
+    Aabb::from_points(Point3::new(-1.0, -1.0, -1.0), Point3::new(1.0, 1.0, 1.0))
+  }
+
+  fn pdf_value(&self, ray: &Ray) -> f64 {
+    let mut record = HitRecord::default();
+    if !self.hit(ray, Interval::new(0.001, f64::INFINITY), &mut record) {
+      return 0.0;
+    }
+    let distance_squared = ray.position().length_squared();
+    if distance_squared <= 1.0 {
+      return 1.0 / (4.0 * PI);
+    }
+    let cos_theta_max = (1.0 - 1.0 / distance_squared).sqrt();
+    1.0 / (2.0 * PI * (1.0 - cos_theta_max))
+  }
+
+  fn random(&self, mut rng: &mut dyn RngCore, origin: Point3, _: f64) -> Vector3 {
+    if origin.length_squared() <= 1.0 {
+      return Vector3::random_unit_vector(&mut rng);
+    }
+    // rejection keeps the draws uniform over the cone
+    let cos_theta_max = (1.0 - 1.0 / origin.length_squared()).sqrt();
+    loop {
+      let direction = Vector3::random_unit_vector(&mut rng);
+      if vector::dot(direction, -origin.to_unit()) >= cos_theta_max {
+        return direction;
+      }
+    }
+  }
+}
+
+/// Checks pdf_value integrates to one over directions from origin, and
+/// that random draws from it, by estimating the solid angle both ways
+pub fn assert_pdf_integrates(hittable: &impl Hittable, origin: Point3, time: f64) {
+  let mut rng = ChaCha8Rng::seed_from_u64(6);
+  let n = 200_000;
+  let (mut integral, mut hits) = (0.0, 0);
+  for _ in 0..n {
+    let ray = Ray::with_time(origin, Vector3::random_unit_vector(&mut rng), time);
+    let pdf = hittable.pdf_value(&ray);
+    integral += pdf;
+    hits += usize::from(pdf > 0.0);
+  }
+  let integral = 4.0 * PI * integral / n as f64;
+  assert!((integral - 1.0).abs() < 0.03, "pdf integrates to {integral}");
+
+  let uniform_solid_angle = 4.0 * PI * hits as f64 / n as f64;
+  let samples = 20_000;
+  let sampled_solid_angle = (0..samples)
+    .map(|_| {
+      let direction = hittable.random(&mut rng, origin, time);
+      let pdf = hittable.pdf_value(&Ray::with_time(origin, direction, time));
+      assert!(pdf > 0.0);
+      1.0 / pdf
+    })
+    .sum::<f64>() / samples as f64;
+  assert!((sampled_solid_angle / uniform_solid_angle - 1.0).abs() < 0.03);
+}
@@ -0,0 +1,169 @@
+
+use crate::*;
+
+use point::Point3;
+use colour::Colour;
+
+use std::sync::Arc;
+
+pub trait Texture: Send + Sync {
+  /// Produces the colour at surface coordinates (u, v) and point
+  fn value(&self, u: f64, v: f64, point: Point3) -> Colour;
+}
+
+pub struct SolidColour {
+  pub albedo: Colour,
+}
+
+impl SolidColour {
+  pub fn new(albedo: Colour) -> Self {
+    Self { albedo, }
+  }
+}
+
+impl Texture for SolidColour {
+  fn value(&self, _u: f64, _v: f64, _point: Point3) -> Colour {
+    self.albedo
+  }
+}
+
+/// Alternates between two textures in a 3D grid of cubes with side length `scale`
+pub struct Checker {
+  inv_scale: f64,
+  pub even: Arc<dyn Texture>,
+  pub odd: Arc<dyn Texture>,
+}
+
+impl Checker {
+  pub fn new(scale: f64, even: Arc<dyn Texture>, odd: Arc<dyn Texture>) -> Self {
+    Self { inv_scale: 1.0 / scale, even, odd, }
+  }
+
+  pub fn from_colours(scale: f64, even: Colour, odd: Colour) -> Self {
+    Self::new(scale, Arc::new(SolidColour::new(even)), Arc::new(SolidColour::new(odd)))
+  }
+}
+
+impl Texture for Checker {
+  fn value(&self, u: f64, v: f64, point: Point3) -> Colour {
+    let x = (self.inv_scale * point.x).floor() as i64;
+    let y = (self.inv_scale * point.y).floor() as i64;
+    let z = (self.inv_scale * point.z).floor() as i64;
+
+    if (x + y + z).rem_euclid(2) == 0 {
+      self.even.value(u, v, point)
+    } else {
+      self.odd.value(u, v, point)
+    }
+  }
+}
+
+/// Colours looked up from an image, top row first, with (0, 0) at the bottom left
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImageTexture {
+  width: usize,
+  height: usize,
+  pixels: Vec<Colour>,
+}
+
+impl ImageTexture {
+  pub fn new(width: usize, height: usize, pixels: Vec<Colour>) -> Result<Self, RaytracerError> {
+    if width == 0 || height == 0 || pixels.len() != width * height {
+      return Err(RaytracerError::ImageTextureError);
+    }
+    Ok(Self { width, height, pixels, })
+  }
+
+  pub fn width(&self) -> usize {
+    self.width
+  }
+
+  pub fn height(&self) -> usize {
+    self.height
+  }
+}
+
+impl Texture for ImageTexture {
+  fn value(&self, u: f64, v: f64, _point: Point3) -> Colour {
+    // flip v, image rows run top to bottom
+    let u = u.clamp(0.0, 1.0);
+    let v = 1.0 - v.clamp(0.0, 1.0);
+
+    let i = ((u * self.width as f64) as usize).min(self.width - 1);
+    let j = ((v * self.height as f64) as usize).min(self.height - 1);
+    self.pixels[j * self.width + i]
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use rstest::*;
+
+  mod solid_colour {
+    use super::*;
+
+    #[rstest]
+    #[case(0.0, 0.0, Point3::new(0.0, 0.0, 0.0))]
+    #[case(0.3, 0.9, Point3::new(-4.0, 2.0, 8.0))]
+    fn value(#[case] u: f64, #[case] v: f64, #[case] point: Point3) {
+      let albedo = Colour::new(0.1, 0.2, 0.3);
+      assert_eq!(SolidColour::new(albedo).value(u, v, point), albedo);
+    }
+  }
+
+  mod checker {
+    use super::*;
+
+    #[rstest]
+    #[case(Point3::new(0.5, 0.5, 0.5), Colour::new(1.0, 1.0, 1.0))]
+    #[case(Point3::new(1.5, 0.5, 0.5), Colour::new(0.0, 0.0, 0.0))]
+    #[case(Point3::new(1.5, 1.5, 0.5), Colour::new(1.0, 1.0, 1.0))]
+    #[case(Point3::new(-0.5, 0.5, 0.5), Colour::new(0.0, 0.0, 0.0))]
+    #[case(Point3::new(-0.5, -0.5, -0.5), Colour::new(0.0, 0.0, 0.0))]
+    #[case(Point3::new(2.5, 2.5, 2.5), Colour::new(1.0, 1.0, 1.0))]
+    fn value(#[case] point: Point3, #[case] expected: Colour) {
+      let checker = Checker::from_colours(1.0, Colour::new(1.0, 1.0, 1.0), Colour::new(0.0, 0.0, 0.0));
+      assert_eq!(checker.value(0.0, 0.0, point), expected);
+    }
+
+    #[rstest]
+    fn scale() {
+      let checker = Checker::from_colours(2.0, Colour::new(1.0, 1.0, 1.0), Colour::new(0.0, 0.0, 0.0));
+      assert_eq!(checker.value(0.0, 0.0, Point3::new(1.5, 0.5, 0.5)), Colour::new(1.0, 1.0, 1.0));
+      assert_eq!(checker.value(0.0, 0.0, Point3::new(2.5, 0.5, 0.5)), Colour::new(0.0, 0.0, 0.0));
+    }
+  }
+
+  mod image_texture {
+    use super::*;
+
+    fn quadrants() -> ImageTexture {
+      let pixels = vec![
+        Colour::new(1.0, 0.0, 0.0), Colour::new(0.0, 1.0, 0.0),
+        Colour::new(0.0, 0.0, 1.0), Colour::new(1.0, 1.0, 1.0),
+      ];
+      ImageTexture::new(2, 2, pixels).unwrap()
+    }
+
+    #[rstest]
+    #[case(2, 2, 4, true)]
+    #[case(2, 2, 3, false)]
+    #[case(0, 1, 0, false)]
+    fn new(#[case] width: usize, #[case] height: usize, #[case] count: usize, #[case] expected: bool) {
+      let texture = ImageTexture::new(width, height, vec![Colour::default(); count]);
+      assert_eq!(texture.is_ok(), expected);
+    }
+
+    #[rstest]
+    #[case(0.25, 0.75, Colour::new(1.0, 0.0, 0.0))]
+    #[case(0.75, 0.75, Colour::new(0.0, 1.0, 0.0))]
+    #[case(0.25, 0.25, Colour::new(0.0, 0.0, 1.0))]
+    #[case(0.75, 0.25, Colour::new(1.0, 1.0, 1.0))]
+    #[case(1.0, 1.0, Colour::new(0.0, 1.0, 0.0))]
+    #[case(-1.0, -1.0, Colour::new(0.0, 0.0, 1.0))]
+    fn value(#[case] u: f64, #[case] v: f64, #[case] expected: Colour) {
+      assert_eq!(quadrants().value(u, v, Point3::default()), expected);
+    }
+  }
+}
@@ -0,0 +1,173 @@
+
+use crate::*;
+
+use colour::Colour;
+use transform::Matrix3;
+
+/// Curves compressing linear radiance into the displayable range [0, 1]
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum ToneMapOperator {
+  // cuts off everything above 1
+  #[default]
+  Clamp,
+  Reinhard,
+  // reinhard that reaches 1 at white instead of at infinity
+  ExtendedReinhard { white: f64 },
+  // Narkowicz's fit of the ACES filmic reference rendering transform
+  Aces,
+  // Hable's Uncharted 2 filmic curve
+  Hable,
+  // Wrensch's polynomial fit of Sobotka's AgX with its default look
+  AgX,
+}
+
+impl ToneMapOperator {
+  pub fn apply(&self, colour: Colour) -> Colour {
+    let colour = Colour::new(colour.x.max(0.0), colour.y.max(0.0), colour.z.max(0.0));
+    let mapped = match *self {
+      Self::Clamp => colour,
+      Self::Reinhard => map_channels(colour, |x| x / (1.0 + x)),
+      Self::ExtendedReinhard { white } => {
+        map_channels(colour, |x| x * (1.0 + x / (white * white)) / (1.0 + x))
+      },
+      Self::Aces => {
+        map_channels(colour, |x| (x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14))
+      },
+      Self::Hable => {
+        let white_scale = 1.0 / hable(11.2);
+        map_channels(colour, |x| hable(2.0 * x) * white_scale)
+      },
+      Self::AgX => agx(colour),
+    };
+    map_channels(mapped, |x| x.clamp(0.0, 1.0))
+  }
+}
+
+/// Exposure, in stops, and the operator applied to linear radiance before it
+/// is encoded for display
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct ToneMap {
+  pub operator: ToneMapOperator,
+  // stops (EV) the radiance is scaled by; each one doubles it
+  pub exposure: f64,
+}
+
+impl ToneMap {
+  pub fn new(operator: ToneMapOperator, exposure: f64) -> Self {
+    Self { operator, exposure, }
+  }
+
+  /// Produces colour exposed and compressed into [0, 1], still linear
+  pub fn apply(&self, colour: Colour) -> Colour {
+    self.operator.apply(self.exposure.exp2() * colour)
+  }
+}
+
+fn map_channels(colour: Colour, f: impl Fn(f64) -> f64) -> Colour {
+  Colour::new(f(colour.x), f(colour.y), f(colour.z))
+}
+
+fn hable(x: f64) -> f64 {
+  let (a, b, c, d, e, f) = (0.15, 0.50, 0.10, 0.20, 0.02, 0.30);
+  ((x * (a * x + c * b) + d * e) / (x * (a * x + b) + d * f)) - e / f
+}
+
+fn agx(colour: Colour) -> Colour {
+  const INSET: Matrix3 = Matrix3::new([
+    [0.842479062253094, 0.0784335999999992, 0.0792237451477643],
+    [0.0423282422610123, 0.878468636469772, 0.0791661274605434],
+    [0.0423756549057051, 0.0784336, 0.879142973793104],
+  ]);
+  const OUTSET: Matrix3 = Matrix3::new([
+    [1.19687900512017, -0.0980208811401368, -0.0990297440797205],
+    [-0.0528968517574562, 1.15190312990417, -0.0989611768448433],
+    [-0.0529716355144438, -0.0980434501171241, 1.15107367264116],
+  ]);
+  const MIN_EV: f64 = -12.47393;
+  const MAX_EV: f64 = 4.026069;
+
+  // log encode, then the sigmoid contrast curve
+  let encoded = map_channels(INSET * colour, |x| {
+    let x = (x.max(1e-10).log2().clamp(MIN_EV, MAX_EV) - MIN_EV) / (MAX_EV - MIN_EV);
+    let (x2, x4) = (x * x, x * x * x * x);
+    15.5 * x4 * x2 - 40.14 * x4 * x + 31.96 * x4 - 6.868 * x2 * x + 0.4298 * x2 + 0.1191 * x - 0.00232
+  });
+  // the curve produces display values, so undo a 2.2 gamma to stay linear
+  map_channels(OUTSET * encoded, |x| x.max(0.0).powf(2.2))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use rstest::*;
+
+  mod tone_map_operator {
+    use super::*;
+
+    #[rstest]
+    #[case(ToneMapOperator::Clamp, 0.5, 0.5)]
+    #[case(ToneMapOperator::Clamp, 4.0, 1.0)]
+    #[case(ToneMapOperator::Reinhard, 1.0, 0.5)]
+    #[case(ToneMapOperator::Reinhard, 3.0, 0.75)]
+    #[case(ToneMapOperator::ExtendedReinhard { white: 4.0 }, 4.0, 1.0)]
+    #[case(ToneMapOperator::ExtendedReinhard { white: 4.0 }, 1.0, 0.53125)]
+    #[case(ToneMapOperator::Aces, 1.0, 0.8037974683544304)]
+    #[case(ToneMapOperator::Hable, 5.6, 1.0)]
+    fn apply(#[case] operator: ToneMapOperator, #[case] value: f64, #[case] expected: f64) {
+      let mapped = operator.apply(Colour::new(value, value, value));
+      assert!((mapped.x - expected).abs() < 1e-12, "{} != {expected}", mapped.x);
+      assert_eq!(mapped.x, mapped.y);
+      assert_eq!(mapped.y, mapped.z);
+    }
+
+    #[rstest]
+    #[case(ToneMapOperator::Clamp)]
+    #[case(ToneMapOperator::Reinhard)]
+    #[case(ToneMapOperator::ExtendedReinhard { white: 8.0 })]
+    #[case(ToneMapOperator::Aces)]
+    #[case(ToneMapOperator::Hable)]
+    #[case(ToneMapOperator::AgX)]
+    fn monotonic(#[case] operator: ToneMapOperator) {
+      // negative radiance is treated as black
+      let black = operator.apply(Colour::new(-1.0, -1.0, -1.0));
+      assert!(black.x >= 0.0 && black.x < 0.01);
+      let mut last = black.x;
+      for n in 1..200 {
+        let value = 0.05 * n as f64;
+        let mapped = operator.apply(Colour::new(value, value, value)).x;
+        assert!(mapped >= last && mapped <= 1.0, "{value} maps to {mapped}");
+        last = mapped;
+      }
+    }
+
+    #[rstest]
+    fn agx() {
+      // grey stays grey, mid grey lands near the middle of the display range
+      let grey = ToneMapOperator::AgX.apply(Colour::new(0.18, 0.18, 0.18));
+      assert!((grey.x - grey.y).abs() < 1e-3 && (grey.y - grey.z).abs() < 1e-3);
+      assert!(grey.x > 0.1 && grey.x < 0.3);
+      // bright saturated colours desaturate rather than clip to a flat primary
+      let red = ToneMapOperator::AgX.apply(Colour::new(64.0, 0.0, 0.0));
+      assert!(red.x > 0.8 && red.y > 0.05 && red.z > 0.05);
+    }
+  }
+
+  mod tone_map {
+    use super::*;
+
+    #[rstest]
+    fn default() {
+      assert_eq!(ToneMap::default(), ToneMap::new(ToneMapOperator::Clamp, 0.0));
+      let colour = Colour::new(0.25, 0.5, 2.0);
+      assert_eq!(ToneMap::default().apply(colour), Colour::new(0.25, 0.5, 1.0));
+    }
+
+    #[rstest]
+    #[case(1.0, Colour::new(0.5, 1.0, 1.0))]
+    #[case(-2.0, Colour::new(0.0625, 0.125, 0.5))]
+    fn exposure(#[case] stops: f64, #[case] expected: Colour) {
+      let tone_map = ToneMap::new(ToneMapOperator::Clamp, stops);
+      assert_eq!(tone_map.apply(Colour::new(0.25, 0.5, 2.0)), expected);
+    }
+  }
+}
@@ -0,0 +1,560 @@
+
+use crate::*;
+
+use ray::Ray;
+use aabb::Aabb;
+use point::Point3;
+use vector::Vector3;
+use interval::Interval;
+
+use std::ops::Mul;
+
+/// Row-major 4x4 matrix acting on column vectors in homogeneous coordinates
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Matrix4 {
+  pub m: [[f64; 4]; 4],
+}
+
+impl Matrix4 {
+  pub const fn new(m: [[f64; 4]; 4]) -> Self {
+    Self { m, }
+  }
+
+  pub fn translation(offset: Vector3) -> Self {
+    Self::new([
+      [1.0, 0.0, 0.0, offset.x],
+      [0.0, 1.0, 0.0, offset.y],
+      [0.0, 0.0, 1.0, offset.z],
+      [0.0, 0.0, 0.0, 1.0],
+    ])
+  }
+
+  pub fn scaling(factors: Vector3) -> Self {
+    Self::new([
+      [factors.x, 0.0, 0.0, 0.0],
+      [0.0, factors.y, 0.0, 0.0],
+      [0.0, 0.0, factors.z, 0.0],
+      [0.0, 0.0, 0.0, 1.0],
+    ])
+  }
+
+  /// Produces the counter-clockwise rotation by degrees about axis, looking
+  /// down the axis towards the origin (Rodrigues' formula)
+  pub fn rotation(axis: Vector3, degrees: f64) -> Self {
+    let Vector3 { x, y, z } = axis.to_unit();
+    let (sin, cos) = degrees.to_radians().sin_cos();
+    let t = 1.0 - cos;
+    Self::new([
+      [t*x*x + cos, t*x*y - sin*z, t*x*z + sin*y, 0.0],
+      [t*x*y + sin*z, t*y*y + cos, t*y*z - sin*x, 0.0],
+      [t*x*z - sin*y, t*y*z + sin*x, t*z*z + cos, 0.0],
+      [0.0, 0.0, 0.0, 1.0],
+    ])
+  }
+
+  pub fn transpose(&self) -> Self {
+    let mut m = [[0.0; 4]; 4];
+    for (i, row) in m.iter_mut().enumerate() {
+      for (j, value) in row.iter_mut().enumerate() {
+        *value = self.m[j][i];
+      }
+    }
+    Self::new(m)
+  }
+
+  /// Produces the inverse by Gauss-Jordan elimination, or None if singular
+  pub fn inverse(&self) -> Option<Self> {
+    let mut a = self.m;
+    let mut inv = IDENTITY.m;
+
+    for col in 0..4 {
+      // partial pivoting keeps the elimination stable
+      let pivot = (col..4)
+        .max_by(|&i, &j| a[i][col].abs().total_cmp(&a[j][col].abs()))
+        .unwrap_or(col);
+      if a[pivot][col].abs() < 1e-12 {
+        return None;
+      }
+      a.swap(col, pivot);
+      inv.swap(col, pivot);
+
+      let scale = 1.0 / a[col][col];
+      for j in 0..4 {
+        a[col][j] *= scale;
+        inv[col][j] *= scale;
+      }
+
+      for row in 0..4 {
+        if row == col {
+          continue;
+        }
+        let factor = a[row][col];
+        for j in 0..4 {
+          a[row][j] -= factor * a[col][j];
+          inv[row][j] -= factor * inv[col][j];
+        }
+      }
+    }
+
+    Some(Self::new(inv))
+  }
+
+  pub fn transform_point(&self, p: Point3) -> Point3 {
+    let m = &self.m;
+    let x = m[0][0]*p.x + m[0][1]*p.y + m[0][2]*p.z + m[0][3];
+    let y = m[1][0]*p.x + m[1][1]*p.y + m[1][2]*p.z + m[1][3];
+    let z = m[2][0]*p.x + m[2][1]*p.y + m[2][2]*p.z + m[2][3];
+    let w = m[3][0]*p.x + m[3][1]*p.y + m[3][2]*p.z + m[3][3];
+    if w == 1.0 {
+      Point3::new(x, y, z)
+    } else {
+      Point3::new(x / w, y / w, z / w)
+    }
+  }
+
+  /// Produces the transformed direction; translation does not apply
+  pub fn transform_vector(&self, v: Vector3) -> Vector3 {
+    let m = &self.m;
+    Vector3::new(
+      m[0][0]*v.x + m[0][1]*v.y + m[0][2]*v.z,
+      m[1][0]*v.x + m[1][1]*v.y + m[1][2]*v.z,
+      m[2][0]*v.x + m[2][1]*v.y + m[2][2]*v.z,
+    )
+  }
+}
+
+impl Default for Matrix4 {
+  fn default() -> Self {
+    IDENTITY
+  }
+}
+
+impl Mul for Matrix4 {
+  type Output = Self;
+
+  fn mul(self, rhs: Self) -> Self::Output {
+    let mut m = [[0.0; 4]; 4];
+    for (i, row) in m.iter_mut().enumerate() {
+      for (j, value) in row.iter_mut().enumerate() {
+        *value = (0..4).map(|k| self.m[i][k] * rhs.m[k][j]).sum();
+      }
+    }
+    Self::new(m)
+  }
+}
+
+pub const IDENTITY: Matrix4 = Matrix4::new([
+  [1.0, 0.0, 0.0, 0.0],
+  [0.0, 1.0, 0.0, 0.0],
+  [0.0, 0.0, 1.0, 0.0],
+  [0.0, 0.0, 0.0, 1.0],
+]);
+
+/// Row-major 3x3 matrix acting on column vectors, such as a linear map
+/// between colour spaces
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Matrix3 {
+  pub m: [[f64; 3]; 3],
+}
+
+impl Matrix3 {
+  pub const IDENTITY: Self = Self::new([
+    [1.0, 0.0, 0.0],
+    [0.0, 1.0, 0.0],
+    [0.0, 0.0, 1.0],
+  ]);
+
+  pub const fn new(m: [[f64; 3]; 3]) -> Self {
+    Self { m, }
+  }
+
+  pub fn diagonal(d: Vector3) -> Self {
+    Self::new([
+      [d.x, 0.0, 0.0],
+      [0.0, d.y, 0.0],
+      [0.0, 0.0, d.z],
+    ])
+  }
+
+  /// Produces the matrix with columns a, b and c
+  pub fn from_columns(a: Vector3, b: Vector3, c: Vector3) -> Self {
+    Self::new([
+      [a.x, b.x, c.x],
+      [a.y, b.y, c.y],
+      [a.z, b.z, c.z],
+    ])
+  }
+
+  pub fn transpose(&self) -> Self {
+    let mut m = [[0.0; 3]; 3];
+    for (i, row) in m.iter_mut().enumerate() {
+      for (j, value) in row.iter_mut().enumerate() {
+        *value = self.m[j][i];
+      }
+    }
+    Self::new(m)
+  }
+
+  pub fn determinant(&self) -> f64 {
+    let m = &self.m;
+    m[0][0] * (m[1][1]*m[2][2] - m[1][2]*m[2][1])
+      - m[0][1] * (m[1][0]*m[2][2] - m[1][2]*m[2][0])
+      + m[0][2] * (m[1][0]*m[2][1] - m[1][1]*m[2][0])
+  }
+
+  /// Produces the inverse from the adjugate, or None if singular
+  pub fn inverse(&self) -> Option<Self> {
+    let det = self.determinant();
+    if det.abs() < 1e-12 {
+      return None;
+    }
+    let mut inv = [[0.0; 3]; 3];
+    for (i, row) in inv.iter_mut().enumerate() {
+      for (j, value) in row.iter_mut().enumerate() {
+        // cofactor of the transposed element, with the sign folded into the cyclic order
+        let (r0, r1) = ((j + 1) % 3, (j + 2) % 3);
+        let (c0, c1) = ((i + 1) % 3, (i + 2) % 3);
+        *value = (self.m[r0][c0]*self.m[r1][c1] - self.m[r0][c1]*self.m[r1][c0]) / det;
+      }
+    }
+    Some(Self::new(inv))
+  }
+}
+
+impl Default for Matrix3 {
+  fn default() -> Self {
+    Self::IDENTITY
+  }
+}
+
+impl Mul for Matrix3 {
+  type Output = Self;
+
+  fn mul(self, rhs: Self) -> Self::Output {
+    let mut m = [[0.0; 3]; 3];
+    for (i, row) in m.iter_mut().enumerate() {
+      for (j, value) in row.iter_mut().enumerate() {
+        *value = (0..3).map(|k| self.m[i][k] * rhs.m[k][j]).sum();
+      }
+    }
+    Self::new(m)
+  }
+}
+
+impl Mul<Vector3> for Matrix3 {
+  type Output = Vector3;
+
+  fn mul(self, v: Vector3) -> Self::Output {
+    let m = &self.m;
+    Vector3::new(
+      m[0][0]*v.x + m[0][1]*v.y + m[0][2]*v.z,
+      m[1][0]*v.x + m[1][1]*v.y + m[1][2]*v.z,
+      m[2][0]*v.x + m[2][1]*v.y + m[2][2]*v.z,
+    )
+  }
+}
+
+/// Affine transform from object to world space, kept alongside its inverse.
+/// Chained calls apply in order, so `scale` then `translate` scales first
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Transform {
+  matrix: Matrix4,
+  inverse: Matrix4,
+}
+
+impl Transform {
+  pub fn new(matrix: Matrix4) -> Result<Self, RaytracerError> {
+    let inverse = matrix.inverse().ok_or(RaytracerError::TransformError)?;
+    Ok(Self { matrix, inverse, })
+  }
+
+  pub fn identity() -> Self {
+    Self::default()
+  }
+
+  pub fn matrix(&self) -> Matrix4 {
+    self.matrix
+  }
+
+  pub fn inverse(&self) -> Self {
+    Self { matrix: self.inverse, inverse: self.matrix, }
+  }
+
+  /// Produces the linear part, which acts on directions
+  pub fn linear(&self) -> Matrix3 {
+    let m = &self.matrix.m;
+    Matrix3::new([
+      [m[0][0], m[0][1], m[0][2]],
+      [m[1][0], m[1][1], m[1][2]],
+      [m[2][0], m[2][1], m[2][2]],
+    ])
+  }
+
+  /// Produces self followed by other
+  pub fn then(&self, other: &Transform) -> Self {
+    Self {
+      matrix: other.matrix * self.matrix,
+      inverse: self.inverse * other.inverse,
+    }
+  }
+
+  pub fn translate(&self, offset: Vector3) -> Self {
+    self.then(&Self {
+      matrix: Matrix4::translation(offset),
+      inverse: Matrix4::translation(-offset),
+    })
+  }
+
+  /// Scales by factors along each axis, which must all be non-zero
+  pub fn scale(&self, factors: Vector3) -> Self {
+    self.then(&Self {
+      matrix: Matrix4::scaling(factors),
+      inverse: Matrix4::scaling(Vector3::new(1.0 / factors.x, 1.0 / factors.y, 1.0 / factors.z)),
+    })
+  }
+
+  /// Rotates by degrees about axis, through the origin
+  pub fn rotate(&self, axis: Vector3, degrees: f64) -> Self {
+    let rotation = Matrix4::rotation(axis, degrees);
+    // rotations are orthogonal
+    self.then(&Self { matrix: rotation, inverse: rotation.transpose(), })
+  }
+
+  pub fn rotate_x(&self, degrees: f64) -> Self {
+    self.rotate(Vector3::new(1.0, 0.0, 0.0), degrees)
+  }
+
+  pub fn rotate_y(&self, degrees: f64) -> Self {
+    self.rotate(Vector3::new(0.0, 1.0, 0.0), degrees)
+  }
+
+  pub fn rotate_z(&self, degrees: f64) -> Self {
+    self.rotate(Vector3::new(0.0, 0.0, 1.0), degrees)
+  }
+
+  pub fn point(&self, p: Point3) -> Point3 {
+    self.matrix.transform_point(p)
+  }
+
+  pub fn vector(&self, v: Vector3) -> Vector3 {
+    self.matrix.transform_vector(v)
+  }
+
+  /// Produces the transformed unit normal; normals transform by the inverse
+  /// transpose so they stay perpendicular under non-uniform scale
+  pub fn normal(&self, n: Vector3) -> Vector3 {
+    self.inverse.transpose().transform_vector(n).to_unit()
+  }
+
+  /// Produces the transformed ray; directions are not renormalised, so ray
+  /// distances are the same on both sides of the transform
+  pub fn ray(&self, ray: &Ray) -> Ray {
+    Ray::with_time(self.point(ray.position()), self.vector(ray.direction()), ray.time())
+  }
+
+  /// Produces the box enclosing the transformed corners of bbox
+  pub fn bounding_box(&self, bbox: &Aabb) -> Aabb {
+    let axes = [bbox.x, bbox.y, bbox.z];
+    if axes.iter().any(|axis| axis.min > axis.max) {
+      return aabb::EMPTY;
+    }
+    if axes.iter().any(|axis| !axis.min.is_finite() || !axis.max.is_finite()) {
+      // infinite extents would multiply out to NaN
+      return aabb::UNIVERSE;
+    }
+
+    let mut result = aabb::EMPTY;
+    for i in 0..8 {
+      let corner = Point3::new(
+        if i & 1 == 0 { bbox.x.min } else { bbox.x.max },
+        if i & 2 == 0 { bbox.y.min } else { bbox.y.max },
+        if i & 4 == 0 { bbox.z.min } else { bbox.z.max },
+      );
+      let p = self.point(corner);
+      result = Aabb::surrounding(result, Aabb::new(
+        Interval::new(p.x, p.x),
+        Interval::new(p.y, p.y),
+        Interval::new(p.z, p.z),
+      ));
+    }
+    result
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use rstest::*;
+
+  use testing::assert_near;
+
+  mod matrix3 {
+    use super::*;
+
+    #[rstest]
+    fn mul_identity() {
+      let m = Matrix3::new([[1.0, 2.0, 3.0], [0.0, 1.0, 4.0], [5.0, 6.0, 0.0]]);
+      assert_eq!(m * Matrix3::IDENTITY, m);
+      assert_eq!(Matrix3::IDENTITY * m, m);
+      assert_eq!(Matrix3::default(), Matrix3::IDENTITY);
+      assert_eq!(m * Vector3::new(1.0, 0.0, -1.0), Vector3::new(-2.0, -4.0, 5.0));
+    }
+
+    #[rstest]
+    fn from_columns_transpose() {
+      let (a, b, c) = (Vector3::new(1.0, 2.0, 3.0), Vector3::new(4.0, 5.0, 6.0), Vector3::new(7.0, 8.0, 9.0));
+      let m = Matrix3::from_columns(a, b, c);
+      assert_eq!(m * Vector3::new(0.0, 1.0, 0.0), b);
+      assert_eq!(m.transpose().m[2], [7.0, 8.0, 9.0]);
+      assert_eq!(m.transpose().transpose(), m);
+      assert_eq!(Matrix3::diagonal(a) * c, Vector3::new(7.0, 16.0, 27.0));
+    }
+
+    #[rstest]
+    #[case(Matrix3::new([[1.0, 2.0, 3.0], [0.0, 1.0, 4.0], [5.0, 6.0, 0.0]]), 1.0)]
+    #[case(Matrix3::diagonal(Vector3::new(2.0, 0.5, -4.0)), -4.0)]
+    #[case(Matrix3::new([[0.4124, 0.3576, 0.1805], [0.2126, 0.7152, 0.0722], [0.0193, 0.1192, 0.9505]]), 0.20712)]
+    fn inverse(#[case] m: Matrix3, #[case] determinant: f64) {
+      assert!((m.determinant() - determinant).abs() < 1e-4);
+      let product = m * m.inverse().unwrap();
+      for (i, row) in product.m.iter().enumerate() {
+        for (j, value) in row.iter().enumerate() {
+          assert!((value - Matrix3::IDENTITY.m[i][j]).abs() < 1e-12);
+        }
+      }
+    }
+
+    #[rstest]
+    fn inverse_singular() {
+      assert_eq!(Matrix3::diagonal(Vector3::new(1.0, 0.0, 1.0)).inverse(), None);
+    }
+  }
+
+  mod matrix4 {
+    use super::*;
+
+    #[rstest]
+    fn mul_identity() {
+      let m = Matrix4::rotation(Vector3::new(1.0, 2.0, 3.0), 30.0) * Matrix4::translation(Vector3::new(1.0, 0.0, -2.0));
+      assert_eq!(m * IDENTITY, m);
+      assert_eq!(IDENTITY * m, m);
+      assert_eq!(Matrix4::default(), IDENTITY);
+    }
+
+    #[rstest]
+    fn transpose() {
+      let m = Matrix4::translation(Vector3::new(1.0, 2.0, 3.0));
+      assert_eq!(m.transpose().m[3], [1.0, 2.0, 3.0, 1.0]);
+      assert_eq!(m.transpose().transpose(), m);
+    }
+
+    #[rstest]
+    #[case(Matrix4::translation(Vector3::new(1.0, -2.0, 3.0)))]
+    #[case(Matrix4::scaling(Vector3::new(2.0, 0.5, -4.0)))]
+    #[case(Matrix4::rotation(Vector3::new(1.0, 1.0, 0.0), 73.0))]
+    #[case(Matrix4::new([[0.0, 1.0, 0.0, 0.0], [2.0, 0.0, 1.0, 0.0], [0.0, 3.0, 1.0, 5.0], [0.0, 0.0, 0.0, 1.0]]))]
+    fn inverse(#[case] m: Matrix4) {
+      let product = m * m.inverse().unwrap();
+      for (i, row) in product.m.iter().enumerate() {
+        for (j, value) in row.iter().enumerate() {
+          assert!((value - IDENTITY.m[i][j]).abs() < 1e-12);
+        }
+      }
+    }
+
+    #[rstest]
+    fn inverse_singular() {
+      assert_eq!(Matrix4::scaling(Vector3::new(1.0, 0.0, 1.0)).inverse(), None);
+    }
+
+    #[rstest]
+    #[case(Vector3::new(1.0, 0.0, 0.0), Vector3::new(0.0, 1.0, 0.0), Vector3::new(0.0, 0.0, 1.0))]
+    #[case(Vector3::new(0.0, 1.0, 0.0), Vector3::new(0.0, 0.0, 1.0), Vector3::new(1.0, 0.0, 0.0))]
+    #[case(Vector3::new(0.0, 0.0, 1.0), Vector3::new(1.0, 0.0, 0.0), Vector3::new(0.0, 1.0, 0.0))]
+    fn rotation(#[case] axis: Vector3, #[case] from: Vector3, #[case] expected: Vector3) {
+      assert_near(Matrix4::rotation(axis, 90.0).transform_vector(from), expected);
+    }
+
+    #[rstest]
+    fn transform_point_vector() {
+      let m = Matrix4::translation(Vector3::new(1.0, 2.0, 3.0));
+      let v = Vector3::new(1.0, 1.0, 1.0);
+      assert_eq!(m.transform_point(v), Point3::new(2.0, 3.0, 4.0));
+      assert_eq!(m.transform_vector(v), v);
+    }
+  }
+
+  mod transform {
+    use super::*;
+
+    #[rstest]
+    fn new() {
+      assert!(Transform::new(Matrix4::scaling(Vector3::new(1.0, 2.0, 3.0))).is_ok());
+      assert!(Transform::new(Matrix4::scaling(Vector3::new(1.0, 0.0, 3.0))).is_err());
+    }
+
+    #[rstest]
+    fn chain_order() {
+      let transform = Transform::identity()
+        .scale(Vector3::new(2.0, 2.0, 2.0))
+        .translate(Vector3::new(1.0, 0.0, 0.0));
+      assert_eq!(transform.point(Point3::new(1.0, 0.0, 0.0)), Point3::new(3.0, 0.0, 0.0));
+    }
+
+    #[rstest]
+    fn inverse() {
+      let transform = Transform::identity()
+        .scale(Vector3::new(2.0, 0.5, 3.0))
+        .rotate(Vector3::new(1.0, 2.0, -1.0), 40.0)
+        .translate(Vector3::new(-3.0, 1.0, 2.0));
+      let p = Point3::new(0.3, -1.2, 4.0);
+      assert_near(transform.inverse().point(transform.point(p)), p);
+      assert_near(transform.point(transform.inverse().point(p)), p);
+      let general = Transform::new(transform.matrix()).unwrap();
+      assert_near(general.inverse().point(p), transform.inverse().point(p));
+    }
+
+    #[rstest]
+    fn normal_non_uniform_scale() {
+      // the plane x + y = 0 squashed along x keeps normals perpendicular to it
+      let transform = Transform::identity().scale(Vector3::new(0.5, 1.0, 1.0));
+      let tangent = transform.vector(Vector3::new(1.0, -1.0, 0.0));
+      let normal = transform.normal(Vector3::new(1.0, 1.0, 0.0));
+      assert!(vector::dot(tangent, normal).abs() < 1e-12);
+      assert!((normal.length() - 1.0).abs() < 1e-12);
+      assert_near(normal, Vector3::new(2.0, 1.0, 0.0).to_unit());
+    }
+
+    #[rstest]
+    fn linear() {
+      let transform = Transform::identity()
+        .scale(Vector3::new(2.0, 0.5, 3.0))
+        .rotate(Vector3::new(1.0, 2.0, -1.0), 40.0)
+        .translate(Vector3::new(-3.0, 1.0, 2.0));
+      let v = Vector3::new(0.3, -1.2, 4.0);
+      assert_near(transform.linear() * v, transform.vector(v));
+      assert!((transform.linear().determinant() - 3.0).abs() < 1e-12);
+    }
+
+    #[rstest]
+    fn ray() {
+      let transform = Transform::identity().scale(Vector3::new(2.0, 2.0, 2.0));
+      let ray = transform.ray(&Ray::new(Point3::new(1.0, 0.0, 0.0), Vector3::new(0.0, 1.0, 0.0)));
+      assert_eq!(ray.position(), Point3::new(2.0, 0.0, 0.0));
+      assert_eq!(ray.direction(), Vector3::new(0.0, 2.0, 0.0));
+    }
+
+    #[rstest]
+    fn bounding_box() {
+      let bbox = Aabb::from_points(Point3::new(-1.0, -1.0, -1.0), Point3::new(1.0, 1.0, 1.0));
+      let transform = Transform::identity().rotate_z(45.0).translate(Vector3::new(0.0, 0.0, 5.0));
+      let result = transform.bounding_box(&bbox);
+      let r = 2.0_f64.sqrt();
+      assert!((result.x.max - r).abs() < 1e-12 && (result.x.min + r).abs() < 1e-12);
+      assert!((result.y.max - r).abs() < 1e-12 && (result.y.min + r).abs() < 1e-12);
+      assert_eq!(result.z, Interval::new(4.0, 6.0));
+      assert_eq!(transform.bounding_box(&aabb::EMPTY), aabb::EMPTY);
+      assert_eq!(transform.bounding_box(&aabb::UNIVERSE), aabb::UNIVERSE);
+    }
+  }
+}
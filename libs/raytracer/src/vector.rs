@@ -5,6 +5,7 @@ use rand::Rng;
 use crate::*;
 
 use std::fmt;
+use std::f64::consts::PI;
 use std::ops::{Neg, Add, Sub, Mul, Div, Index, IndexMut, AddAssign, MulAssign, DivAssign};
 
 #[derive(Debug, Default, Clone, Copy, PartialEq, PartialOrd)]
@@ -44,11 +45,31 @@ impl Vector3 {
     }
   }
 
+  pub fn random_in_unit_disk(rng: &mut impl Rng) -> Self {
+    loop {
+      let p = Vector3::new(rng.gen_range(-1.0..1.0), rng.gen_range(-1.0..1.0), 0.0);
+      if p.length_squared() < 1.0 {
+        return p;
+      }
+    }
+  }
+
   pub fn random_unit_vector(rng: &mut impl Rng) -> Self {
     let vec = Self::random_in_unit_sphere(rng);
     vec.to_unit()
   }
 
+  /// Produces a unit vector about normal with density cos(theta) / pi, from
+  /// exactly two draws
+  pub fn random_cosine_direction(rng: &mut impl Rng, normal: Self) -> Self {
+    let (r1, r2) = (rng.gen::<f64>(), rng.gen::<f64>());
+    let phi = 2.0 * PI * r1;
+    let (x, y, z) = (phi.cos() * r2.sqrt(), phi.sin() * r2.sqrt(), (1.0 - r2).sqrt());
+    let w = normal.to_unit();
+    let (u, v) = tangents(w);
+    x*u + y*v + z*w
+  }
+
   pub fn random_on_hemisphere(rng: &mut impl Rng, normal: Self) -> Self {
     let on_unit_sphere = Self::random_unit_vector(rng);
     // in same hemisphere as normal
@@ -233,6 +254,18 @@ pub fn near_zero(a: Vector3) -> bool {
   (a.x.abs() < s) && (a.y.abs() < s) && (a.z.abs() < s)
 }
 
+/// Produces two unit vectors that, with unit normal, form a right handed
+/// orthonormal basis (Duff et al., branchless)
+pub fn tangents(normal: Vector3) -> (Vector3, Vector3) {
+  let sign = 1.0_f64.copysign(normal.z);
+  let a = -1.0 / (sign + normal.z);
+  let b = normal.x * normal.y * a;
+  (
+    Vector3::new(1.0 + sign * normal.x * normal.x * a, sign * b, -sign * normal.x),
+    Vector3::new(b, sign + normal.y * normal.y * a, -normal.y),
+  )
+}
+
 pub fn reflect(a: Vector3, b: Vector3) -> Vector3 {
   // reflected dir is just a + 2(dist_from_a_inside_to_radius),
   // where the length of dist_from_a_inside_to_radius is a*n,
@@ -311,6 +344,16 @@ mod tests {
       }
     }
 
+    #[rstest]
+    fn random_in_unit_disk() {
+      let mut rng = ChaCha8Rng::seed_from_u64(4);
+      for _ in 0..100 {
+        let vec = Vector3::random_in_unit_disk(&mut rng);
+        assert!(vec.length_squared() < 1.0);
+        assert_eq!(vec.z, 0.0);
+      }
+    }
+
     #[rstest]
     fn random_unit_vector() {
       let approx_equal = |a: f64, b: f64, dp: u8| -> bool {
@@ -326,6 +369,23 @@ mod tests {
       }
     }
 
+    #[rstest]
+    fn random_cosine_direction() {
+      let mut rng = ChaCha8Rng::seed_from_u64(4);
+      let normal = Vector3::new(0.3, 0.4, 0.8);
+      let samples = 20_000;
+      let mut mean_cos = 0.0;
+      for _ in 0..samples {
+        let vec = Vector3::random_cosine_direction(&mut rng, normal);
+        assert!((vec.length() - 1.0).abs() < 1e-9);
+        let cos = super::dot(vec, normal.to_unit());
+        assert!(cos >= 0.0);
+        mean_cos += cos / samples as f64;
+      }
+      // the mean of cos(theta) under density cos(theta) / pi is 2/3
+      assert!((mean_cos - 2.0 / 3.0).abs() < 0.01);
+    }
+
     #[rstest]
     fn random_on_hemisphere() {
       let mut rng = ChaCha8Rng::seed_from_u64(4);
@@ -486,5 +546,20 @@ mod tests {
     fn cross(#[case] a: Vector3, #[case] b: Vector3, #[case] expected: Vector3) {
       assert_eq!(super::cross(a, b), expected);
     }
+
+    #[rstest]
+    #[case(Vector3::new(0.0, 0.0, 1.0))]
+    #[case(Vector3::new(0.0, 0.0, -1.0))]
+    #[case(Vector3::new(0.0, 1.0, 0.0))]
+    #[case(Vector3::new(1.0, -2.0, 0.5).to_unit())]
+    fn tangents(#[case] normal: Vector3) {
+      let (t, b) = super::tangents(normal);
+      for (x, y) in [(t, b), (b, normal), (normal, t)] {
+        assert!(super::dot(x, y).abs() < 1e-12);
+      }
+      assert!((t.length() - 1.0).abs() < 1e-12);
+      assert!((b.length() - 1.0).abs() < 1e-12);
+      assert!((super::cross(t, b) - normal).length() < 1e-12);
+    }
   }
 }
\ No newline at end of file
@@ -0,0 +1,42 @@
+
+use criterion::{criterion_group, criterion_main, Criterion};
+
+use lib_raytracer::prelude::*; // external crate; pub fns only
+
+mod bench_bvh {
+  use super::*;
+
+  const SPHERE_COUNT: usize = 2000;
+  const SAMPLES_PER_PIXEL: usize = 4;
+
+  pub fn render_list(c: &mut Criterion) {
+    let mut group = c.benchmark_group("bvh_render");
+    group.sample_size(10);
+    group.bench_function("VecOfHittable::render", |b| {
+      let (mut camera, hittable) = simulation::generate_sphere_field(SPHERE_COUNT, 42);
+      camera.config.samples_per_pixel = SAMPLES_PER_PIXEL;
+      let renderer = Renderer::default();
+      b.iter(|| renderer.render(&camera, &hittable))
+    });
+    group.finish();
+  }
+
+  pub fn render_bvh(c: &mut Criterion) {
+    let mut group = c.benchmark_group("bvh_render");
+    group.sample_size(10);
+    group.bench_function("Bvh::render", |b| {
+      let (mut camera, hittable) = simulation::generate_sphere_field(SPHERE_COUNT, 42);
+      camera.config.samples_per_pixel = SAMPLES_PER_PIXEL;
+      let bvh = Bvh::new(hittable);
+      let renderer = Renderer::default();
+      b.iter(|| renderer.render(&camera, &bvh))
+    });
+    group.finish();
+  }
+}
+
+criterion_group!(benches_bvh, 
+  bench_bvh::render_list, 
+  bench_bvh::render_bvh, 
+);
+criterion_main!(benches_bvh);
@@ -12,9 +12,9 @@ mod bench_png {
     let mut group = c.benchmark_group("png_render");
     group.sample_size(10);
     group.bench_function("Png::render", |b| {
-      let (mut camera, mut hittable) = simulation::generate_world();
-      let mut png = Png::new();
-      b.iter(|| png.render(&mut camera, &mut hittable)) 
+      let (camera, hittable) = simulation::generate_world();
+      let renderer = Renderer::default();
+      b.iter(|| Png.encode_to_vec(&renderer.render(&camera, &hittable)))
     });
     group.finish();
   }
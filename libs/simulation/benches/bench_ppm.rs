@@ -3,7 +3,7 @@ use criterion::{criterion_group, criterion_main, Criterion};
 
 use lib_raytracer::prelude::*; // external crate; pub fns only
 
-use simulation::ppm::Ppm;
+use simulation::ppm::{Ppm, PnmEncoding, BitDepth};
 
 mod bench_ppm {
   use super::*;
@@ -12,15 +12,33 @@ mod bench_ppm {
     let mut group = c.benchmark_group("ppm_render");
     group.sample_size(10);
     group.bench_function("Ppm::render", |b| {
-      let (mut camera, mut hittable) = simulation::generate_world();
-      let mut ppm = Ppm::new();
-      b.iter(|| ppm.render(&mut camera, &mut hittable)) 
+      let (camera, hittable) = simulation::generate_world();
+      let renderer = Renderer::default();
+      b.iter(|| Ppm::default().encode_to_vec(&renderer.render(&camera, &hittable)))
     });
     group.finish();
   }
+
+  // encodes one render in each variant, so only the encoding is timed
+  pub fn encode(c: &mut Criterion) {
+    let mut group = c.benchmark_group("ppm_encode");
+    let (mut camera, hittable) = simulation::generate_world();
+    camera.config.samples_per_pixel = 1;
+    let framebuffer = Renderer::default().render(&camera, &hittable);
+    for encoding in [PnmEncoding::Ascii, PnmEncoding::Binary] {
+      for depth in [BitDepth::Eight, BitDepth::Sixteen] {
+        group.bench_function(format!("Ppm::encode({encoding:?}, {depth:?})"), |b| {
+          let ppm = Ppm::new(encoding, depth);
+          b.iter(|| ppm.encode_to_vec(&framebuffer))
+        });
+      }
+    }
+    group.finish();
+  }
 }
 
 criterion_group!(benches_ppm, 
   bench_ppm::render, 
+  bench_ppm::encode, 
 );
 criterion_main!(benches_ppm);
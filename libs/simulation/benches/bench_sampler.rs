@@ -0,0 +1,31 @@
+
+use criterion::{criterion_group, criterion_main, Criterion};
+
+use lib_raytracer::prelude::*; // external crate; pub fns only
+
+mod bench_sampler {
+  use super::*;
+
+  const SAMPLES_PER_PIXEL: usize = 16;
+
+  // renders the same world with each sampler, for comparing noise at equal time
+  pub fn render(c: &mut Criterion) {
+    let mut group = c.benchmark_group("sampler_render");
+    group.sample_size(10);
+    for kind in [SamplerKind::Independent, SamplerKind::Stratified, SamplerKind::Halton, SamplerKind::Sobol] {
+      group.bench_function(format!("{kind:?}::render"), |b| {
+        let (mut camera, hittable) = simulation::generate_world();
+        camera.config.samples_per_pixel = SAMPLES_PER_PIXEL;
+        camera.config.sampler = kind;
+        let renderer = Renderer::default();
+        b.iter(|| renderer.render(&camera, &hittable))
+      });
+    }
+    group.finish();
+  }
+}
+
+criterion_group!(benches_sampler, 
+  bench_sampler::render, 
+);
+criterion_main!(benches_sampler);
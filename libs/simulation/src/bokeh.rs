@@ -0,0 +1,21 @@
+
+use lib_raytracer::prelude::*;
+
+use std::path::Path;
+use std::sync::Arc;
+
+/// Produces an aperture shaped like the image at path, where brighter pixels
+/// let more light through
+pub fn load_aperture<P: AsRef<Path>>(path: P) -> Result<Aperture, RaytracerError> {
+  let image = image::open(path)
+    .map_err(|_| RaytracerError::ImageLoadError)?
+    .into_luma8();
+  let (width, height) = image.dimensions();
+  let weights: Vec<f64> = image
+    .pixels()
+    .map(|pixel| pixel.0[0] as f64 / 255.0)
+    .collect();
+
+  let mask = ApertureMask::new(width as usize, height as usize, &weights)?;
+  Ok(Aperture::Mask(Arc::new(mask)))
+}
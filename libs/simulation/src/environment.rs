@@ -0,0 +1,23 @@
+
+use lib_raytracer::prelude::*;
+
+use crate::texture::image_colours;
+
+use std::path::Path;
+use std::sync::Arc;
+
+/// Produces an environment from an equirectangular image, such as a Radiance
+/// `.hdr` or OpenEXR light probe; float pixel values are used as linear
+/// radiance, and 8 or 16-bit ones are decoded from sRGB first
+pub fn load_environment_map<P: AsRef<Path>>(path: P) -> Result<Environment, RaytracerError> {
+  load_environment_map_in(path, ColourSpace::Srgb)
+}
+
+/// Produces an environment from an equirectangular image, converted into the
+/// linear colour_space a scene is rendered in
+pub fn load_environment_map_in<P: AsRef<Path>>(path: P, colour_space: ColourSpace) -> Result<Environment, RaytracerError> {
+  let image = image::open(path).map_err(|_| RaytracerError::ImageLoadError)?;
+  let (width, height) = (image.width() as usize, image.height() as usize);
+  let map = EnvironmentMap::new(width, height, image_colours(&image, colour_space))?;
+  Ok(Environment::Map(Arc::new(map)))
+}
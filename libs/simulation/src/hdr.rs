@@ -0,0 +1,68 @@
+
+use lib_raytracer::prelude::*;
+
+use image::Rgb;
+use image::codecs::hdr::HdrEncoder;
+
+use std::io::Write;
+
+/// Radiance RGBE encoder writing linear colour in the output space with a
+/// shared exponent
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Hdr;
+
+impl ImageEncoder for Hdr {
+  fn extension(&self) -> &'static str {
+    "hdr"
+  }
+
+  fn encode(&self, framebuffer: &Framebuffer, writer: &mut dyn Write) -> Result<(), RaytracerError> {
+    let pixels = framebuffer.to_output()
+      .iter()
+      .map(|colour| Rgb([colour.x as f32, colour.y as f32, colour.z as f32]))
+      .collect::<Vec<_>>();
+    HdrEncoder::new(writer)
+      .encode(&pixels, framebuffer.width(), framebuffer.height())
+      .map_err(|error| RaytracerError::ImageEncodeError { message: error.to_string(), })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use rstest::*;
+
+  #[rstest]
+  fn encode() {
+    let pixels = vec![Colour::new(0.5, 2.0, 16.0), Colour::new(0.0, 0.25, 1000.0)];
+    let framebuffer = Framebuffer::from_pixels(2, 1, pixels).unwrap();
+    let bytes = Hdr.encode_to_vec(&framebuffer).unwrap();
+    assert!(bytes.starts_with(b"#?RADIANCE"));
+    let image = image::load_from_memory(&bytes).unwrap().into_rgb32f();
+    assert_eq!(image.dimensions(), (2, 1));
+    // the shared exponent keeps about 8 bits relative to each pixel's brightest channel
+    let expected = [0.5, 2.0, 16.0, 0.0, 0.25, 1000.0];
+    for (n, (&value, expected)) in image.into_raw().iter().zip(expected).enumerate() {
+      let tolerance = if n < 3 { 16.0 } else { 1000.0 } / 128.0;
+      assert!((value - expected).abs() <= tolerance, "{value} != {expected}");
+    }
+  }
+
+  #[rstest]
+  fn encode_error() {
+    struct Full;
+    impl Write for Full {
+      fn write(&mut self, _: &[u8]) -> std::io::Result<usize> {
+        Err(std::io::Error::other("disk full"))
+      }
+
+      fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+      }
+    }
+
+    let framebuffer = Framebuffer::from_pixels(1, 1, vec![Colour::new(1.0, 1.0, 1.0)]).unwrap();
+    let result = Hdr.encode(&framebuffer, &mut Full);
+    assert!(matches!(result, Err(RaytracerError::ImageEncodeError { message }) if message.contains("disk full")));
+  }
+}
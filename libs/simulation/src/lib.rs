@@ -3,10 +3,33 @@ use lib_raytracer::prelude::*;
 
 pub mod ppm;
 pub mod png;
+pub mod openexr;
+pub mod hdr;
+pub mod pfm;
+pub mod obj;
+pub mod bokeh;
+pub mod environment;
 pub mod sphere;
+pub mod planar;
+pub mod texture;
+pub mod triangle;
+pub mod volume;
 
+#[cfg(test)]
+mod testing;
+
+use ppm::{Ppm, Pgm};
+use png::Png;
+use openexr::Exr;
+use hdr::Hdr;
+use pfm::Pfm;
 use sphere::Sphere;
+use planar::Plane;
+
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
 
+use std::path::Path;
 use std::sync::Arc;
 
 pub fn generate_world() -> (Camera, impl Hittable) {
@@ -20,8 +43,11 @@ pub fn generate_world() -> (Camera, impl Hittable) {
     let material_left   = Arc::new(Dielectric::new(1.5));
     let material_right  = Arc::new(Metal::new(Colour::new(0.8, 0.6, 0.2), 0.0));
 
+    // the ground's normal is a fixed non-zero vector
+    let ground = Plane::new(Point3::new(0.0, -0.5, 0.0), Vector3::new(0.0, 1.0, 0.0), material_ground).unwrap();
+
     vec![
-      Box::new(Sphere::new(100.0, Point3::new(0.0, -100.5, -1.0), material_ground)),
+      Box::new(ground),
       Box::new(Sphere::new(0.5, Point3::new(0.0, 0.0, -1.0), material_center)),
       Box::new(Sphere::new(0.5, Point3::new(-1.0, 0.0, -1.0), material_left)),
       Box::new(Sphere::new(0.5, Point3::new(1.0, 0.0, -1.0), material_right)),
@@ -31,7 +57,102 @@ pub fn generate_world() -> (Camera, impl Hittable) {
   (camera, objects)
 }
 
-pub fn render_scene_with_world(scene: &mut impl Scene) -> Result<usize, RaytracerError> {
-  let (mut camera, mut hittable) = generate_world();
-  scene.render(&mut camera, &mut hittable)
-}
\ No newline at end of file
+/// Produces a camera and `count` small randomly placed spheres in front of it
+pub fn generate_sphere_field(count: usize, seed: u64) -> (Camera, VecOfHittable) {
+  let width = 400;
+  let aspect_ratio = 16.0 / 9.0;
+  let mut rng = StdRng::seed_from_u64(seed);
+
+  let camera = Camera::new(width, aspect_ratio);
+  let objects: VecOfHittable = (0..count)
+    .map(|_| {
+      let center = Point3::new(
+        rng.gen_range(-4.0..4.0),
+        rng.gen_range(-2.0..2.0),
+        rng.gen_range(-6.0..-2.0),
+      );
+      let material: Arc<dyn Material> = match rng.gen_range(0..3) {
+        0 => Arc::new(Lambertian::new(Colour::random(&mut rng))),
+        1 => Arc::new(Metal::new(Colour::random_range(&mut rng, 0.5, 1.0), rng.gen_range(0.0..0.5))),
+        _ => Arc::new(Dielectric::new(1.5)),
+      };
+      Box::new(Sphere::new(rng.gen_range(0.02..0.1), center, material)) as Box<dyn Hittable>
+    })
+    .collect();
+
+  (camera, objects)
+}
+
+pub fn render_world(renderer: &Renderer) -> Framebuffer {
+  let (camera, hittable) = generate_world();
+  renderer.render(&camera, &hittable)
+}
+
+/// Produces an encoder, with its default settings, for every format images
+/// can be saved in; a new format only needs adding here
+pub fn encoders() -> Vec<Box<dyn ImageEncoder>> {
+  vec![
+    Box::new(Ppm::default()),
+    Box::new(Pgm::default()),
+    Box::new(Png),
+    Box::new(Exr::Half),
+    Box::new(Hdr),
+    Box::new(Pfm),
+  ]
+}
+
+/// Produces the encoder for images saved with extension, if there is one
+pub fn encoder_for_extension(extension: &str) -> Option<Box<dyn ImageEncoder>> {
+  encoders()
+    .into_iter()
+    .find(|encoder| encoder.extension().eq_ignore_ascii_case(extension))
+}
+
+/// Produces the encoder for the format named by the extension of path
+pub fn encoder_for_path<P: AsRef<Path>>(path: P) -> Result<Box<dyn ImageEncoder>, RaytracerError> {
+  let extension = path.as_ref().extension().map(|extension| extension.to_string_lossy()).unwrap_or_default();
+  encoder_for_extension(&extension)
+    .ok_or_else(|| RaytracerError::UnknownExtensionError { extension: extension.into_owned(), })
+}
+
+/// Saves framebuffer in the format named by the extension of path
+///
+/// Produces number of bytes written
+pub fn save_framebuffer<P: AsRef<Path>>(framebuffer: &Framebuffer, path: P) -> Result<usize, RaytracerError> {
+  let path = path.as_ref();
+  encoder_for_path(path)?.save_to_path(framebuffer, path)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use rstest::*;
+
+  #[rstest]
+  #[case("ppm", Some("ppm"))]
+  #[case("PNG", Some("png"))]
+  #[case("exr", Some("exr"))]
+  #[case("jpg", None)]
+  fn encoder_for_extension(#[case] extension: &str, #[case] expected: Option<&str>) {
+    let encoder = super::encoder_for_extension(extension);
+    assert_eq!(encoder.map(|encoder| encoder.extension()), expected);
+  }
+
+  #[rstest]
+  #[case("output.jpg", "jpg")]
+  #[case("output", "")]
+  fn save_framebuffer_unknown_extension(#[case] path: &str, #[case] expected: &str) {
+    let framebuffer = Framebuffer::from_pixels(1, 1, vec![Colour::default()]).unwrap();
+    let result = save_framebuffer(&framebuffer, path);
+    assert!(matches!(result, Err(RaytracerError::UnknownExtensionError { extension }) if extension == expected));
+  }
+
+  #[rstest]
+  fn encoders_are_unique() {
+    let mut extensions: Vec<_> = encoders().iter().map(|encoder| encoder.extension()).collect();
+    let count = extensions.len();
+    extensions.sort_unstable();
+    extensions.dedup();
+    assert_eq!(extensions.len(), count);
+  }
+}
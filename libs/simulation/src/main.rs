@@ -1,37 +1,23 @@
-
 use simulation::*;
 
-use ppm::Ppm;
-use png::Png;
+use lib_raytracer::prelude::*;
 
+use std::env;
 use std::error::Error;
+use std::path::Path;
 
 fn main() -> Result<(), Box<dyn Error>> {
-  let scene_option = "png";
-
-  match scene_option {
-    "ppm" => {
-      let path = "./output.ppm";
-      let mut ppm = Ppm::new();
-      println!("Rendering scene at {path}...");
-      let count = render_scene_with_world(&mut ppm)?;
+  // the output's extension picks its encoder, e.g. ./output.ppm or ./output.exr
+  let path = env::args().nth(1).unwrap_or_else(|| "./output.png".to_string());
+  // checked before rendering, so an unknown extension fails fast
+  let encoder = encoder_for_path(&path)?;
 
-      println!("Image rendered with {count} bytes, attempting to save...");
-      ppm.save_to_path(path)?;
-      println!("Scene saved... DONE");
-    },
-    "png" => {
-      let path = "./output.png";
-      let mut png = Png::new();
-      println!("Rendering scene at {path}...");
-      let count = render_scene_with_world(&mut png)?;
+  println!("Rendering scene...");
+  let framebuffer = render_world(&Renderer::default());
 
-      println!("Image rendered with {count} bytes, attempting to save...");
-      png.save_to_path(path)?;
-      println!("Scene saved... DONE");
-    },
-    _ => ()
-  }
+  println!("Image rendered, attempting to save {path}...");
+  let count = encoder.save_to_path(&framebuffer, Path::new(&path))?;
+  println!("Scene saved with {count} bytes... DONE");
 
   Ok(())
 }
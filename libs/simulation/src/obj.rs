@@ -0,0 +1,525 @@
+
+use lib_raytracer::prelude::*;
+
+use crate::texture::load_image_texture;
+use crate::triangle::{TriangleMesh, MeshBuffers, MeshMaterial};
+
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+use std::collections::HashMap;
+
+/// Materials of a `.mtl` library, by name
+pub type MaterialLibrary = HashMap<String, Arc<dyn Material>>;
+
+/// Produces the meshes of the `.obj` file at path, with materials from any
+/// `mtllib` it references (resolved relative to the file)
+pub fn load_obj<P: AsRef<Path>>(path: P) -> Result<Bvh, RaytracerError> {
+  let path = path.as_ref();
+  let source = fs::read_to_string(path)?;
+  let base = path.parent().unwrap_or(Path::new("."));
+
+  parse_obj(&source, &path.display().to_string(), |name| {
+    let mtl_source = fs::read_to_string(base.join(name))?;
+    parse_mtl(&mtl_source, name, base)
+  })
+}
+
+fn parse_error(file: &str, line: usize, message: impl Into<String>) -> RaytracerError {
+  RaytracerError::ParseError {
+    file: file.to_string(),
+    line,
+    message: message.into(),
+  }
+}
+
+fn parse_floats(file: &str, line: usize, args: &[&str], min: usize, max: usize) -> Result<Vec<f64>, RaytracerError> {
+  if args.len() < min || args.len() > max {
+    return Err(parse_error(file, line, format!("expected {min} to {max} numbers, found {}", args.len())));
+  }
+  args
+    .iter()
+    .map(|arg| arg.parse::<f64>().map_err(|_| parse_error(file, line, format!("invalid number '{arg}'"))))
+    .collect()
+}
+
+fn parse_colour(file: &str, line: usize, args: &[&str]) -> Result<Colour, RaytracerError> {
+  let fs = parse_floats(file, line, args, 1, 3)?;
+  // a single value is shorthand for grey
+  Ok(match fs[..] {
+    [x] => Colour::new(x, x, x),
+    [r, g, b] => Colour::new(r, g, b),
+    _ => return Err(parse_error(file, line, "expected 1 or 3 colour components")),
+  })
+}
+
+/// Produces the zero-based index of a one-based (or negative, counting back
+/// from the end) obj index into a buffer of count elements
+fn resolve_index(index: &str, count: usize) -> Result<usize, String> {
+  let i = index.parse::<i64>().map_err(|_| format!("invalid index '{index}'"))?;
+  let resolved = if i > 0 { i - 1 } else { count as i64 + i };
+  if i == 0 || resolved < 0 || resolved >= count as i64 {
+    Err(format!("index {i} out of range for {count} elements"))
+  } else {
+    Ok(resolved as usize)
+  }
+}
+
+// position, texcoord and normal indices of one face corner
+type VertexRef = (usize, Option<usize>, Option<usize>);
+
+struct Face {
+  corners: Vec<VertexRef>,
+  material: Arc<dyn Material>,
+}
+
+/// Produces the meshes described by obj source, one per group, calling
+/// load_mtl with the name of each referenced material library
+pub fn parse_obj(
+  source: &str,
+  file: &str,
+  mut load_mtl: impl FnMut(&str) -> Result<MaterialLibrary, RaytracerError>,
+) -> Result<Bvh, RaytracerError> {
+  let mut positions: Vec<Point3> = Vec::new();
+  let mut normals: Vec<Vector3> = Vec::new();
+  let mut texcoords: Vec<(f64, f64)> = Vec::new();
+  let mut library = MaterialLibrary::new();
+  let mut material: Arc<dyn Material> = Arc::new(Lambertian::new(Colour::new(0.8, 0.8, 0.8)));
+
+  // faces of each group, in the order groups first appear
+  let mut groups: Vec<(String, Vec<Face>)> = vec![(String::new(), Vec::new())];
+  let mut group = 0;
+
+  for (index, text) in source.lines().enumerate() {
+    let line = index + 1;
+    let text = text.split('#').next().unwrap_or("").trim();
+    let mut words = text.split_whitespace();
+    let Some(keyword) = words.next() else {
+      continue;
+    };
+    let args: Vec<&str> = words.collect();
+
+    match keyword {
+      "v" => {
+        let v = parse_floats(file, line, &args, 3, 4)?;
+        positions.push(Point3::new(v[0], v[1], v[2]));
+      },
+      "vn" => {
+        let n = parse_floats(file, line, &args, 3, 3)?;
+        normals.push(Vector3::new(n[0], n[1], n[2]));
+      },
+      "vt" => {
+        let t = parse_floats(file, line, &args, 1, 3)?;
+        texcoords.push((t[0], t.get(1).copied().unwrap_or(0.0)));
+      },
+      "f" => {
+        if args.len() < 3 {
+          return Err(parse_error(file, line, "face needs at least 3 vertices"));
+        }
+        let corners = args
+          .iter()
+          .map(|corner| {
+            let mut parts = corner.split('/');
+            let p = resolve_index(parts.next().unwrap_or(""), positions.len())?;
+            let t = match parts.next() {
+              Some("") | None => None,
+              Some(t) => Some(resolve_index(t, texcoords.len())?),
+            };
+            let n = match parts.next() {
+              Some("") | None => None,
+              Some(n) => Some(resolve_index(n, normals.len())?),
+            };
+            Ok((p, t, n))
+          })
+          .collect::<Result<Vec<VertexRef>, String>>()
+          .map_err(|message| parse_error(file, line, message))?;
+        groups[group].1.push(Face { corners, material: Arc::clone(&material), });
+      },
+      "g" | "o" => {
+        let name = args.join(" ");
+        group = match groups.iter().position(|(existing, _)| *existing == name) {
+          Some(existing) => existing,
+          None => {
+            groups.push((name, Vec::new()));
+            groups.len() - 1
+          },
+        };
+      },
+      "usemtl" => {
+        let name = args.join(" ");
+        material = library
+          .get(&name)
+          .cloned()
+          .ok_or_else(|| parse_error(file, line, format!("unknown material '{name}'")))?;
+      },
+      "mtllib" => {
+        for name in args {
+          let mtl = load_mtl(name)
+            .map_err(|error| parse_error(file, line, format!("unable to load material library '{name}': {error}")))?;
+          library.extend(mtl);
+        }
+      },
+      // smoothing groups, lines, points, curves and anything else unsupported
+      // have no effect on the render
+      _ => (),
+    }
+  }
+
+  let meshes: VecOfHittable = groups
+    .into_iter()
+    .filter(|(_, faces)| !faces.is_empty())
+    .map(|(_, faces)| {
+      build_mesh(&faces, &positions, &normals, &texcoords).map(|mesh| Box::new(mesh) as Box<dyn Hittable>)
+    })
+    .collect::<Result<_, _>>()?;
+
+  Ok(Bvh::new(meshes))
+}
+
+/// Produces a mesh with one vertex per distinct corner of the faces, fan
+/// triangulating any polygons
+fn build_mesh(
+  faces: &[Face],
+  positions: &[Point3],
+  normals: &[Vector3],
+  texcoords: &[(f64, f64)],
+) -> Result<TriangleMesh, RaytracerError> {
+  // attributes are only kept when every corner in the group has them
+  let corners = || faces.iter().flat_map(|face| face.corners.iter());
+  let has_normals = corners().all(|(_, _, n)| n.is_some());
+  let has_texcoords = corners().all(|(_, t, _)| t.is_some());
+
+  let mut buffers = MeshBuffers::default();
+  let mut vertices: HashMap<VertexRef, usize> = HashMap::new();
+  let mut face_materials: Vec<Arc<dyn Material>> = Vec::new();
+
+  for face in faces {
+    let indices: Vec<usize> = face
+      .corners
+      .iter()
+      .map(|&(p, t, n)| {
+        let key = (p, t.filter(|_| has_texcoords), n.filter(|_| has_normals));
+        *vertices.entry(key).or_insert_with(|| {
+          buffers.positions.push(positions[p]);
+          if let Some(t) = key.1 {
+            buffers.texcoords.push(texcoords[t]);
+          }
+          if let Some(n) = key.2 {
+            buffers.normals.push(normals[n]);
+          }
+          buffers.positions.len() - 1
+        })
+      })
+      .collect();
+
+    for k in 1..indices.len() - 1 {
+      buffers.indices.push([indices[0], indices[k], indices[k + 1]]);
+      face_materials.push(Arc::clone(&face.material));
+    }
+  }
+
+  let shared = face_materials.windows(2).all(|pair| Arc::ptr_eq(&pair[0], &pair[1]));
+  let material = if shared {
+    MeshMaterial::PerMesh(Arc::clone(&face_materials[0]))
+  } else {
+    MeshMaterial::PerFace(face_materials)
+  };
+
+  TriangleMesh::new(buffers, material)
+}
+
+struct MtlEntry {
+  name: String,
+  kd: Colour,
+  ks: Colour,
+  ke: Colour,
+  ns: f64,
+  ni: f64,
+  d: f64,
+  illum: u32,
+  map_kd: Option<Arc<dyn Texture>>,
+}
+
+impl MtlEntry {
+  fn new(name: String) -> Self {
+    Self {
+      name,
+      kd: Colour::new(0.8, 0.8, 0.8),
+      ks: Colour::default(),
+      ke: Colour::default(),
+      ns: 0.0,
+      ni: 1.5,
+      d: 1.0,
+      illum: 2,
+      map_kd: None,
+    }
+  }
+
+  /// Produces the closest material we support; emissive surfaces become
+  /// lights, transparent ones glass, mirror illumination models metal, and
+  /// everything else diffuse
+  fn to_material(&self) -> Arc<dyn Material> {
+    let transparent = self.d < 1.0 || matches!(self.illum, 4 | 6 | 7 | 9);
+    let reflective = matches!(self.illum, 3 | 5 | 8);
+
+    if !near_zero(self.ke) {
+      Arc::new(DiffuseLight::new(self.ke))
+    } else if transparent {
+      Arc::new(Dielectric::new(self.ni))
+    } else if reflective {
+      // blinn-phong exponent to an approximate roughness
+      let fuzz = (2.0 / (self.ns.max(0.0) + 2.0)).sqrt();
+      Arc::new(Metal::new(self.ks, fuzz))
+    } else if let Some(ref texture) = self.map_kd {
+      Arc::new(Lambertian::from_texture(Arc::clone(texture)))
+    } else {
+      Arc::new(Lambertian::new(self.kd))
+    }
+  }
+}
+
+/// Produces the materials described by mtl source; texture maps are loaded
+/// relative to base
+pub fn parse_mtl(source: &str, file: &str, base: &Path) -> Result<MaterialLibrary, RaytracerError> {
+  let mut entries: Vec<MtlEntry> = Vec::new();
+
+  for (index, text) in source.lines().enumerate() {
+    let line = index + 1;
+    let text = text.split('#').next().unwrap_or("").trim();
+    let mut words = text.split_whitespace();
+    let Some(keyword) = words.next() else {
+      continue;
+    };
+    let args: Vec<&str> = words.collect();
+
+    if keyword == "newmtl" {
+      entries.push(MtlEntry::new(args.join(" ")));
+      continue;
+    }
+    let Some(entry) = entries.last_mut() else {
+      return Err(parse_error(file, line, format!("'{keyword}' before any newmtl")));
+    };
+
+    match keyword {
+      "Kd" => entry.kd = parse_colour(file, line, &args)?,
+      "Ks" => entry.ks = parse_colour(file, line, &args)?,
+      "Ke" => entry.ke = parse_colour(file, line, &args)?,
+      "Ns" => entry.ns = parse_floats(file, line, &args, 1, 1)?[0],
+      "Ni" => entry.ni = parse_floats(file, line, &args, 1, 1)?[0],
+      "d" => entry.d = parse_floats(file, line, &args, 1, 1)?[0],
+      "Tr" => entry.d = 1.0 - parse_floats(file, line, &args, 1, 1)?[0],
+      "illum" => {
+        entry.illum = args
+          .first()
+          .and_then(|arg| arg.parse().ok())
+          .ok_or_else(|| parse_error(file, line, "expected an illumination model number"))?;
+      },
+      "map_Kd" => {
+        // options precede the file name, which comes last
+        let name = args.last().ok_or_else(|| parse_error(file, line, "expected a texture file"))?;
+        let texture = load_image_texture(base.join(name))
+          .map_err(|_| parse_error(file, line, format!("unable to load texture '{name}'")))?;
+        entry.map_kd = Some(Arc::new(texture));
+      },
+      // ambient terms, the remaining maps and pbr extensions have no
+      // equivalent here
+      _ => (),
+    }
+  }
+
+  Ok(entries
+    .iter()
+    .map(|entry| (entry.name.clone(), entry.to_material()))
+    .collect())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use rstest::*;
+
+  use crate::testing::hit;
+
+  fn no_mtl(name: &str) -> Result<MaterialLibrary, RaytracerError> {
+    Err(parse_error("test.obj", 0, format!("no library '{name}'")))
+  }
+
+  fn error_line(error: RaytracerError) -> usize {
+    match error {
+      RaytracerError::ParseError { line, .. } => line,
+      other => panic!("expected a parse error, found {other}"),
+    }
+  }
+
+  mod obj {
+    use super::*;
+
+    const QUAD: &str = "
+      # unit quad at z = -1
+      v -1 -1 -1
+      v 1 -1 -1
+      v 1 1 -1
+      v -1 1 -1
+      vt 0 0
+      vt 1 0
+      vt 1 1
+      vt 0 1
+      vn 0 0 1
+      f 1/1/1 2/2/1 3/3/1 4/4/1
+    ";
+
+    #[rstest]
+    fn quad() {
+      let bvh = parse_obj(QUAD, "test.obj", no_mtl).unwrap();
+      let forward = Vector3::new(0.0, 0.0, -1.0);
+
+      let record = hit(&bvh, Point3::new(0.5, 0.5, 0.0), forward).unwrap();
+      assert!((record.d - 1.0).abs() < 1e-12);
+      assert!(record.front_face);
+      assert!((record.u - 0.75).abs() < 1e-12);
+      assert!((record.v - 0.75).abs() < 1e-12);
+      assert!(hit(&bvh, Point3::new(-0.5, -0.5, 0.0), forward).is_some());
+      assert!(hit(&bvh, Point3::new(1.5, 0.0, 0.0), forward).is_none());
+    }
+
+    #[rstest]
+    fn negative_indices_and_groups() {
+      let source = "
+        g first
+        v 0 0 -1
+        v 1 0 -1
+        v 0 1 -1
+        f -3 -2 -1
+        g second
+        v 0 0 -2
+        v -1 0 -2
+        v 0 -1 -2
+        f -3//  -2 -1
+      ";
+      let bvh = parse_obj(source, "test.obj", no_mtl).unwrap();
+      let forward = Vector3::new(0.0, 0.0, -1.0);
+      let first = hit(&bvh, Point3::new(0.2, 0.2, 0.0), forward).unwrap();
+      let second = hit(&bvh, Point3::new(-0.2, -0.2, 0.0), forward).unwrap();
+      assert!((first.d - 1.0).abs() < 1e-12);
+      assert!((second.d - 2.0).abs() < 1e-12);
+    }
+
+    #[rstest]
+    fn materials() {
+      let source = "
+        mtllib lights.mtl
+        v -1 -1 -1
+        v 1 -1 -1
+        v 0 1 -1
+        usemtl lamp
+        f 1 2 3
+      ";
+      let load = |name: &str| {
+        assert_eq!(name, "lights.mtl");
+        parse_mtl("newmtl lamp\nKe 4 4 4", name, Path::new("."))
+      };
+      let bvh = parse_obj(source, "test.obj", load).unwrap();
+      let record = hit(&bvh, Point3::default(), Vector3::new(0.0, 0.0, -1.0)).unwrap();
+      let material = record.material.unwrap();
+      assert_eq!(material.emitted(0.0, 0.0, record.position), Colour::new(4.0, 4.0, 4.0));
+    }
+
+    #[rstest]
+    fn unsupported_statements() {
+      let source = format!("vp 0.5 0.5\ncstype bspline\nmg 1 0.5\n{QUAD}s off\nl 1 2\n");
+      let bvh = parse_obj(&source, "test.obj", no_mtl).unwrap();
+      assert!(hit(&bvh, Point3::new(0.5, 0.5, 0.0), Vector3::new(0.0, 0.0, -1.0)).is_some());
+    }
+
+    #[rstest]
+    fn mtllib_error() {
+      let error = parse_obj("\nmtllib nowhere.mtl\n", "test.obj", no_mtl).err().unwrap();
+      assert!(error.to_string().contains("nowhere.mtl"), "{error}");
+      assert_eq!(error_line(error), 2);
+    }
+
+    #[rstest]
+    #[case("v 0 0\n", 1)]
+    #[case("v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 4\n", 4)]
+    #[case("v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2\n", 4)]
+    #[case("v 0 0 0\n\nvn 0 x 1\n", 3)]
+    #[case("v 0 0 0\nv 1 0 0\nv 0 1 0\nf 0 1 2\n", 4)]
+    #[case("v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1/2 2/1 3/1\n", 4)]
+    #[case("v 0 0 0\nusemtl missing\n", 2)]
+    #[case("mtllib nowhere.mtl\n", 1)]
+    #[case("v 0 0 0\nmtllib lights.mtl nowhere.mtl\n", 2)]
+    fn malformed(#[case] source: &str, #[case] expected_line: usize) {
+      let error = parse_obj(source, "test.obj", no_mtl).err().unwrap();
+      assert_eq!(error_line(error), expected_line);
+    }
+  }
+
+  mod mtl {
+    use super::*;
+
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    fn attenuation(material: &Arc<dyn Material>) -> Colour {
+      let ray = Ray::new(Point3::new(0.0, 1.0, 0.0), Vector3::new(0.0, -1.0, 0.0));
+      let record = HitRecord::new(Point3::default(), Vector3::new(0.0, 1.0, 0.0), 1.0);
+      let mut rng = ChaCha8Rng::seed_from_u64(4);
+      material.scatter(&mut rng, &ray, &record).map_or(Colour::default(), |srec| srec.attenuation)
+    }
+
+    #[rstest]
+    fn mapping() {
+      let source = "
+        newmtl matte
+        Kd 0.5 0.25 0.125
+
+        newmtl mirror
+        illum 3
+        Ks 0.9 0.9 0.9
+        Ns 1000
+
+        newmtl glass
+        d 0.2
+        Ni 1.33
+
+        newmtl lamp
+        Kd 1 1 1
+        Ke 10 9 8
+      ";
+      let library = parse_mtl(source, "test.mtl", Path::new(".")).unwrap();
+      assert_eq!(library.len(), 4);
+      assert_eq!(attenuation(&library["matte"]), Colour::new(0.5, 0.25, 0.125));
+      assert_eq!(attenuation(&library["mirror"]), Colour::new(0.9, 0.9, 0.9));
+      assert_eq!(attenuation(&library["glass"]), Colour::new(1.0, 1.0, 1.0));
+      let point = Point3::default();
+      assert_eq!(library["lamp"].emitted(0.0, 0.0, point), Colour::new(10.0, 9.0, 8.0));
+      assert_eq!(library["matte"].emitted(0.0, 0.0, point), Colour::default());
+    }
+
+    #[rstest]
+    fn unsupported_statements() {
+      let source = "
+        newmtl rough
+        Kd 0.5 0.5 0.5
+        Pr 0.8
+        Pm 0.1
+        Ps 0.2
+        norm normal.png
+        map_Bump -bm 0.5 bump.png
+        map_Ke glow.png
+      ";
+      let library = parse_mtl(source, "test.mtl", Path::new(".")).unwrap();
+      assert_eq!(attenuation(&library["rough"]), Colour::new(0.5, 0.5, 0.5));
+    }
+
+    #[rstest]
+    #[case("Kd 1 1 1\n", 1)]
+    #[case("newmtl a\nKd 1 x 1\n", 2)]
+    #[case("newmtl a\n\nillum\n", 3)]
+    #[case("newmtl a\nmap_Kd missing.png\n", 2)]
+    fn malformed(#[case] source: &str, #[case] expected_line: usize) {
+      let error = parse_mtl(source, "test.mtl", Path::new(".")).err().unwrap();
+      assert_eq!(error_line(error), expected_line);
+    }
+  }
+}
@@ -0,0 +1,80 @@
+
+use lib_raytracer::prelude::*;
+
+use exr::prelude::{f16, Image, SpecificChannels, Vec2, WritableImage};
+
+use std::io::{Cursor, Write};
+
+/// OpenEXR encoder writing linear RGBA in the output space, alpha always 1,
+/// at half or full float precision
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum Exr {
+  #[default]
+  Half,
+  Float,
+}
+
+impl ImageEncoder for Exr {
+  fn extension(&self) -> &'static str {
+    "exr"
+  }
+
+  fn encode(&self, framebuffer: &Framebuffer, writer: &mut dyn Write) -> Result<(), RaytracerError> {
+    let size = (framebuffer.width(), framebuffer.height());
+    let pixels = framebuffer.to_output();
+    let rgba = |Vec2(i, j): Vec2<usize>| {
+      let colour = pixels[j * framebuffer.width() + i];
+      (colour.x as f32, colour.y as f32, colour.z as f32, 1.0_f32)
+    };
+
+    // exr seeks back to write its offset tables, so it is written to memory first
+    let mut bytes = Cursor::new(Vec::new());
+    let result = match self {
+      Self::Half => {
+        let channels = SpecificChannels::rgba(|position| {
+          let (r, g, b, a) = rgba(position);
+          (f16::from_f32(r), f16::from_f32(g), f16::from_f32(b), f16::from_f32(a))
+        });
+        Image::from_channels(size, channels).write().to_buffered(&mut bytes)
+      },
+      Self::Float => {
+        Image::from_channels(size, SpecificChannels::rgba(rgba)).write().to_buffered(&mut bytes)
+      },
+    };
+    result.map_err(|error| RaytracerError::ImageEncodeError { message: error.to_string(), })?;
+    writer.write_all(bytes.get_ref())?;
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use rstest::*;
+
+  #[rstest]
+  #[case(Exr::Half)]
+  #[case(Exr::Float)]
+  fn encode(#[case] exr: Exr) {
+    let pixels = vec![Colour::new(0.5, 2.0, 16.0), Colour::new(0.0, 0.25, 1024.0)];
+    let framebuffer = Framebuffer::from_pixels(2, 1, pixels).unwrap();
+    let bytes = exr.encode_to_vec(&framebuffer).unwrap();
+    let image = image::load_from_memory(&bytes).unwrap().into_rgba32f();
+    assert_eq!(image.dimensions(), (2, 1));
+    // values above 1 survive, and these are exact even at half precision
+    assert_eq!(image.into_raw(), vec![0.5, 2.0, 16.0, 1.0, 0.0, 0.25, 1024.0, 1.0]);
+  }
+
+  #[rstest]
+  fn encode_precision() {
+    let framebuffer = Framebuffer::from_pixels(1, 1, vec![Colour::new(0.1, 0.2, 0.3)]).unwrap();
+    let decode = |exr: Exr| {
+      let bytes = exr.encode_to_vec(&framebuffer).unwrap();
+      image::load_from_memory(&bytes).unwrap().into_rgb32f().into_raw()
+    };
+    assert_eq!(decode(Exr::Float), vec![0.1, 0.2, 0.3]);
+    let half = decode(Exr::Half);
+    assert_ne!(half, vec![0.1, 0.2, 0.3]);
+    assert!(half.iter().zip([0.1, 0.2, 0.3]).all(|(a, b)| (a - b).abs() < 1e-3));
+  }
+}
@@ -0,0 +1,61 @@
+
+use lib_raytracer::prelude::*;
+
+use std::io::Write;
+
+/// Portable FloatMap encoder writing linear colour in the output space as
+/// little-endian 32-bit floats, bottom row first as the format requires
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Pfm;
+
+impl ImageEncoder for Pfm {
+  fn extension(&self) -> &'static str {
+    "pfm"
+  }
+
+  fn encode(&self, framebuffer: &Framebuffer, writer: &mut dyn Write) -> Result<(), RaytracerError> {
+    // a negative scale marks the data as little-endian
+    write!(writer, "PF\n{} {}\n-1.0\n", framebuffer.width(), framebuffer.height())?;
+    let pixels = framebuffer.to_output();
+    let mut bytes = Vec::with_capacity(pixels.len() * 12);
+    for row in pixels.chunks(framebuffer.width()).rev() {
+      for colour in row {
+        for value in [colour.x, colour.y, colour.z] {
+          bytes.extend_from_slice(&(value as f32).to_le_bytes());
+        }
+      }
+    }
+    writer.write_all(&bytes)?;
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use rstest::*;
+
+  #[rstest]
+  fn encode() {
+    let pixels = vec![Colour::new(0.5, 2.0, 16.0), Colour::new(0.1, 0.0, 1000.0)];
+    let framebuffer = Framebuffer::from_pixels(1, 2, pixels).unwrap();
+    let bytes = Pfm.encode_to_vec(&framebuffer).unwrap();
+    let header = b"PF\n1 2\n-1.0\n";
+    assert_eq!(&bytes[..header.len()], header);
+    let values = bytes[header.len()..]
+      .chunks(4)
+      .map(|chunk| f32::from_le_bytes(chunk.try_into().unwrap()))
+      .collect::<Vec<_>>();
+    assert_eq!(values, vec![0.1, 0.0, 1000.0, 0.5, 2.0, 16.0]);
+  }
+
+  #[rstest]
+  fn encode_output_space() {
+    let mut framebuffer = Framebuffer::from_pixels(1, 1, vec![Colour::new(1.0, 0.0, 0.0)]).unwrap();
+    framebuffer.set_output_space(ColourSpace::Rec2020);
+    let bytes = Pfm.encode_to_vec(&framebuffer).unwrap();
+    let expected = ColourSpace::Srgb.convert(Colour::new(1.0, 0.0, 0.0), ColourSpace::Rec2020);
+    let red = f32::from_le_bytes(bytes[bytes.len() - 12..bytes.len() - 8].try_into().unwrap());
+    assert_eq!(red, expected.x as f32);
+  }
+}
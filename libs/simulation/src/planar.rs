@@ -0,0 +1,450 @@
+
+use lib_raytracer::prelude::*;
+use lib_raytracer::aabb;
+
+use rand::{Rng, RngCore};
+
+use std::f64::consts::PI;
+use std::sync::Arc;
+
+// minimum thickness of a flat primitive's bounding box along any axis
+const BBOX_PADDING: f64 = 1e-4;
+
+/// Produces the distance along ray to the plane through point with unit
+/// normal, if the ray is not parallel to it and the hit lies within ray_i
+fn intersect_plane(point: Point3, normal: Vector3, ray: &Ray, ray_i: Interval) -> Option<f64> {
+  let denom = dot(normal, ray.direction());
+  if denom.abs() < 1e-12 {
+    return None;
+  }
+
+  let d = dot(normal, point - ray.position()) / denom;
+  if ray_i.surrounds(d) { Some(d) } else { None }
+}
+
+fn fill_record(record: &mut HitRecord, ray: &Ray, d: f64, normal: Vector3, uv: (f64, f64), material: &Arc<dyn Material>) {
+  record.d = d;
+  record.position = ray.at(d);
+  record.set_face_normal(ray, normal);
+  (record.u, record.v) = uv;
+  record.material = Some(Arc::clone(material));
+}
+
+/// Produces the solid angle density of ray when points are picked uniformly
+/// over area of a flat primitive, if hittable is hit at all
+fn area_pdf(hittable: &impl Hittable, area: f64, ray: &Ray) -> f64 {
+  let mut record = HitRecord::default();
+  if !hittable.hit(ray, Interval::new(0.001, f64::INFINITY), &mut record) {
+    return 0.0;
+  }
+
+  let length = ray.direction().length();
+  let distance_squared = record.d * record.d * length * length;
+  let cos = dot(ray.direction(), record.normal).abs() / length;
+  distance_squared / (cos * area)
+}
+
+/// Parallelogram covering corner + a*u + b*v for a and b in [0, 1]; it faces
+/// the side u x v points to, and (a, b) are its uvs
+#[derive(Clone)]
+pub struct Quad {
+  corner: Point3,
+  u: Vector3,
+  v: Vector3,
+  normal: Vector3,
+  // maps a point on the plane, relative to corner, to (a, b)
+  w: Vector3,
+  material: Arc<dyn Material>,
+}
+
+impl Quad {
+  /// Produces the quad, unless u and v are parallel or either is zero,
+  /// since they would span no area
+  pub fn new(corner: Point3, u: Vector3, v: Vector3, material: Arc<dyn Material>) -> Result<Self, RaytracerError> {
+    let n = cross(u, v);
+    if n.length_squared() == 0.0 {
+      return Err(RaytracerError::PlanarError);
+    }
+    let w = n / n.length_squared();
+    Ok(Self { corner, u, v, normal: n.to_unit(), w, material, })
+  }
+}
+
+impl Hittable for Quad {
+  fn hit(&self, ray: &Ray, ray_i: Interval, record: &mut HitRecord) -> bool {
+    let Some(d) = intersect_plane(self.corner, self.normal, ray, ray_i) else {
+      return false;
+    };
+
+    let planar = ray.at(d) - self.corner;
+    let a = dot(self.w, cross(planar, self.v));
+    let b = dot(self.w, cross(self.u, planar));
+    if !(0.0..=1.0).contains(&a) || !(0.0..=1.0).contains(&b) {
+      return false;
+    }
+
+    fill_record(record, ray, d, self.normal, (a, b), &self.material);
+    true
+  }
+
+  fn bounding_box(&self) -> Aabb {
+    let diagonal = Aabb::from_points(self.corner, self.corner + self.u + self.v);
+    let other = Aabb::from_points(self.corner + self.u, self.corner + self.v);
+    Aabb::surrounding(diagonal, other).padded(BBOX_PADDING)
+  }
+
+  fn pdf_value(&self, ray: &Ray) -> f64 {
+    area_pdf(self, cross(self.u, self.v).length(), ray)
+  }
+
+  fn random(&self, rng: &mut dyn RngCore, origin: Point3, _: f64) -> Vector3 {
+    let point = self.corner + rng.gen::<f64>()*self.u + rng.gen::<f64>()*self.v;
+    point - origin
+  }
+}
+
+/// Produces the six quads of the box with a and b as opposite corners, all
+/// facing outwards, unless the box is flat along some axis
+pub fn make_box(a: Point3, b: Point3, material: Arc<dyn Material>) -> Result<VecOfHittable, RaytracerError> {
+  let min = Point3::new(a.x.min(b.x), a.y.min(b.y), a.z.min(b.z));
+  let max = Point3::new(a.x.max(b.x), a.y.max(b.y), a.z.max(b.z));
+
+  let dx = Vector3::new(max.x - min.x, 0.0, 0.0);
+  let dy = Vector3::new(0.0, max.y - min.y, 0.0);
+  let dz = Vector3::new(0.0, 0.0, max.z - min.z);
+
+  let sides = [
+    (Point3::new(min.x, min.y, max.z), dx, dy),  // front
+    (Point3::new(max.x, min.y, max.z), -dz, dy), // right
+    (Point3::new(max.x, min.y, min.z), -dx, dy), // back
+    (Point3::new(min.x, min.y, min.z), dz, dy),  // left
+    (Point3::new(min.x, max.y, max.z), dx, -dz), // top
+    (Point3::new(min.x, min.y, min.z), dx, dz),  // bottom
+  ];
+  sides
+    .into_iter()
+    .map(|(corner, u, v)| Ok(Box::new(Quad::new(corner, u, v, Arc::clone(&material))?) as Box<dyn Hittable>))
+    .collect()
+}
+
+/// Flat disk facing along normal; u is the angle around the center as a
+/// fraction of a turn, v the distance from it as a fraction of radius
+#[derive(Clone)]
+pub struct Disk {
+  center: Point3,
+  normal: Vector3,
+  radius: f64,
+  tangent: Vector3,
+  bitangent: Vector3,
+  material: Arc<dyn Material>,
+}
+
+impl Disk {
+  /// Produces the disk, unless normal or radius is zero
+  pub fn new(center: Point3, normal: Vector3, radius: f64, material: Arc<dyn Material>) -> Result<Self, RaytracerError> {
+    if normal.length_squared() == 0.0 || radius == 0.0 {
+      return Err(RaytracerError::PlanarError);
+    }
+    let normal = normal.to_unit();
+    let (tangent, bitangent) = tangents(normal);
+    Ok(Self { center, normal, radius: radius.abs(), tangent, bitangent, material, })
+  }
+}
+
+impl Hittable for Disk {
+  fn hit(&self, ray: &Ray, ray_i: Interval, record: &mut HitRecord) -> bool {
+    let Some(d) = intersect_plane(self.center, self.normal, ray, ray_i) else {
+      return false;
+    };
+
+    let planar = ray.at(d) - self.center;
+    if planar.length_squared() > self.radius * self.radius {
+      return false;
+    }
+
+    let (x, y) = (dot(planar, self.tangent), dot(planar, self.bitangent));
+    let u = (y.atan2(x) + PI) / (2.0 * PI);
+    let v = planar.length() / self.radius;
+    fill_record(record, ray, d, self.normal, (u, v), &self.material);
+    true
+  }
+
+  fn bounding_box(&self) -> Aabb {
+    // a disk reaches radius * sin(angle between normal and axis) along each axis
+    let n = self.normal;
+    let extent = self.radius * Vector3::new(
+      (1.0 - n.x*n.x).max(0.0).sqrt(),
+      (1.0 - n.y*n.y).max(0.0).sqrt(),
+      (1.0 - n.z*n.z).max(0.0).sqrt(),
+    );
+    Aabb::from_points(self.center - extent, self.center + extent).padded(BBOX_PADDING)
+  }
+
+  fn pdf_value(&self, ray: &Ray) -> f64 {
+    area_pdf(self, PI * self.radius * self.radius, ray)
+  }
+
+  fn random(&self, mut rng: &mut dyn RngCore, origin: Point3, _: f64) -> Vector3 {
+    let p = Vector3::random_in_unit_disk(&mut rng);
+    let point = self.center + self.radius * (p.x*self.tangent + p.y*self.bitangent);
+    point - origin
+  }
+}
+
+/// Infinite plane through point facing along normal; uvs are distances
+/// along two tangents from point, so they are unbounded. Its bounding box is
+/// infinite, which a Bvh handles by testing it outside the tree
+#[derive(Clone)]
+pub struct Plane {
+  point: Point3,
+  normal: Vector3,
+  tangent: Vector3,
+  bitangent: Vector3,
+  material: Arc<dyn Material>,
+}
+
+impl Plane {
+  /// Produces the plane, unless normal is zero
+  pub fn new(point: Point3, normal: Vector3, material: Arc<dyn Material>) -> Result<Self, RaytracerError> {
+    if normal.length_squared() == 0.0 {
+      return Err(RaytracerError::PlanarError);
+    }
+    let normal = normal.to_unit();
+    let (tangent, bitangent) = tangents(normal);
+    Ok(Self { point, normal, tangent, bitangent, material, })
+  }
+}
+
+impl Hittable for Plane {
+  fn hit(&self, ray: &Ray, ray_i: Interval, record: &mut HitRecord) -> bool {
+    let Some(d) = intersect_plane(self.point, self.normal, ray, ray_i) else {
+      return false;
+    };
+
+    let planar = ray.at(d) - self.point;
+    let uv = (dot(planar, self.tangent), dot(planar, self.bitangent));
+    fill_record(record, ray, d, self.normal, uv, &self.material);
+    true
+  }
+
+  fn bounding_box(&self) -> Aabb {
+    // only an axis aligned plane is bounded, and then only along its normal
+    let axis = (0..3).find(|&n| (self.normal[n].abs() - 1.0).abs() < 1e-12);
+    match axis {
+      Some(n) => {
+        let mut bbox = aabb::UNIVERSE;
+        let slab = Interval::new(self.point[n], self.point[n]).expand(BBOX_PADDING);
+        match n {
+          0 => bbox.x = slab,
+          1 => bbox.y = slab,
+          _ => bbox.z = slab,
+        }
+        bbox
+      },
+      None => aabb::UNIVERSE,
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use rstest::*;
+
+  use crate::testing::{grey, hit, assert_pdf_integrates};
+
+  mod quad {
+    use super::*;
+
+    fn unit_quad() -> Quad {
+      Quad::new(Point3::new(0.0, 0.0, -1.0), Vector3::new(2.0, 0.0, 0.0), Vector3::new(1.0, 1.0, 0.0), grey()).unwrap()
+    }
+
+    #[rstest]
+    #[case(Point3::new(1.0, 0.5, 0.0), true)]
+    #[case(Point3::new(2.9, 0.9, 0.0), true)]
+    #[case(Point3::new(0.25, 0.75, 0.0), false)]
+    #[case(Point3::new(2.5, 0.25, 0.0), false)]
+    #[case(Point3::new(1.0, 1.5, 0.0), false)]
+    #[case(Point3::new(1.0, -0.5, 0.0), false)]
+    fn inside(#[case] origin: Point3, #[case] expected: bool) {
+      assert_eq!(hit(&unit_quad(), origin, Vector3::new(0.0, 0.0, -1.0)).is_some(), expected);
+    }
+
+    #[rstest]
+    #[case(Point3::new(0.0, 0.0, 0.0), (0.0, 0.0))]
+    #[case(Point3::new(2.0, 0.0, 0.0), (1.0, 0.0))]
+    #[case(Point3::new(1.0, 1.0, 0.0), (0.0, 1.0))]
+    #[case(Point3::new(3.0, 1.0, 0.0), (1.0, 1.0))]
+    fn uvs(#[case] origin: Point3, #[case] expected: (f64, f64)) {
+      let record = hit(&unit_quad(), origin, Vector3::new(0.0, 0.0, -1.0)).unwrap();
+      assert!((record.u - expected.0).abs() < 1e-12 && (record.v - expected.1).abs() < 1e-12);
+      assert_eq!(record.d, 1.0);
+    }
+
+    #[rstest]
+    #[case(Point3::new(1.0, 0.5, 0.0), Vector3::new(0.0, 0.0, -1.0), true)]
+    #[case(Point3::new(1.0, 0.5, -2.0), Vector3::new(0.0, 0.0, 1.0), false)]
+    fn front_face(#[case] origin: Point3, #[case] direction: Vector3, #[case] expected: bool) {
+      let record = hit(&unit_quad(), origin, direction).unwrap();
+      assert_eq!(record.front_face, expected);
+      // the normal always faces the ray
+      assert_eq!(record.normal, -direction);
+    }
+
+    #[rstest]
+    fn parallel() {
+      assert!(hit(&unit_quad(), Point3::new(-1.0, 0.5, -1.0), Vector3::new(1.0, 0.0, 0.0)).is_none());
+    }
+
+    #[rstest]
+    #[case(Vector3::new(1.0, 0.0, 0.0), Vector3::new(-2.0, 0.0, 0.0))]
+    #[case(Vector3::new(1.0, 0.0, 0.0), Vector3::default())]
+    fn degenerate(#[case] u: Vector3, #[case] v: Vector3) {
+      assert!(Quad::new(Point3::default(), u, v, grey()).is_err());
+    }
+
+    #[rstest]
+    fn bounding_box() {
+      let bbox = unit_quad().bounding_box();
+      assert_eq!((bbox.x.min, bbox.x.max), (0.0, 3.0));
+      assert_eq!((bbox.y.min, bbox.y.max), (0.0, 1.0));
+      assert!(bbox.z.contains(-1.0) && bbox.z.size() > 0.0);
+    }
+
+    #[rstest]
+    fn pdf() {
+      assert_pdf_integrates(&unit_quad(), Point3::new(1.0, 0.5, 0.0));
+    }
+  }
+
+  mod make_box {
+    use super::*;
+
+    #[rstest]
+    fn outward() {
+      let sides = make_box(Point3::new(1.0, 2.0, 3.0), Point3::new(-1.0, -2.0, -3.0), grey()).unwrap();
+      assert_eq!(sides.len(), 6);
+
+      // from outside, a ray towards the center meets the front of exactly one side
+      let axes = [Vector3::new(1.0, 0.0, 0.0), Vector3::new(0.0, 1.0, 0.0), Vector3::new(0.0, 0.0, 1.0)];
+      for axis in axes {
+        for sign in [1.0, -1.0] {
+          let outward = sign * axis;
+          let origin = Point3::default() + 10.0 * outward;
+          let hits: Vec<_> = sides.iter().filter_map(|side| hit(side.as_ref(), origin, -outward)).collect();
+          assert_eq!(hits.len(), 2);
+          let nearest = hits.iter().min_by(|a, b| a.d.total_cmp(&b.d)).unwrap();
+          assert!(nearest.front_face, "side facing {outward:?} faces inwards");
+          assert_eq!(nearest.normal, outward);
+        }
+      }
+    }
+
+    #[rstest]
+    fn flat() {
+      assert!(make_box(Point3::new(1.0, 2.0, 3.0), Point3::new(-1.0, 2.0, -3.0), grey()).is_err());
+    }
+  }
+
+  mod disk {
+    use super::*;
+
+    fn tilted_disk() -> Disk {
+      Disk::new(Point3::new(0.0, 0.0, -2.0), Vector3::new(0.0, 0.0, 3.0), 1.0, grey()).unwrap()
+    }
+
+    #[rstest]
+    #[case(Point3::new(0.0, 0.0, 0.0), true)]
+    #[case(Point3::new(0.7, 0.7, 0.0), true)]
+    #[case(Point3::new(0.75, 0.75, 0.0), false)]
+    #[case(Point3::new(0.0, -1.1, 0.0), false)]
+    fn inside(#[case] origin: Point3, #[case] expected: bool) {
+      assert_eq!(hit(&tilted_disk(), origin, Vector3::new(0.0, 0.0, -1.0)).is_some(), expected);
+    }
+
+    #[rstest]
+    fn uvs() {
+      let record = hit(&tilted_disk(), Point3::new(0.0, 0.0, 0.0), Vector3::new(0.0, 0.0, -1.0)).unwrap();
+      assert_eq!(record.v, 0.0);
+      // opposite points are half a turn apart
+      let a = hit(&tilted_disk(), Point3::new(0.5, 0.0, 0.0), Vector3::new(0.0, 0.0, -1.0)).unwrap();
+      let b = hit(&tilted_disk(), Point3::new(-0.5, 0.0, 0.0), Vector3::new(0.0, 0.0, -1.0)).unwrap();
+      assert!((a.v - 0.5).abs() < 1e-12 && (b.v - 0.5).abs() < 1e-12);
+      assert!(((a.u - b.u).abs() - 0.5).abs() < 1e-12);
+    }
+
+    #[rstest]
+    #[case(Point3::new(0.0, 0.0, 0.0), Vector3::new(0.0, 0.0, -1.0), true)]
+    #[case(Point3::new(0.0, 0.0, -4.0), Vector3::new(0.0, 0.0, 1.0), false)]
+    fn front_face(#[case] origin: Point3, #[case] direction: Vector3, #[case] expected: bool) {
+      let record = hit(&tilted_disk(), origin, direction).unwrap();
+      assert_eq!(record.front_face, expected);
+      assert_eq!(record.normal, -direction);
+    }
+
+    #[rstest]
+    #[case(Vector3::default(), 1.0)]
+    #[case(Vector3::new(0.0, 1.0, 0.0), 0.0)]
+    fn degenerate(#[case] normal: Vector3, #[case] radius: f64) {
+      assert!(Disk::new(Point3::default(), normal, radius, grey()).is_err());
+    }
+
+    #[rstest]
+    fn bounding_box() {
+      let disk = Disk::new(Point3::default(), Vector3::new(0.0, 1.0, 0.0), 2.0, grey()).unwrap();
+      let bbox = disk.bounding_box();
+      assert_eq!((bbox.x.min, bbox.x.max), (-2.0, 2.0));
+      assert_eq!((bbox.z.min, bbox.z.max), (-2.0, 2.0));
+      assert!(bbox.y.size() > 0.0 && bbox.y.size() < 0.01);
+    }
+
+    #[rstest]
+    fn pdf() {
+      assert_pdf_integrates(&tilted_disk(), Point3::new(0.5, 0.0, 0.0));
+    }
+  }
+
+  mod plane {
+    use super::*;
+
+    #[rstest]
+    fn hit_anywhere() {
+      let plane = Plane::new(Point3::new(0.0, -1.0, 0.0), Vector3::new(0.0, 2.0, 0.0), grey()).unwrap();
+      let record = hit(&plane, Point3::new(1000.0, 0.0, -500.0), Vector3::new(0.0, -1.0, 0.0)).unwrap();
+      assert_eq!(record.d, 1.0);
+      assert!(record.front_face);
+      let record = hit(&plane, Point3::new(0.0, -2.0, 0.0), Vector3::new(0.0, 1.0, 0.0)).unwrap();
+      assert!(!record.front_face);
+      assert!(hit(&plane, Point3::default(), Vector3::new(0.0, 1.0, 0.0)).is_none());
+      assert!(hit(&plane, Point3::default(), Vector3::new(1.0, 0.0, 0.0)).is_none());
+    }
+
+    #[rstest]
+    fn degenerate() {
+      assert!(Plane::new(Point3::default(), Vector3::default(), grey()).is_err());
+    }
+
+    #[rstest]
+    #[case(Vector3::new(0.0, 1.0, 0.0), true)]
+    #[case(Vector3::new(1.0, 1.0, 0.0), false)]
+    fn bounding_box(#[case] normal: Vector3, #[case] slab: bool) {
+      let bbox = Plane::new(Point3::new(0.0, -1.0, 0.0), normal, grey()).unwrap().bounding_box();
+      assert!(!bbox.is_finite());
+      assert_eq!(bbox.y.size() < 0.01, slab);
+    }
+
+    #[rstest]
+    fn in_bvh() {
+      let objects: VecOfHittable = vec![
+        Box::new(Plane::new(Point3::new(0.0, -1.0, 0.0), Vector3::new(0.0, 1.0, 0.0), grey()).unwrap()),
+        Box::new(Quad::new(Point3::new(-1.0, -1.0, -2.0), Vector3::new(2.0, 0.0, 0.0), Vector3::new(0.0, 2.0, 0.0), grey()).unwrap()),
+      ];
+      let bvh = Bvh::new(objects);
+      let record = hit(&bvh, Point3::default(), Vector3::new(0.0, -1.0, -1.0)).unwrap();
+      assert!((record.d - 1.0).abs() < 1e-12);
+      let record = hit(&bvh, Point3::new(0.0, 0.5, 0.0), Vector3::new(0.0, -0.5, -1.0)).unwrap();
+      assert!((record.d - 2.0).abs() < 1e-12);
+    }
+  }
+}
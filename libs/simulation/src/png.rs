@@ -1,52 +1,44 @@
 
 use lib_raytracer::prelude::*;
 
-use image::{Rgba, DynamicImage};
+use image::RgbaImage;
+use image::codecs::png::PngEncoder;
 
-use std::path::Path;
+use std::io::Write;
 
-#[derive(Debug, Default)]
-pub struct Png {
-  image: Option<DynamicImage>,
-}
+/// 8-bit RGBA portable network graphics encoder, alpha always opaque
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Png;
 
-impl Png {
-  pub fn new() -> Self {
-    Self { image: None, }
+impl ImageEncoder for Png {
+  fn extension(&self) -> &'static str {
+    "png"
   }
 
-  pub fn save_to_path<P: AsRef<Path>>(self, path: P) -> Result<(), RaytracerError> {
-    if let Some(image) = self.image {
-      image.save(path).map_err(|_| RaytracerError::SceneSaveError)
-    } else {
-      Err(RaytracerError::SceneSaveError)
-    }
+  fn encode(&self, framebuffer: &Framebuffer, writer: &mut dyn Write) -> Result<(), RaytracerError> {
+    let bytes = framebuffer.to_pixels().iter().flat_map(|pixel| [pixel.r, pixel.g, pixel.b, 255]).collect();
+    let image = RgbaImage::from_raw(framebuffer.width() as u32, framebuffer.height() as u32, bytes)
+      .ok_or(RaytracerError::SceneSaveError)?;
+    image
+      .write_with_encoder(PngEncoder::new(writer))
+      .map_err(|error| RaytracerError::ImageEncodeError { message: error.to_string(), })
   }
 }
 
-impl Scene for Png {
-  fn render(&mut self, camera: &mut Camera, hittable: &mut impl Hittable) -> Result<usize, RaytracerError> {
-    let Camera { config, .. } = *camera;
-    let Config { image_width, image_height, .. } = config;
-    let max_depth = 50;
-    let mut rng = rand::thread_rng();
-
-    self.image = Some(DynamicImage::new_rgba8(image_width as u32, image_height as u32));
-    let image = self.image.as_mut().unwrap();
-
-    image
-      .as_mut_rgba8().unwrap() // safe; we create as rgba8
-      .enumerate_pixels_mut()
-      .for_each(|(i, j, pixel)| {
-        let pixel_colour = (0..camera.config.samples_per_pixel)
-          .fold(Colour::default(), |pixel_colour, _| {
-            let ray = camera.get_ray(&mut rng, i as usize, j as usize);
-            pixel_colour + camera.ray_colour(&mut rng, &ray, hittable, max_depth)
-          });
-        let pixel_colour = colour_to_pixel(&pixel_colour, camera.config.samples_per_pixel);
-        *pixel = Rgba([pixel_colour.r, pixel_colour.g, pixel_colour.b, 255]);
-      });
-
-    Ok(self.image.as_ref().unwrap().as_bytes().len())
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use rstest::*;
+
+  #[rstest]
+  fn encode() {
+    let pixels = vec![Colour::new(1.0, 0.0, 0.25), Colour::new(0.0, 1.0, 0.0)];
+    let framebuffer = Framebuffer::from_pixels(2, 1, pixels).unwrap();
+    let bytes = Png.encode_to_vec(&framebuffer).unwrap();
+    let image = image::load_from_memory(&bytes).unwrap();
+    assert_eq!(image.color(), image::ColorType::Rgba8);
+    let image = image.into_rgba8();
+    assert_eq!(image.dimensions(), (2, 1));
+    assert_eq!(image.into_raw(), vec![255, 0, 137, 255, 0, 255, 0, 255]);
   }
-}
\ No newline at end of file
+}
@@ -1,48 +1,379 @@
 
 use lib_raytracer::prelude::*;
 
-use std::fs::File;
+use std::fs;
 use std::io::Write;
 use std::path::Path;
 
-#[derive(Debug, Default)]
+fn pnm_error(file: &str, message: impl Into<String>) -> RaytracerError {
+  RaytracerError::ImageParseError {
+    file: file.to_string(),
+    message: message.into(),
+  }
+}
+
+/// Whether netpbm samples are written as decimal text (P2, P3) or raw bytes
+/// (P5, P6)
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum PnmEncoding {
+  Ascii,
+  #[default]
+  Binary,
+}
+
+/// Precision of netpbm samples; 16-bit samples are big endian with a maxval
+/// of 65535
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum BitDepth {
+  #[default]
+  Eight,
+  Sixteen,
+}
+
+impl BitDepth {
+  pub fn maxval(&self) -> u16 {
+    match self {
+      Self::Eight => 255,
+      Self::Sixteen => 65535,
+    }
+  }
+}
+
+/// Portable pixmap encoder, binary P6 unless ascii P3 is asked for
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
 pub struct Ppm {
-  bytes: Vec<u8>,
+  pub encoding: PnmEncoding,
+  pub depth: BitDepth,
 }
 
 impl Ppm {
-  pub fn new() -> Self {
-    Self { bytes: Vec::new(), }
-  }
-
-  pub fn save_to_path<P: AsRef<Path>>(self, path: P) -> Result<(), RaytracerError> {
-    let mut file = File::create(path.as_ref())?;
-    file.write_all(&self.bytes)?;
-    Ok(())
-  }
-}
-
-impl Scene for Ppm {
-  fn render(&mut self, camera: &mut Camera, hittable: &mut impl Hittable) -> Result<usize, RaytracerError> {
-    let Camera { config, .. } = *camera;
-    let Config { image_width, image_height, .. } = config;
-    let max_depth = 50;
-    let mut rng = rand::thread_rng();
-
-    // '\n' appended by writeln! is necessary for formatting...
-    writeln!(&mut self.bytes, "P3\n{} {}\n255", image_width, image_height)?;
-    for j in 0..image_height {
-      for i in 0..image_width {
-        let pixel_colour = (0..camera.config.samples_per_pixel)
-          .fold(Colour::default(), |pixel_colour, _| {
-            let ray = camera.get_ray(&mut rng, i, j);
-            pixel_colour + camera.ray_colour(&mut rng, &ray, hittable, max_depth)
-          });
-        let pixel = colour_to_pixel(&pixel_colour, camera.config.samples_per_pixel);
-        writeln!(&mut self.bytes, "{} {} {}", pixel.r, pixel.g, pixel.b)?;
+  pub fn new(encoding: PnmEncoding, depth: BitDepth) -> Self {
+    Self { encoding, depth, }
+  }
+}
+
+impl ImageEncoder for Ppm {
+  fn extension(&self) -> &'static str {
+    "ppm"
+  }
+
+  fn encode(&self, framebuffer: &Framebuffer, writer: &mut dyn Write) -> Result<(), RaytracerError> {
+    let samples = framebuffer.tone_mapped()
+      .iter()
+      .flat_map(|colour| [colour.x, colour.y, colour.z])
+      .collect::<Vec<_>>();
+    let magic = match self.encoding { PnmEncoding::Ascii => "P3", PnmEncoding::Binary => "P6" };
+    write_pnm(writer, framebuffer, magic, &samples, self.encoding, self.depth)
+  }
+}
+
+/// Portable graymap encoder writing the luminance of each pixel, for passes
+/// with a single channel; binary P5 unless ascii P2 is asked for. Luminance
+/// is weighted for sRGB (Rec. 709) primaries whatever the output space is
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Pgm {
+  pub encoding: PnmEncoding,
+  pub depth: BitDepth,
+}
+
+impl Pgm {
+  pub fn new(encoding: PnmEncoding, depth: BitDepth) -> Self {
+    Self { encoding, depth, }
+  }
+}
+
+impl ImageEncoder for Pgm {
+  fn extension(&self) -> &'static str {
+    "pgm"
+  }
+
+  fn encode(&self, framebuffer: &Framebuffer, writer: &mut dyn Write) -> Result<(), RaytracerError> {
+    let samples = framebuffer.tone_mapped().into_iter().map(luminance).collect::<Vec<_>>();
+    let magic = match self.encoding { PnmEncoding::Ascii => "P2", PnmEncoding::Binary => "P5" };
+    write_pnm(writer, framebuffer, magic, &samples, self.encoding, self.depth)
+  }
+}
+
+// samples are linear, in the framebuffer's output space
+fn write_pnm(
+  writer: &mut dyn Write,
+  framebuffer: &Framebuffer,
+  magic: &str,
+  samples: &[f64],
+  encoding: PnmEncoding,
+  depth: BitDepth,
+) -> Result<(), RaytracerError> {
+  let maxval = depth.maxval();
+  let space = framebuffer.output_space();
+  let quantise = |sample: f64| (space.encode(sample.clamp(0.0, 1.0)) * maxval as f64).round() as u16;
+
+  // '\n' appended by writeln! is necessary for formatting...
+  writeln!(writer, "{magic}\n{} {}\n{maxval}", framebuffer.width(), framebuffer.height())?;
+  let mut bytes = Vec::with_capacity(samples.len() * 4);
+  match encoding {
+    PnmEncoding::Ascii => {
+      let channels = samples.len() / (framebuffer.width() * framebuffer.height());
+      for pixel in samples.chunks(channels) {
+        let values = pixel.iter().map(|&sample| quantise(sample).to_string()).collect::<Vec<_>>();
+        writeln!(&mut bytes, "{}", values.join(" "))?;
+      }
+    },
+    PnmEncoding::Binary => {
+      for &sample in samples {
+        match depth {
+          BitDepth::Eight => bytes.push(quantise(sample) as u8),
+          BitDepth::Sixteen => bytes.extend_from_slice(&quantise(sample).to_be_bytes()),
+        }
+      }
+    },
+  }
+  writer.write_all(&bytes)?;
+  Ok(())
+}
+
+/// Produces the framebuffer of the sRGB PPM or PGM image at path
+pub fn load_pnm<P: AsRef<Path>>(path: P) -> Result<Framebuffer, RaytracerError> {
+  load_pnm_in(path, ColourSpace::Srgb)
+}
+
+/// Produces the framebuffer of the PPM or PGM image at path, encoded in
+/// colour_space
+pub fn load_pnm_in<P: AsRef<Path>>(path: P, colour_space: ColourSpace) -> Result<Framebuffer, RaytracerError> {
+  let path = path.as_ref();
+  let bytes = fs::read(path)?;
+  parse_pnm(&bytes, colour_space, &path.display().to_string())
+}
+
+/// Produces the framebuffer of a PPM (P3, P6) or PGM (P2, P5) image, ascii or
+/// binary with any maxval up to 65535. Samples are decoded to linear with the
+/// transfer function of colour_space, which the framebuffer is then rendered
+/// and output in, so an image written by Ppm or Pgm with that output space
+/// reads back as the values it was written from; gray pixels read as equal
+/// channels
+pub fn parse_pnm(bytes: &[u8], colour_space: ColourSpace, file: &str) -> Result<Framebuffer, RaytracerError> {
+  let mut position = 0;
+  let mut header = [0usize; 4];
+  for (n, value) in header.iter_mut().enumerate() {
+    // whitespace and comments may separate any two header fields
+    loop {
+      while position < bytes.len() && bytes[position].is_ascii_whitespace() {
+        position += 1;
+      }
+      if position < bytes.len() && bytes[position] == b'#' {
+        while position < bytes.len() && bytes[position] != b'\n' {
+          position += 1;
+        }
+      } else {
+        break;
       }
     }
+    let start = position;
+    while position < bytes.len() && !bytes[position].is_ascii_whitespace() {
+      position += 1;
+    }
+    let token = String::from_utf8_lossy(&bytes[start..position]);
+    *value = if n == 0 {
+      match token.as_ref() {
+        "P2" => 2,
+        "P3" => 3,
+        "P5" => 5,
+        "P6" => 6,
+        _ => return Err(pnm_error(file, format!("unsupported format '{token}'"))),
+      }
+    } else {
+      token.parse().map_err(|_| pnm_error(file, format!("expected a number, found '{token}'")))?
+    };
+  }
+  let [magic, width, height, maxval] = header;
+  if width == 0 || height == 0 {
+    return Err(pnm_error(file, "width and height must be positive"));
+  }
+  if maxval == 0 || maxval > 65535 {
+    return Err(pnm_error(file, format!("maxval {maxval} is not in 1..=65535")));
+  }
+
+  let channels = if magic == 2 || magic == 5 { 1 } else { 3 };
+  let count = width
+    .checked_mul(height)
+    .and_then(|pixels| pixels.checked_mul(channels))
+    .ok_or_else(|| pnm_error(file, format!("{width} by {height} image is too large")))?;
+  let samples: Vec<usize> = if magic == 2 || magic == 3 {
+    String::from_utf8_lossy(&bytes[position..])
+      .split_ascii_whitespace()
+      .map(|token| token.parse().map_err(|_| pnm_error(file, format!("expected a sample, found '{token}'"))))
+      .collect::<Result<_, _>>()?
+  } else {
+    // a single whitespace byte separates the header from the samples
+    let data = bytes.get(position + 1..).unwrap_or_default();
+    let size = if maxval < 256 { 1 } else { 2 };
+    let expected = count
+      .checked_mul(size)
+      .ok_or_else(|| pnm_error(file, format!("{width} by {height} image is too large")))?;
+    if data.len() != expected {
+      return Err(pnm_error(file, format!("expected {expected} bytes of samples, found {}", data.len())));
+    }
+    data
+      .chunks_exact(size)
+      .map(|sample| sample.iter().fold(0, |value, &byte| (value << 8) | byte as usize))
+      .collect()
+  };
+  if samples.len() != count {
+    return Err(pnm_error(file, format!("expected {count} samples, found {}", samples.len())));
+  }
+  if let Some(sample) = samples.iter().find(|&&sample| sample > maxval) {
+    return Err(pnm_error(file, format!("sample {sample} is above maxval {maxval}")));
+  }
+
+  let decode = |sample: usize| colour_space.decode(sample as f64 / maxval as f64);
+  let pixels = samples
+    .chunks_exact(channels)
+    .map(|pixel| match *pixel {
+      [gray] => Colour::new(decode(gray), decode(gray), decode(gray)),
+      [r, g, b] => Colour::new(decode(r), decode(g), decode(b)),
+      _ => unreachable!(),
+    })
+    .collect();
+  let mut framebuffer = Framebuffer::from_pixels(width, height, pixels)?;
+  framebuffer.set_colour_space(colour_space);
+  framebuffer.set_output_space(colour_space);
+  Ok(framebuffer)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use rstest::*;
+
+  fn framebuffer() -> Framebuffer {
+    let pixels = vec![Colour::new(1.0, 0.0, 0.25), Colour::new(0.0, 1.0, 0.0)];
+    Framebuffer::from_pixels(2, 1, pixels).unwrap()
+  }
+
+  mod ppm {
+    use super::*;
+
+    #[rstest]
+    fn encode_ascii() {
+      let bytes = Ppm::new(PnmEncoding::Ascii, BitDepth::Eight).encode_to_vec(&framebuffer()).unwrap();
+      assert_eq!(String::from_utf8(bytes).unwrap(), "P3\n2 1\n255\n255 0 137\n0 255 0\n");
+    }
+
+    #[rstest]
+    fn encode_binary() {
+      let bytes = Ppm::default().encode_to_vec(&framebuffer()).unwrap();
+      assert_eq!(bytes, b"P6\n2 1\n255\n\xff\x00\x89\x00\xff\x00".to_vec());
+    }
+
+    #[rstest]
+    fn encode_sixteen() {
+      let bytes = Ppm::new(PnmEncoding::Binary, BitDepth::Sixteen).encode_to_vec(&framebuffer()).unwrap();
+      let header = b"P6\n2 1\n65535\n";
+      assert_eq!(&bytes[..header.len()], header);
+      let samples = bytes[header.len()..]
+        .chunks(2)
+        .map(|sample| u16::from_be_bytes(sample.try_into().unwrap()))
+        .collect::<Vec<_>>();
+      let quarter = (srgb_encode(0.25) * 65535.0).round() as u16;
+      assert_eq!(samples, vec![65535, 0, quarter, 0, 65535, 0]);
 
-    Ok(self.bytes.len())
+      let ascii = Ppm::new(PnmEncoding::Ascii, BitDepth::Sixteen).encode_to_vec(&framebuffer()).unwrap();
+      let expected = format!("P3\n2 1\n65535\n65535 0 {quarter}\n0 65535 0\n");
+      assert_eq!(String::from_utf8(ascii).unwrap(), expected);
+    }
+  }
+
+  mod pgm {
+    use super::*;
+
+    #[rstest]
+    fn encode() {
+      let gray = Framebuffer::from_pixels(2, 1, vec![Colour::new(0.5, 0.5, 0.5), Colour::new(0.0, 1.0, 0.0)]).unwrap();
+      let expected = [(srgb_encode(0.5) * 255.0).round() as u8, (srgb_encode(0.7152) * 255.0).round() as u8];
+      let ascii = Pgm::new(PnmEncoding::Ascii, BitDepth::Eight).encode_to_vec(&gray).unwrap();
+      assert_eq!(String::from_utf8(ascii).unwrap(), format!("P2\n2 1\n255\n{}\n{}\n", expected[0], expected[1]));
+      let binary = Pgm::default().encode_to_vec(&gray).unwrap();
+      assert_eq!(binary, [b"P5\n2 1\n255\n".to_vec(), expected.to_vec()].concat());
+    }
+  }
+
+  mod pnm {
+    use super::*;
+
+    #[rstest]
+    #[case(Ppm::new(PnmEncoding::Ascii, BitDepth::Eight))]
+    #[case(Ppm::new(PnmEncoding::Binary, BitDepth::Eight))]
+    #[case(Ppm::new(PnmEncoding::Ascii, BitDepth::Sixteen))]
+    #[case(Ppm::new(PnmEncoding::Binary, BitDepth::Sixteen))]
+    fn parse_ppm(#[case] ppm: Ppm) {
+      let framebuffer = framebuffer();
+      let bytes = ppm.encode_to_vec(&framebuffer).unwrap();
+      let read = parse_pnm(&bytes, ColourSpace::Srgb, "test.ppm").unwrap();
+      assert_eq!((read.width(), read.height()), (2, 1));
+      // reading back and writing again reproduces the image exactly
+      assert_eq!(ppm.encode_to_vec(&read).unwrap(), bytes);
+      let tolerance = if ppm.depth == BitDepth::Eight { 1e-2 } else { 1e-4 };
+      for (read, expected) in read.pixels().iter().zip(framebuffer.pixels()) {
+        assert!((*read - *expected).length() < tolerance, "{read:?} != {expected:?}");
+      }
+    }
+
+    #[rstest]
+    #[case(Pgm::new(PnmEncoding::Ascii, BitDepth::Eight))]
+    #[case(Pgm::new(PnmEncoding::Binary, BitDepth::Sixteen))]
+    fn parse_pgm(#[case] pgm: Pgm) {
+      let framebuffer = Framebuffer::from_pixels(1, 2, vec![Colour::new(0.2, 0.2, 0.2), Colour::new(1.0, 1.0, 1.0)]).unwrap();
+      let bytes = pgm.encode_to_vec(&framebuffer).unwrap();
+      let read = parse_pnm(&bytes, ColourSpace::Srgb, "test.pgm").unwrap();
+      assert_eq!((read.width(), read.height()), (1, 2));
+      assert_eq!(pgm.encode_to_vec(&read).unwrap(), bytes);
+      assert!((read.get(0, 0).x - 0.2).abs() < 1e-2);
+      assert_eq!(read.get(0, 0).x, read.get(0, 0).z);
+      assert_eq!(read.get(0, 1), Colour::new(1.0, 1.0, 1.0));
+    }
+
+    #[rstest]
+    #[case(ColourSpace::Rec2020)]
+    #[case(ColourSpace::AcesCg)]
+    fn parse_colour_space(#[case] space: ColourSpace) {
+      let mut framebuffer = framebuffer();
+      framebuffer.set_colour_space(space);
+      framebuffer.set_output_space(space);
+      let ppm = Ppm::new(PnmEncoding::Binary, BitDepth::Sixteen);
+      let bytes = ppm.encode_to_vec(&framebuffer).unwrap();
+      let read = parse_pnm(&bytes, space, "test.ppm").unwrap();
+      assert_eq!((read.colour_space(), read.output_space()), (space, space));
+      assert_eq!(ppm.encode_to_vec(&read).unwrap(), bytes);
+      for (read, expected) in read.pixels().iter().zip(framebuffer.pixels()) {
+        assert!((*read - *expected).length() < 1e-4, "{read:?} != {expected:?}");
+      }
+      // read as srgb, the same samples decode to different radiance
+      let srgb = parse_pnm(&bytes, ColourSpace::Srgb, "test.ppm").unwrap();
+      assert!((srgb.get(0, 0).z - 0.25).abs() > 1e-2);
+    }
+
+    #[rstest]
+    fn parse_comments() {
+      let bytes = b"P2\n# made by hand\n2 # width\n1\n4\n0 4\n";
+      let read = parse_pnm(bytes, ColourSpace::Srgb, "test.pgm").unwrap();
+      assert_eq!(read.pixels(), &[Colour::new(0.0, 0.0, 0.0), Colour::new(1.0, 1.0, 1.0)]);
+    }
+
+    #[rstest]
+    #[case(b"P4\n1 1\n255\n0\n".to_vec())]
+    #[case(b"P3\n1 x\n255\n0 0 0\n".to_vec())]
+    #[case(b"P3\n0 1\n255\n".to_vec())]
+    #[case(b"P3\n1 1\n0\n0 0 0\n".to_vec())]
+    #[case(b"P3\n1 1\n70000\n0 0 0\n".to_vec())]
+    #[case(b"P3\n1 1\n255\n0 0\n".to_vec())]
+    #[case(b"P3\n1 1\n255\n0 0 256\n".to_vec())]
+    #[case(b"P6\n1 1\n255\n\x00\x00".to_vec())]
+    #[case(b"P5\n1 1\n65535\n\x00".to_vec())]
+    #[case(b"P6\n1 1\n255".to_vec())]
+    #[case(b"P6\n4294967296 4294967296\n255\n".to_vec())]
+    #[case(b"P5\n4294967296 2147483648\n65535\n".to_vec())]
+    #[case(b"".to_vec())]
+    fn parse_invalid(#[case] bytes: Vec<u8>) {
+      assert!(matches!(parse_pnm(&bytes, ColourSpace::Srgb, "test.ppm"), Err(RaytracerError::ImageParseError { .. })));
+    }
   }
-}
\ No newline at end of file
+}
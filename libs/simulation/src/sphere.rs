@@ -1,24 +1,39 @@
 
 use lib_raytracer::prelude::*;
+use lib_raytracer::aabb;
 
+use rand::{Rng, RngCore};
+
+use std::f64::consts::PI;
 use std::sync::Arc;
 
 #[derive(Clone)]
 pub struct Sphere {
   radius: f64,
-  center: Point3,
+  // position of the center over time
+  center: Keyframes<Point3>,
   material: Arc<dyn Material>,
 }
 
 impl Sphere {
   pub fn new(radius: f64, center: Point3, material: Arc<dyn Material>) -> Self {
+    Self::keyframed(radius, Keyframes::constant(center), material)
+  }
+
+  /// Produces the sphere moving from center0 at time 0 to center1 at time 1
+  pub fn moving(radius: f64, center0: Point3, center1: Point3, material: Arc<dyn Material>) -> Self {
+    Self::keyframed(radius, Keyframes::linear(center0, center1), material)
+  }
+
+  pub fn keyframed(radius: f64, center: Keyframes<Point3>, material: Arc<dyn Material>) -> Self {
     Self { radius, center, material, }
   }
 }
 
 impl Hittable for Sphere {
-  fn hit(&mut self, ray: &Ray, ray_i: Interval, record: &mut HitRecord) -> bool {
-    let oc = ray.position() - self.center;
+  fn hit(&self, ray: &Ray, ray_i: Interval, record: &mut HitRecord) -> bool {
+    let center = self.center.at(ray.time());
+    let oc = ray.position() - center;
     let a = ray.direction().length_squared();
     let half_b = dot(oc, ray.direction());
     let c = oc.length_squared() - self.radius*self.radius;
@@ -39,10 +54,59 @@ impl Hittable for Sphere {
 
     record.d = root;
     record.position = ray.at(record.d);
-    let outward_normal = (record.position - self.center) / self.radius;
+    let outward_normal = (record.position - center) / self.radius;
     record.set_face_normal(ray, outward_normal);
+    (record.u, record.v) = direction_to_uv(outward_normal);
     record.material = Some(Arc::clone(&self.material));
 
     true
   }
+
+  fn bounding_box(&self) -> Aabb {
+    let radius = self.radius.abs();
+    let radii = Vector3::new(radius, radius, radius);
+    // the center moves in straight lines between keyframes
+    self.center
+      .keys()
+      .iter()
+      .fold(aabb::EMPTY, |bbox, (_, center)| {
+        Aabb::surrounding(bbox, Aabb::from_points(*center - radii, *center + radii))
+      })
+  }
+
+  fn pdf_value(&self, ray: &Ray) -> f64 {
+    let mut record = HitRecord::default();
+    if !self.hit(ray, Interval::new(0.001, f64::INFINITY), &mut record) {
+      return 0.0;
+    }
+
+    let distance_squared = (self.center.at(ray.time()) - ray.position()).length_squared();
+    let radius_squared = self.radius * self.radius;
+    if distance_squared <= radius_squared {
+      // seen from inside, every direction is equally likely
+      return 1.0 / (4.0 * PI);
+    }
+    let cos_theta_max = (1.0 - radius_squared / distance_squared).sqrt();
+    1.0 / (2.0 * PI * (1.0 - cos_theta_max))
+  }
+
+  fn random(&self, mut rng: &mut dyn RngCore, origin: Point3, time: f64) -> Vector3 {
+    let direction = self.center.at(time) - origin;
+    let distance_squared = direction.length_squared();
+    let radius_squared = self.radius * self.radius;
+    if distance_squared <= radius_squared {
+      return Vector3::random_unit_vector(&mut rng);
+    }
+
+    // uniform over the cone of directions the sphere subtends
+    let cos_theta_max = (1.0 - radius_squared / distance_squared).sqrt();
+    let (r1, r2) = (rng.gen::<f64>(), rng.gen::<f64>());
+    let z = 1.0 + r2 * (cos_theta_max - 1.0);
+    let phi = 2.0 * PI * r1;
+    let sin_theta = (1.0 - z*z).max(0.0).sqrt();
+
+    let w = direction.to_unit();
+    let (u, v) = tangents(w);
+    sin_theta*phi.cos()*u + sin_theta*phi.sin()*v + z*w
+  }
 }
\ No newline at end of file
@@ -0,0 +1,47 @@
+
+use lib_raytracer::prelude::*;
+
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+
+use std::f64::consts::PI;
+use std::sync::Arc;
+
+pub fn grey() -> Arc<dyn Material> {
+  Arc::new(Lambertian::new(Colour::new(0.5, 0.5, 0.5)))
+}
+
+pub fn hit(hittable: &(impl Hittable + ?Sized), origin: Point3, direction: Vector3) -> Option<HitRecord> {
+  let mut record = HitRecord::default();
+  let ray = Ray::new(origin, direction);
+  hittable.hit(&ray, Interval::new(0.001, f64::INFINITY), &mut record).then_some(record)
+}
+
+/// Checks pdf_value is a density over directions from origin, by
+/// integrating it over the sphere, and that it is the density random draws
+/// from, by estimating the solid angle both ways
+pub fn assert_pdf_integrates(hittable: &impl Hittable, origin: Point3) {
+  let mut rng = ChaCha8Rng::seed_from_u64(6);
+  let n = 200_000;
+  let (mut integral, mut hits) = (0.0, 0);
+  for _ in 0..n {
+    let ray = Ray::new(origin, Vector3::random_unit_vector(&mut rng));
+    let pdf = hittable.pdf_value(&ray);
+    integral += pdf;
+    hits += usize::from(pdf > 0.0);
+  }
+  let integral = 4.0 * PI * integral / n as f64;
+  assert!((integral - 1.0).abs() < 0.03, "pdf integrates to {integral}");
+
+  let uniform_solid_angle = 4.0 * PI * hits as f64 / n as f64;
+  let samples = 20_000;
+  let sampled_solid_angle = (0..samples)
+    .map(|_| {
+      let direction = hittable.random(&mut rng, origin, 0.0);
+      let pdf = hittable.pdf_value(&Ray::new(origin, direction));
+      assert!(pdf > 0.0);
+      1.0 / pdf
+    })
+    .sum::<f64>() / samples as f64;
+  assert!((sampled_solid_angle / uniform_solid_angle - 1.0).abs() < 0.03);
+}
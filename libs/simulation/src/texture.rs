@@ -0,0 +1,63 @@
+
+use lib_raytracer::prelude::*;
+
+use image::DynamicImage;
+
+use std::path::Path;
+
+/// Produces a texture from any image format the `image` crate can decode, in
+/// linear sRGB
+pub fn load_image_texture<P: AsRef<Path>>(path: P) -> Result<ImageTexture, RaytracerError> {
+  load_image_texture_in(path, ColourSpace::Srgb)
+}
+
+/// Produces a texture from any image format the `image` crate can decode,
+/// converted into the linear colour_space a scene is rendered in
+pub fn load_image_texture_in<P: AsRef<Path>>(path: P, colour_space: ColourSpace) -> Result<ImageTexture, RaytracerError> {
+  let image = image::open(path).map_err(|_| RaytracerError::ImageLoadError)?;
+  let (width, height) = (image.width() as usize, image.height() as usize);
+  ImageTexture::new(width, height, image_colours(&image, colour_space))
+}
+
+/// Produces the linear colours of an sRGB image, top row first, converted into
+/// colour_space; integer images are taken to be sRGB encoded and are decoded,
+/// float images to be linear already
+pub fn image_colours(image: &DynamicImage, colour_space: ColourSpace) -> Vec<Colour> {
+  let is_float = matches!(image, DynamicImage::ImageRgb32F(_) | DynamicImage::ImageRgba32F(_));
+  let decode = |value: f32| if is_float { value as f64 } else { srgb_decode(value as f64) };
+  let conversion = ColourSpace::Srgb.conversion_to(colour_space);
+  image
+    .to_rgb32f()
+    .pixels()
+    .map(|pixel| {
+      let [r, g, b] = pixel.0;
+      conversion * Colour::new(decode(r), decode(g), decode(b))
+    })
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use rstest::*;
+
+  use image::{Rgb, Rgb32FImage, RgbImage};
+
+  #[rstest]
+  fn image_colours_decoded() {
+    let image = DynamicImage::ImageRgb8(RgbImage::from_pixel(1, 1, Rgb([0, 188, 255])));
+    let colours = image_colours(&image, ColourSpace::Srgb);
+    // 188 is about half as bright as 255 once decoded, not three quarters
+    assert_eq!(colours[0].x, 0.0);
+    assert!((colours[0].y - 0.5029).abs() < 1e-3);
+    assert!((colours[0].z - 1.0).abs() < 1e-12);
+  }
+
+  #[rstest]
+  fn image_colours_linear() {
+    let image = DynamicImage::ImageRgb32F(Rgb32FImage::from_pixel(1, 1, Rgb([0.25, 0.5, 4.0])));
+    assert_eq!(image_colours(&image, ColourSpace::Srgb), vec![Colour::new(0.25, 0.5, 4.0)]);
+    let converted = image_colours(&image, ColourSpace::AcesCg);
+    assert_eq!(converted, vec![ColourSpace::Srgb.convert(Colour::new(0.25, 0.5, 4.0), ColourSpace::AcesCg)]);
+  }
+}
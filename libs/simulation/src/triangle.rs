@@ -0,0 +1,416 @@
+
+use lib_raytracer::prelude::*;
+
+use std::sync::Arc;
+
+// minimum thickness of a triangle's bounding box along any axis
+const BBOX_PADDING: f64 = 1e-4;
+
+/// Produces (d, b1, b2) where the ray meets triangle v0 v1 v2, with b1 and b2
+/// the barycentric weights of v1 and v2 (Möller–Trumbore)
+fn intersect(vertices: [Point3; 3], ray: &Ray, ray_i: Interval) -> Option<(f64, f64, f64)> {
+  let [v0, v1, v2] = vertices;
+  let edge1 = v1 - v0;
+  let edge2 = v2 - v0;
+
+  let p = cross(ray.direction(), edge2);
+  let det = dot(edge1, p);
+  // ray parallel to the triangle's plane
+  if det.abs() < 1e-12 {
+    return None;
+  }
+  let inv_det = 1.0 / det;
+
+  let t = ray.position() - v0;
+  let b1 = dot(t, p) * inv_det;
+  if !(0.0..=1.0).contains(&b1) {
+    return None;
+  }
+
+  let q = cross(t, edge1);
+  let b2 = dot(ray.direction(), q) * inv_det;
+  if b2 < 0.0 || b1 + b2 > 1.0 {
+    return None;
+  }
+
+  let d = dot(edge2, q) * inv_det;
+  if !ray_i.surrounds(d) {
+    return None;
+  }
+
+  Some((d, b1, b2))
+}
+
+/// Fills record for a hit at barycentric (b1, b2); front_face follows the
+/// winding order, while normals (if given) only shade. UVs are interpolated
+/// from texcoords, or are the barycentrics themselves
+fn fill_record(
+  record: &mut HitRecord,
+  ray: &Ray,
+  vertices: [Point3; 3],
+  normals: Option<[Vector3; 3]>,
+  texcoords: Option<[(f64, f64); 3]>,
+  (d, b1, b2): (f64, f64, f64),
+) {
+  let [v0, v1, v2] = vertices;
+  let geometric_normal = cross(v1 - v0, v2 - v0).to_unit();
+
+  record.d = d;
+  record.position = ray.at(d);
+  record.set_face_normal(ray, geometric_normal);
+  if let Some([n0, n1, n2]) = normals {
+    let shading_normal = ((1.0 - b1 - b2)*n0 + b1*n1 + b2*n2).to_unit();
+    record.normal = if record.front_face { shading_normal } else { -shading_normal };
+  }
+  (record.u, record.v) = match texcoords {
+    Some([t0, t1, t2]) => (
+      (1.0 - b1 - b2)*t0.0 + b1*t1.0 + b2*t2.0,
+      (1.0 - b1 - b2)*t0.1 + b1*t1.1 + b2*t2.1,
+    ),
+    None => (b1, b2),
+  };
+}
+
+fn bounding_box(vertices: [Point3; 3]) -> Aabb {
+  let [v0, v1, v2] = vertices;
+  Aabb::surrounding(Aabb::from_points(v0, v1), Aabb::from_points(v1, v2)).padded(BBOX_PADDING)
+}
+
+#[derive(Clone)]
+pub struct Triangle {
+  vertices: [Point3; 3],
+  normals: Option<[Vector3; 3]>,
+  material: Arc<dyn Material>,
+}
+
+impl Triangle {
+  /// Produces a flat triangle; counter-clockwise vertices face the viewer
+  pub fn new(a: Point3, b: Point3, c: Point3, material: Arc<dyn Material>) -> Self {
+    Self { vertices: [a, b, c], normals: None, material, }
+  }
+
+  /// Produces the triangle shaded with normals interpolated across its face
+  pub fn with_normals(mut self, normals: [Vector3; 3]) -> Self {
+    self.normals = Some(normals.map(Vector3::to_unit));
+    self
+  }
+}
+
+impl Hittable for Triangle {
+  fn hit(&self, ray: &Ray, ray_i: Interval, record: &mut HitRecord) -> bool {
+    match intersect(self.vertices, ray, ray_i) {
+      Some(hit) => {
+        fill_record(record, ray, self.vertices, self.normals, None, hit);
+        record.material = Some(Arc::clone(&self.material));
+        true
+      },
+      None => false,
+    }
+  }
+
+  fn bounding_box(&self) -> Aabb {
+    bounding_box(self.vertices)
+  }
+}
+
+/// Materials of a mesh, either shared by every face or one per face
+#[derive(Clone)]
+pub enum MeshMaterial {
+  PerMesh(Arc<dyn Material>),
+  PerFace(Vec<Arc<dyn Material>>),
+}
+
+/// Vertex and index buffers of a mesh; each face indexes three vertices
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MeshBuffers {
+  pub positions: Vec<Point3>,
+  // one per position, or empty for flat shading
+  pub normals: Vec<Vector3>,
+  // one (u, v) per position, or empty for barycentric uvs
+  pub texcoords: Vec<(f64, f64)>,
+  pub indices: Vec<[usize; 3]>,
+}
+
+struct MeshShared {
+  buffers: MeshBuffers,
+  material: MeshMaterial,
+}
+
+impl MeshShared {
+  fn vertices(&self, face: usize) -> [Point3; 3] {
+    self.buffers.indices[face].map(|i| self.buffers.positions[i])
+  }
+
+  fn normals(&self, face: usize) -> Option<[Vector3; 3]> {
+    if self.buffers.normals.is_empty() {
+      None
+    } else {
+      Some(self.buffers.indices[face].map(|i| self.buffers.normals[i]))
+    }
+  }
+
+  fn texcoords(&self, face: usize) -> Option<[(f64, f64); 3]> {
+    if self.buffers.texcoords.is_empty() {
+      None
+    } else {
+      Some(self.buffers.indices[face].map(|i| self.buffers.texcoords[i]))
+    }
+  }
+
+  fn material(&self, face: usize) -> &Arc<dyn Material> {
+    match self.material {
+      MeshMaterial::PerMesh(ref material) => material,
+      MeshMaterial::PerFace(ref materials) => &materials[face],
+    }
+  }
+}
+
+/// Single face of a mesh, referring back into the shared buffers
+struct MeshFace {
+  mesh: Arc<MeshShared>,
+  face: usize,
+}
+
+impl Hittable for MeshFace {
+  fn hit(&self, ray: &Ray, ray_i: Interval, record: &mut HitRecord) -> bool {
+    let vertices = self.mesh.vertices(self.face);
+    match intersect(vertices, ray, ray_i) {
+      Some(hit) => {
+        fill_record(record, ray, vertices, self.mesh.normals(self.face), self.mesh.texcoords(self.face), hit);
+        record.material = Some(Arc::clone(self.mesh.material(self.face)));
+        true
+      },
+      None => false,
+    }
+  }
+
+  fn bounding_box(&self) -> Aabb {
+    bounding_box(self.mesh.vertices(self.face))
+  }
+}
+
+/// Indexed triangle mesh; faces share one set of buffers and are held in a Bvh
+pub struct TriangleMesh {
+  mesh: Arc<MeshShared>,
+  bvh: Bvh,
+}
+
+impl TriangleMesh {
+  pub fn new(mut buffers: MeshBuffers, material: MeshMaterial) -> Result<Self, RaytracerError> {
+    let vertex_count = buffers.positions.len();
+    let out_of_bounds = buffers.indices.iter().flatten().any(|&i| i >= vertex_count);
+    let bad_normals = !buffers.normals.is_empty() && buffers.normals.len() != vertex_count;
+    let bad_texcoords = !buffers.texcoords.is_empty() && buffers.texcoords.len() != vertex_count;
+    let bad_materials = match material {
+      MeshMaterial::PerMesh(_) => false,
+      MeshMaterial::PerFace(ref materials) => materials.len() != buffers.indices.len(),
+    };
+    if out_of_bounds || bad_normals || bad_texcoords || bad_materials {
+      return Err(RaytracerError::MeshError);
+    }
+    buffers.normals.iter_mut().for_each(|normal| *normal = normal.to_unit());
+
+    let face_count = buffers.indices.len();
+    let mesh = Arc::new(MeshShared { buffers, material, });
+    let faces: VecOfHittable = (0..face_count)
+      .map(|face| Box::new(MeshFace { mesh: Arc::clone(&mesh), face, }) as Box<dyn Hittable>)
+      .collect();
+
+    Ok(Self { mesh, bvh: Bvh::new(faces), })
+  }
+
+  pub fn buffers(&self) -> &MeshBuffers {
+    &self.mesh.buffers
+  }
+
+  pub fn face_count(&self) -> usize {
+    self.mesh.buffers.indices.len()
+  }
+}
+
+impl Hittable for TriangleMesh {
+  fn hit(&self, ray: &Ray, ray_i: Interval, record: &mut HitRecord) -> bool {
+    self.bvh.hit(ray, ray_i, record)
+  }
+
+  fn bounding_box(&self) -> Aabb {
+    self.bvh.bounding_box()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use rstest::*;
+
+  use crate::testing::{grey, hit};
+
+  fn vertices() -> [Point3; 3] {
+    [Point3::new(0.0, 0.0, -1.0), Point3::new(1.0, 0.0, -1.0), Point3::new(0.0, 1.0, -1.0)]
+  }
+
+  fn quad_buffers() -> MeshBuffers {
+    MeshBuffers {
+      positions: vec![
+        Point3::new(-1.0, -1.0, -1.0),
+        Point3::new(1.0, -1.0, -1.0),
+        Point3::new(1.0, 1.0, -1.0),
+        Point3::new(-1.0, 1.0, -1.0),
+      ],
+      indices: vec![[0, 1, 2], [0, 2, 3]],
+      ..MeshBuffers::default()
+    }
+  }
+
+  mod intersect {
+    use super::*;
+
+    #[rstest]
+    #[case(Point3::new(0.25, 0.25, 0.0), Some((1.0, 0.25, 0.25)))]
+    #[case(Point3::new(0.0, 0.0, 0.0), Some((1.0, 0.0, 0.0)))]
+    #[case(Point3::new(0.5, 0.5, 0.0), Some((1.0, 0.5, 0.5)))]
+    #[case(Point3::new(0.75, 0.75, 0.0), None)]
+    #[case(Point3::new(-0.25, 0.5, 0.0), None)]
+    #[case(Point3::new(0.25, -0.25, 0.0), None)]
+    fn forward(#[case] origin: Point3, #[case] expected: Option<(f64, f64, f64)>) {
+      let ray = Ray::new(origin, Vector3::new(0.0, 0.0, -1.0));
+      let found = super::super::intersect(vertices(), &ray, Interval::new(0.001, f64::INFINITY));
+      assert_eq!(found, expected);
+    }
+
+    #[rstest]
+    fn parallel() {
+      let ray = Ray::new(Point3::new(-1.0, 0.25, -1.0), Vector3::new(1.0, 0.0, 0.0));
+      assert_eq!(super::super::intersect(vertices(), &ray, Interval::new(0.001, f64::INFINITY)), None);
+    }
+
+    #[rstest]
+    fn outside_interval() {
+      let ray = Ray::new(Point3::new(0.25, 0.25, 0.0), Vector3::new(0.0, 0.0, -1.0));
+      assert_eq!(super::super::intersect(vertices(), &ray, Interval::new(0.001, 0.5)), None);
+      // behind the origin
+      let ray = Ray::new(Point3::new(0.25, 0.25, -2.0), Vector3::new(0.0, 0.0, -1.0));
+      assert_eq!(super::super::intersect(vertices(), &ray, Interval::new(0.001, f64::INFINITY)), None);
+    }
+  }
+
+  mod fill_record {
+    use super::*;
+
+    #[rstest]
+    #[case(Vector3::new(0.0, 0.0, -1.0), true)]
+    #[case(Vector3::new(0.0, 0.0, 1.0), false)]
+    fn front_face(#[case] direction: Vector3, #[case] expected: bool) {
+      let ray = Ray::new(Point3::new(0.25, 0.5, -1.0) - direction, direction);
+      let mut record = HitRecord::default();
+      super::super::fill_record(&mut record, &ray, vertices(), None, None, (1.0, 0.25, 0.5));
+      assert_eq!(record.front_face, expected);
+      assert_eq!(record.normal, if expected { Vector3::new(0.0, 0.0, 1.0) } else { Vector3::new(0.0, 0.0, -1.0) });
+      assert_eq!(record.position, Point3::new(0.25, 0.5, -1.0));
+      // uvs are the barycentrics without texcoords
+      assert_eq!((record.u, record.v), (0.25, 0.5));
+    }
+
+    #[rstest]
+    fn texcoords() {
+      let ray = Ray::new(Point3::new(0.25, 0.5, 0.0), Vector3::new(0.0, 0.0, -1.0));
+      let mut record = HitRecord::default();
+      let texcoords = [(0.0, 1.0), (1.0, 1.0), (0.0, 0.0)];
+      super::super::fill_record(&mut record, &ray, vertices(), None, Some(texcoords), (1.0, 0.25, 0.5));
+      assert_eq!((record.u, record.v), (0.25, 0.5));
+    }
+
+    #[rstest]
+    #[case(Vector3::new(0.0, 0.0, -1.0), 1.0)]
+    #[case(Vector3::new(0.0, 0.0, 1.0), -1.0)]
+    fn normals(#[case] direction: Vector3, #[case] sign: f64) {
+      let ray = Ray::new(Point3::new(0.5, 0.5, -1.0) - direction, direction);
+      let mut record = HitRecord::default();
+      let normals = [Vector3::new(0.0, 0.0, 1.0), Vector3::new(1.0, 0.0, 0.0), Vector3::new(0.0, 1.0, 0.0)];
+      super::super::fill_record(&mut record, &ray, vertices(), Some(normals), None, (1.0, 0.5, 0.5));
+      // halfway between the normals at v1 and v2, flipped to face the ray
+      let expected = sign * Vector3::new(1.0, 1.0, 0.0).to_unit();
+      assert!((record.normal - expected).length() < 1e-12, "{:?}", record.normal);
+    }
+  }
+
+  mod triangle {
+    use super::*;
+
+    #[rstest]
+    fn hit_and_miss() {
+      let [a, b, c] = vertices();
+      let material = grey();
+      let triangle = Triangle::new(a, b, c, Arc::clone(&material));
+      let record = hit(&triangle, Point3::new(0.25, 0.25, 0.0), Vector3::new(0.0, 0.0, -1.0)).unwrap();
+      assert_eq!(record.d, 1.0);
+      assert!(record.front_face);
+      assert!(Arc::ptr_eq(&record.material.unwrap(), &material));
+      assert!(hit(&triangle, Point3::new(0.75, 0.75, 0.0), Vector3::new(0.0, 0.0, -1.0)).is_none());
+
+      let bbox = triangle.bounding_box();
+      assert!(bbox.z.size() > 0.0);
+      assert!(bbox.x.contains(0.0) && bbox.x.contains(1.0));
+    }
+
+    #[rstest]
+    fn with_normals() {
+      let [a, b, c] = vertices();
+      let triangle = Triangle::new(a, b, c, grey()).with_normals([Vector3::new(0.0, 0.0, 2.0); 3]);
+      let record = hit(&triangle, Point3::new(0.25, 0.25, -2.0), Vector3::new(0.0, 0.0, 1.0)).unwrap();
+      assert!(!record.front_face);
+      assert_eq!(record.normal, Vector3::new(0.0, 0.0, -1.0));
+    }
+  }
+
+  mod triangle_mesh {
+    use super::*;
+
+    #[rstest]
+    fn new() {
+      let mesh = TriangleMesh::new(quad_buffers(), MeshMaterial::PerMesh(grey())).unwrap();
+      assert_eq!(mesh.face_count(), 2);
+      assert_eq!(mesh.buffers(), &quad_buffers());
+      assert!(hit(&mesh, Point3::new(0.5, -0.5, 0.0), Vector3::new(0.0, 0.0, -1.0)).is_some());
+      assert!(hit(&mesh, Point3::new(-0.5, 0.5, 0.0), Vector3::new(0.0, 0.0, -1.0)).is_some());
+      assert!(hit(&mesh, Point3::new(1.5, 0.0, 0.0), Vector3::new(0.0, 0.0, -1.0)).is_none());
+    }
+
+    #[rstest]
+    #[case(MeshBuffers { indices: vec![[0, 1, 4]], ..quad_buffers() }, 2)]
+    #[case(MeshBuffers { normals: vec![Vector3::new(0.0, 0.0, 1.0); 3], ..quad_buffers() }, 2)]
+    #[case(MeshBuffers { texcoords: vec![(0.0, 0.0); 5], ..quad_buffers() }, 2)]
+    #[case(quad_buffers(), 1)]
+    #[case(quad_buffers(), 3)]
+    fn invalid(#[case] buffers: MeshBuffers, #[case] material_count: usize) {
+      let materials = (0..material_count).map(|_| grey()).collect();
+      let mesh = TriangleMesh::new(buffers, MeshMaterial::PerFace(materials));
+      assert!(matches!(mesh, Err(RaytracerError::MeshError)));
+    }
+
+    #[rstest]
+    fn per_face_material() {
+      let materials = vec![grey(), grey()];
+      let mesh = TriangleMesh::new(quad_buffers(), MeshMaterial::PerFace(materials.clone())).unwrap();
+      // the lower right half is face 0, the upper left face 1
+      for (origin, face) in [(Point3::new(0.5, -0.5, 0.0), 0), (Point3::new(-0.5, 0.5, 0.0), 1)] {
+        let record = hit(&mesh, origin, Vector3::new(0.0, 0.0, -1.0)).unwrap();
+        assert!(Arc::ptr_eq(&record.material.unwrap(), &materials[face]));
+      }
+    }
+
+    #[rstest]
+    fn normalised_normals_and_texcoords() {
+      let buffers = MeshBuffers {
+        normals: vec![Vector3::new(0.0, 0.0, 3.0); 4],
+        texcoords: vec![(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)],
+        ..quad_buffers()
+      };
+      let mesh = TriangleMesh::new(buffers, MeshMaterial::PerMesh(grey())).unwrap();
+      assert_eq!(mesh.buffers().normals, vec![Vector3::new(0.0, 0.0, 1.0); 4]);
+      let record = hit(&mesh, Point3::new(0.5, -0.5, 0.0), Vector3::new(0.0, 0.0, -1.0)).unwrap();
+      assert_eq!(record.normal, Vector3::new(0.0, 0.0, 1.0));
+      assert!((record.u - 0.75).abs() < 1e-12 && (record.v - 0.25).abs() < 1e-12);
+    }
+  }
+}
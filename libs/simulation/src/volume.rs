@@ -0,0 +1,161 @@
+
+use lib_raytracer::prelude::*;
+
+use std::fs;
+use std::path::Path;
+
+fn volume_error(file: &str, message: impl Into<String>) -> RaytracerError {
+  RaytracerError::VolumeLoadError {
+    file: file.to_string(),
+    message: message.into(),
+  }
+}
+
+/// Produces the density grid in the Mitsuba `.vol` file at path, together
+/// with the box it spans
+pub fn load_vol<P: AsRef<Path>>(path: P) -> Result<(DensityGrid, Aabb), RaytracerError> {
+  let path = path.as_ref();
+  let bytes = fs::read(path)?;
+  parse_vol(&bytes, &path.display().to_string())
+}
+
+/// Produces the density grid and bounds of a Mitsuba `.vol` grid: a "VOL" tag
+/// and version 3, then little endian i32 encoding (1 for f32), resolution
+/// x y z and channel count, f32 bounds (min xyz, max xyz), and the voxels x
+/// fastest. Multi-channel voxels are averaged
+pub fn parse_vol(bytes: &[u8], file: &str) -> Result<(DensityGrid, Aabb), RaytracerError> {
+  const HEADER_LEN: usize = 48;
+  if bytes.len() < HEADER_LEN || &bytes[0..3] != b"VOL" {
+    return Err(volume_error(file, "missing VOL header"));
+  }
+  if bytes[3] != 3 {
+    return Err(volume_error(file, format!("unsupported version {}", bytes[3])));
+  }
+
+  let i32_at = |offset: usize| i32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap());
+  let f32_at = |offset: usize| f32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap());
+
+  let encoding = i32_at(4);
+  if encoding != 1 {
+    return Err(volume_error(file, format!("unsupported encoding {encoding}, only float32 (1) is read")));
+  }
+  let dims = [i32_at(8), i32_at(12), i32_at(16), i32_at(20)];
+  if dims.iter().any(|&n| n <= 0) {
+    return Err(volume_error(file, "resolution and channels must be positive"));
+  }
+  let [nx, ny, nz, channels] = dims.map(|n| n as usize);
+  let bounds: Vec<f64> = (0..6).map(|n| f32_at(24 + 4*n) as f64).collect();
+  let bbox = Aabb::from_points(
+    Point3::new(bounds[0], bounds[1], bounds[2]),
+    Point3::new(bounds[3], bounds[4], bounds[5]),
+  );
+
+  let expected = [nx, ny, nz, channels]
+    .iter()
+    .try_fold(4usize, |size, &n| size.checked_mul(n))
+    .and_then(|size| size.checked_add(HEADER_LEN))
+    .ok_or_else(|| volume_error(file, "resolution is too large"))?;
+  if bytes.len() != expected {
+    return Err(volume_error(file, format!("expected {expected} bytes, found {}", bytes.len())));
+  }
+  let densities: Vec<f64> = bytes[HEADER_LEN..]
+    .chunks_exact(4 * channels)
+    .map(|voxel| {
+      let sum: f64 = voxel
+        .chunks_exact(4)
+        .map(|value| f32::from_le_bytes(value.try_into().unwrap()) as f64)
+        .sum();
+      sum / channels as f64
+    })
+    .collect();
+
+  let grid = DensityGrid::new([nx, ny, nz], densities)?;
+  Ok((grid, bbox))
+}
+
+/// Produces the density grid in the headerless file at path, holding one byte
+/// per voxel x fastest, mapped to densities in [0, 1]
+pub fn load_raw<P: AsRef<Path>>(path: P, resolution: [usize; 3]) -> Result<DensityGrid, RaytracerError> {
+  let path = path.as_ref();
+  let bytes = fs::read(path)?;
+  parse_raw(&bytes, resolution, &path.display().to_string())
+}
+
+pub fn parse_raw(bytes: &[u8], resolution: [usize; 3], file: &str) -> Result<DensityGrid, RaytracerError> {
+  let expected = resolution
+    .iter()
+    .try_fold(1usize, |size, &n| size.checked_mul(n))
+    .ok_or_else(|| volume_error(file, "resolution is too large"))?;
+  if bytes.len() != expected {
+    return Err(volume_error(file, format!("expected {expected} bytes, found {}", bytes.len())));
+  }
+  let densities = bytes.iter().map(|&byte| byte as f64 / 255.0).collect();
+  DensityGrid::new(resolution, densities)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use rstest::*;
+
+  fn vol(version: u8, encoding: i32, dims: [i32; 4], bounds: [f32; 6], voxels: &[f32]) -> Vec<u8> {
+    let mut bytes = b"VOL".to_vec();
+    bytes.push(version);
+    bytes.extend(encoding.to_le_bytes());
+    dims.iter().for_each(|n| bytes.extend(n.to_le_bytes()));
+    bounds.iter().for_each(|n| bytes.extend(n.to_le_bytes()));
+    voxels.iter().for_each(|n| bytes.extend(n.to_le_bytes()));
+    bytes
+  }
+
+  mod vol {
+    use super::*;
+
+    #[rstest]
+    fn parse() {
+      let bytes = vol(3, 1, [2, 1, 1, 1], [-1.0, -2.0, -3.0, 1.0, 2.0, 3.0], &[0.25, 0.75]);
+      let (grid, bbox) = parse_vol(&bytes, "test.vol").unwrap();
+      assert_eq!(grid.resolution(), [2, 1, 1]);
+      assert_eq!(grid.max_density(), 0.75);
+      assert!((grid.sample(Point3::new(0.25, 0.5, 0.5)) - 0.25).abs() < 1e-12);
+      assert_eq!(bbox, Aabb::from_points(Point3::new(-1.0, -2.0, -3.0), Point3::new(1.0, 2.0, 3.0)));
+    }
+
+    #[rstest]
+    fn parse_channels() {
+      let bytes = vol(3, 1, [1, 1, 1, 3], [0.0, 0.0, 0.0, 1.0, 1.0, 1.0], &[0.0, 0.5, 1.0]);
+      let (grid, _) = parse_vol(&bytes, "test.vol").unwrap();
+      assert_eq!(grid.max_density(), 0.5);
+    }
+
+    #[rstest]
+    #[case(vol(2, 1, [1, 1, 1, 1], [0.0; 6], &[1.0]))]
+    #[case(vol(3, 2, [1, 1, 1, 1], [0.0; 6], &[1.0]))]
+    #[case(vol(3, 1, [2, 1, 1, 1], [0.0; 6], &[1.0]))]
+    #[case(vol(3, 1, [0, 1, 1, 1], [0.0; 6], &[]))]
+    #[case(vol(3, 1, [1, 1, 1, 1], [0.0; 6], &[-1.0]))]
+    #[case(b"VOX".to_vec())]
+    #[case(vol(3, 1, [i32::MAX, i32::MAX, i32::MAX, i32::MAX], [0.0; 6], &[1.0]))]
+    fn parse_invalid(#[case] bytes: Vec<u8>) {
+      assert!(parse_vol(&bytes, "test.vol").is_err());
+    }
+  }
+
+  mod raw {
+    use super::*;
+
+    #[rstest]
+    fn parse() {
+      let grid = parse_raw(&[0, 255, 51, 0], [2, 2, 1], "test.raw").unwrap();
+      assert_eq!(grid.max_density(), 1.0);
+      assert!((grid.sample(Point3::new(0.25, 0.75, 0.5)) - 0.2).abs() < 1e-12);
+    }
+
+    #[rstest]
+    #[case([2, 2, 1])]
+    #[case([usize::MAX, usize::MAX, 2])]
+    fn parse_invalid(#[case] resolution: [usize; 3]) {
+      assert!(parse_raw(&[0, 255, 51], resolution, "test.raw").is_err());
+    }
+  }
+}